@@ -6,6 +6,7 @@ use opentelemetry::{
 use opentelemetry_sdk::trace::{Config, SpanLimits, Tracer, TracerProvider};
 #[cfg(not(target_os = "windows"))]
 use pprof::criterion::{Output, PProfProfiler};
+use std::borrow::Cow;
 use std::time::SystemTime;
 use tracing::{trace, trace_span};
 use tracing_subscriber::prelude::*;
@@ -53,6 +54,45 @@ fn many_children(c: &mut Criterion) {
 
         group.bench_function("full", |b| b.iter(tracing_harness));
     }
+
+    {
+        let provider = TracerProvider::default();
+        let tracer = provider.tracer("bench");
+        let otel_layer = tracing_opentelemetry::layer()
+            .with_tracer(tracer)
+            .with_tracked_inactivity(false)
+            .with_span_builder_attributes_capacity(2);
+        let _subscriber = tracing_subscriber::registry()
+            .with(otel_layer)
+            .set_default();
+
+        group.bench_function("full_attributes_capacity", |b| {
+            b.iter(set_attributes_harness)
+        });
+    }
+}
+
+fn set_attribute(c: &mut Criterion) {
+    let mut group = c.benchmark_group("otel_set_attribute");
+
+    let provider = TracerProvider::default();
+    let tracer = provider.tracer("bench");
+    let otel_layer = tracing_opentelemetry::layer()
+        .with_tracer(tracer)
+        .with_tracked_inactivity(false);
+    let _subscriber = tracing_subscriber::registry()
+        .with(otel_layer)
+        .set_default();
+
+    group.bench_function("static_str", |b| {
+        b.iter(|| set_attribute_harness(|| "/users/{id}"))
+    });
+    group.bench_function("borrowed_cow", |b| {
+        b.iter(|| set_attribute_harness(|| Cow::Borrowed("/users/{id}")))
+    });
+    group.bench_function("owned_string", |b| {
+        b.iter(|| set_attribute_harness(|| String::from("/users/{id}")))
+    });
 }
 
 fn many_events(c: &mut Criterion) {
@@ -217,6 +257,33 @@ where
     }
 }
 
+fn set_attributes_harness() {
+    use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+    fn dummy() {
+        for _ in 0..99 {
+            let child = trace_span!("child", otel.name = "renamed", user = "alice");
+            child.set_attribute("late.first", 1);
+            child.set_attribute("late.second", 2);
+        }
+    }
+
+    let parent = trace_span!("parent");
+    let _enter = parent.enter();
+    dummy();
+}
+
+fn set_attribute_harness<V: Into<opentelemetry::Value>>(value: impl Fn() -> V) {
+    use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+    let parent = trace_span!("parent");
+    let _enter = parent.enter();
+    for _ in 0..99 {
+        let child = trace_span!("child");
+        child.set_attribute("http.route", value());
+    }
+}
+
 fn tracing_harness() {
     fn dummy() {
         for _ in 0..99 {
@@ -249,12 +316,12 @@ fn events_harness() {
 criterion_group! {
     name = benches;
     config = Criterion::default().with_profiler(PProfProfiler::new(100, Output::Flamegraph(None)));
-    targets = many_children, many_events
+    targets = many_children, set_attribute, many_events
 }
 #[cfg(target_os = "windows")]
 criterion_group! {
     name = benches;
     config = Criterion::default();
-    targets = many_children, many_events
+    targets = many_children, set_attribute, many_events
 }
 criterion_main!(benches);
@@ -0,0 +1,61 @@
+use opentelemetry::{Array, StringValue, Value};
+use std::str::FromStr;
+
+/// The type of an attribute value parsed from a string, see
+/// [`OpenTelemetrySpanExt::set_typed_attribute`].
+///
+/// Array values are parsed from comma-separated elements, ignoring
+/// whitespace around each element.
+///
+/// [`OpenTelemetrySpanExt::set_typed_attribute`]: crate::OpenTelemetrySpanExt::set_typed_attribute
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum AttrType {
+    /// `true` or `false`.
+    Bool,
+    /// A signed 64-bit integer.
+    I64,
+    /// A 64-bit floating point number.
+    F64,
+    /// A string, recorded as is.
+    String,
+    /// An array of booleans.
+    BoolArray,
+    /// An array of signed 64-bit integers.
+    I64Array,
+    /// An array of 64-bit floating point numbers.
+    F64Array,
+    /// An array of strings.
+    StringArray,
+}
+
+impl AttrType {
+    /// Parses `value` as this type, returning `None` if it is invalid.
+    pub(crate) fn parse(&self, value: &str) -> Option<Value> {
+        fn parse_array<T: FromStr>(value: &str) -> Option<Vec<T>> {
+            if value.trim().is_empty() {
+                return Some(Vec::new());
+            }
+            value
+                .split(',')
+                .map(|item| item.trim().parse().ok())
+                .collect()
+        }
+
+        Some(match self {
+            AttrType::Bool => Value::Bool(value.trim().parse().ok()?),
+            AttrType::I64 => Value::I64(value.trim().parse().ok()?),
+            AttrType::F64 => Value::F64(value.trim().parse().ok()?),
+            AttrType::String => Value::String(value.to_owned().into()),
+            AttrType::BoolArray => Value::Array(Array::Bool(parse_array(value)?)),
+            AttrType::I64Array => Value::Array(Array::I64(parse_array(value)?)),
+            AttrType::F64Array => Value::Array(Array::F64(parse_array(value)?)),
+            AttrType::StringArray => Value::Array(Array::String(
+                parse_array::<String>(value)?
+                    .into_iter()
+                    .map(StringValue::from)
+                    .collect(),
+            )),
+        })
+    }
+}
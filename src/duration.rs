@@ -0,0 +1,47 @@
+use std::fmt;
+use std::time::Duration;
+
+/// A [`Duration`] wrapper that renders as a plain integer number of
+/// nanoseconds.
+///
+/// `tracing` has no duration primitive, so durations recorded on events or
+/// spans are usually formatted with their `Debug` implementation (e.g.
+/// `1.5ms`), which exporters cannot parse back into a number. Recording a
+/// `DurationNanos` with the `%` sigil produces a value such as `1500000`
+/// instead.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+/// use tracing_opentelemetry::DurationNanos;
+///
+/// let elapsed = Duration::from_micros(1500);
+/// tracing::info!(elapsed = %DurationNanos(elapsed), "request finished");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct DurationNanos(pub Duration);
+
+impl From<Duration> for DurationNanos {
+    fn from(duration: Duration) -> Self {
+        DurationNanos(duration)
+    }
+}
+
+impl fmt::Display for DurationNanos {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0.as_nanos())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_nanoseconds() {
+        let rendered = DurationNanos(Duration::new(2, 1500)).to_string();
+        assert_eq!(rendered, "2000001500");
+        assert_eq!(rendered.parse::<u128>().unwrap(), 2_000_001_500);
+    }
+}
@@ -0,0 +1,98 @@
+use opentelemetry::trace::{SpanId, TraceId};
+
+/// The format used to render trace and span ids as string attributes.
+///
+/// This controls the `trace_id`, `span_id` and `parent.span_id` style
+/// attributes emitted by the [`OpenTelemetryLayer`], not the ids propagated or
+/// exported as part of the span context.
+///
+/// [`OpenTelemetryLayer`]: crate::OpenTelemetryLayer
+///
+/// # Examples
+///
+/// ```
+/// use opentelemetry::trace::SpanId;
+/// use tracing_opentelemetry::IdFormat;
+///
+/// let id = SpanId::from_hex("00f067aa0ba902b7").unwrap();
+/// assert_eq!(IdFormat::Hex.span_id(id), "00f067aa0ba902b7");
+/// assert_eq!(IdFormat::Decimal.span_id(id), "67667974448284343");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[non_exhaustive]
+pub enum IdFormat {
+    /// Lowercase, zero-padded hexadecimal, as used by W3C trace context.
+    #[default]
+    Hex,
+    /// Unsigned decimal integer.
+    Decimal,
+    /// Standard base64 with padding of the big-endian id bytes.
+    Base64,
+}
+
+impl IdFormat {
+    /// Renders a [`TraceId`] in this format.
+    pub fn trace_id(&self, id: TraceId) -> String {
+        match self {
+            IdFormat::Hex => format!("{:032x}", id),
+            IdFormat::Decimal => u128::from_be_bytes(id.to_bytes()).to_string(),
+            IdFormat::Base64 => base64(&id.to_bytes()),
+        }
+    }
+
+    /// Renders a [`SpanId`] in this format.
+    pub fn span_id(&self, id: SpanId) -> String {
+        match self {
+            IdFormat::Hex => format!("{:016x}", id),
+            IdFormat::Decimal => u64::from_be_bytes(id.to_bytes()).to_string(),
+            IdFormat::Base64 => base64(&id.to_bytes()),
+        }
+    }
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut encoded = String::with_capacity((bytes.len() + 2) / 3 * 4);
+    for chunk in bytes.chunks(3) {
+        let b = [
+            chunk[0],
+            chunk.get(1).copied().unwrap_or(0),
+            chunk.get(2).copied().unwrap_or(0),
+        ];
+        let n = (u32::from(b[0]) << 16) | (u32::from(b[1]) << 8) | u32::from(b[2]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_ids() {
+        let trace_id = TraceId::from_hex("0af7651916cd43dd8448eb211c80319c").unwrap();
+        let span_id = SpanId::from_hex("00f067aa0ba902b7").unwrap();
+
+        assert_eq!(
+            IdFormat::Hex.trace_id(trace_id),
+            "0af7651916cd43dd8448eb211c80319c"
+        );
+        assert_eq!(
+            IdFormat::Decimal.trace_id(trace_id),
+            "14576827793038113322513871894673895836"
+        );
+        assert_eq!(
+            IdFormat::Base64.trace_id(trace_id),
+            "CvdlGRbNQ92ESOshHIAxnA=="
+        );
+        assert_eq!(IdFormat::Base64.span_id(span_id), "APBnqgupArc=");
+    }
+}
@@ -1,17 +1,27 @@
-use crate::{OtelData, PreSampledTracer};
+use crate::{IdFormat, OtelData, PreSampledTracer};
 use once_cell::unsync;
 use opentelemetry::{
+    baggage::BaggageExt,
+    global,
     trace::{self as otel, noop, SpanBuilder, SpanKind, Status, TraceContextExt},
     Context as OtelContext, Key, KeyValue, StringValue, Value,
 };
+use std::backtrace::{Backtrace, BacktraceStatus};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::convert::TryFrom;
 use std::fmt;
+use std::hash::{Hash, Hasher};
 use std::marker;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
 use std::thread;
+use std::time::Duration;
 #[cfg(not(target_arch = "wasm32"))]
 use std::time::Instant;
 use std::{any::TypeId, borrow::Cow};
 use tracing_core::span::{self, Attributes, Id, Record};
-use tracing_core::{field, Event, Subscriber};
+use tracing_core::{field, Event, LevelFilter, Metadata, Subscriber};
 #[cfg(feature = "tracing-log")]
 use tracing_log::NormalizeEvent;
 use tracing_subscriber::layer::Context;
@@ -20,14 +30,49 @@ use tracing_subscriber::Layer;
 #[cfg(target_arch = "wasm32")]
 use web_time::Instant;
 
-const SPAN_NAME_FIELD: &str = "otel.name";
-const SPAN_KIND_FIELD: &str = "otel.kind";
-const SPAN_STATUS_CODE_FIELD: &str = "otel.status_code";
-const SPAN_STATUS_MESSAGE_FIELD: &str = "otel.status_message";
+const SPECIAL_FIELD_PREFIX: &str = "otel.";
+
+// Names of the special fields, relative to the special field prefix.
+const SPAN_NAME_FIELD: &str = "name";
+const SPAN_KIND_FIELD: &str = "kind";
+const SPAN_STATUS_CODE_FIELD: &str = "status_code";
+const SPAN_STATUS_MESSAGE_FIELD: &str = "status_message";
+const SPAN_ID_FIELD: &str = "span_id";
+const SPAN_TRACE_ID_FIELD: &str = "trace_id";
+const SPAN_CODE_COLUMN_FIELD: &str = "code.column";
 
 const EVENT_EXCEPTION_NAME: &str = "exception";
 const FIELD_EXCEPTION_MESSAGE: &str = "exception.message";
 const FIELD_EXCEPTION_STACKTRACE: &str = "exception.stacktrace";
+const FIELD_EXCEPTION_CHAIN: &str = "exception.chain";
+
+const FIELD_EVENT_SEQUENCE: &str = "event.sequence";
+const FIELD_EVENT_COUNT: &str = "event.count";
+const FIELD_SCHEDULING_DELAY: &str = "scheduling.delay_ns";
+const FIELD_APP_STARTUP: &str = "app.startup";
+const FIELD_PROCESS_RUNTIME_NAME: &str = "process.runtime.name";
+const FIELD_PROCESS_RUNTIME_VERSION: &str = "process.runtime.version";
+const FIELD_PROCESS_RUNTIME_DESCRIPTION: &str = "process.runtime.description";
+const FIELD_SPAN_LEVEL: &str = "level";
+const FIELD_LINK_TYPE: &str = "link.type";
+
+const BAGGAGE_ATTRIBUTE_PREFIX: &str = "baggage.";
+
+/// Maximum number of spans walked to find the root of a span's tree, see
+/// [`OpenTelemetrySpanExt::root_context`].
+///
+/// [`OpenTelemetrySpanExt::root_context`]: crate::OpenTelemetrySpanExt::root_context
+const MAX_ROOT_CONTEXT_DEPTH: usize = 128;
+
+const FIELD_CODE_FILEPATH: &str = "code.filepath";
+const FIELD_CODE_NAMESPACE: &str = "code.namespace";
+const FIELD_CODE_LINENO: &str = "code.lineno";
+const FIELD_CODE_FILE_PATH: &str = "code.file.path";
+const FIELD_CODE_LINE_NUMBER: &str = "code.line.number";
+const FIELD_CODE_COLUMN: &str = "code.column";
+const FIELD_CODE_COLUMN_NUMBER: &str = "code.column.number";
+
+const MAX_BUFFERED_SPANS_PER_TRACE: usize = 1024;
 
 /// An [OpenTelemetry] propagation layer for use in a project that uses
 /// [tracing].
@@ -39,11 +84,73 @@ pub struct OpenTelemetryLayer<S, T> {
     location: bool,
     tracked_inactivity: bool,
     with_threads: bool,
+    export_on_error_only: bool,
+    target_versions: HashMap<&'static str, &'static str>,
+    on_enter_attributes: Option<Arc<dyn Fn() -> Vec<KeyValue> + Send + Sync>>,
+    worker_name_fn: Option<Arc<dyn Fn() -> Option<String> + Send + Sync>>,
+    empty_field_markers: bool,
+    filtered_parent_mode: FilteredParentMode,
+    late_reparent_policy: LateReparentPolicy,
+    skip_unsampled_enrichment: bool,
+    span_code_keys: CodeKeys,
+    span_code_column_key: Key,
+    event_code_keys: CodeKeys,
+    inactivity_keys: InactivityKeys,
+    inactivity_unit: InactivityUnit,
+    duration_attribute: bool,
+    event_count_attribute: bool,
+    scheduling_delay_attribute: bool,
+    #[cfg(feature = "json")]
+    json_attribute_bundle: Option<Key>,
+    #[cfg(feature = "json")]
+    json_attribute_bundle_replacing: bool,
+    clock_anomaly_attribute: bool,
+    concurrent_span_safe_timings: bool,
+    unbalanced_exit_reported: AtomicBool,
+    sorted_events: bool,
+    attribute_priority: Vec<&'static str>,
+    kind_inference: bool,
+    span_kind_names: Vec<(&'static str, SpanKind)>,
+    unknown_span_kind_handler: Option<UnknownSpanKindHandler>,
+    max_span_depth: Option<usize>,
+    id_format: IdFormat,
+    span_id_attribute: bool,
+    inherited_attributes_denylist: Option<Vec<Cow<'static, str>>>,
+    dropped_span_callback: Option<DroppedSpanCallback>,
+    trace_context_validation: Option<TraceContextValidation>,
+    event_filter: Option<EventFilter>,
+    event_attribute_filter: Option<EventAttributeFilter>,
+    event_level: bool,
+    message_as_event_name: bool,
+    attribute_namespace: Option<Cow<'static, str>>,
+    special_field_prefix: Cow<'static, str>,
+    exception_event_name: &'static str,
+    event_sequence_numbers: bool,
+    follows_from_link_type: bool,
+    span_target: bool,
+    baggage_attributes: bool,
+    deterministic_thread_ids: Option<DeterministicThreadIds>,
+    eager_span_id: bool,
+    span_level: bool,
+    span_level_key: Key,
+    nonfinite_floats: NonFinite,
+    attributes_headroom: usize,
+    startup_window: Option<StartupWindow>,
+    runtime_attributes: bool,
+    runtime_version: Option<StringValue>,
+    runtime_description: Option<StringValue>,
+    max_attribute_key_length: Option<KeyLengthLimit>,
     sem_conv_config: SemConvConfig,
     get_context: WithContext,
     _registry: marker::PhantomData<S>,
 }
 
+type DroppedSpanCallback = Arc<dyn Fn(&SpanBuilder, DropReason) + Send + Sync>;
+type TraceContextValidation = Arc<dyn Fn(&otel::SpanContext) + Send + Sync>;
+type UnknownSpanKindHandler = Arc<dyn Fn(&str) -> Option<SpanKind> + Send + Sync>;
+type EventFilter = Arc<dyn Fn(&Event<'_>, &Metadata<'_>) -> bool + Send + Sync>;
+type EventAttributeFilter = Arc<dyn Fn(&str) -> bool + Send + Sync>;
+
 impl<S> Default for OpenTelemetryLayer<S, noop::NoopTracer>
 where
     S: Subscriber + for<'span> LookupSpan<'span>,
@@ -53,6 +160,133 @@ where
     }
 }
 
+/// The reason why a span was not exported by an [`OpenTelemetryLayer`].
+///
+/// See [`OpenTelemetryLayer::with_dropped_span_callback`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DropReason {
+    /// The span belongs to a trace without errors, and
+    /// [`OpenTelemetryLayer::with_export_on_error_only`] is enabled.
+    NoErrorInTrace,
+    /// The span could not be buffered because its trace already buffers the
+    /// maximum number of spans, and
+    /// [`OpenTelemetryLayer::with_export_on_error_only`] is enabled.
+    BufferFull,
+    /// The span is nested deeper than the limit set with
+    /// [`OpenTelemetryLayer::with_max_span_depth`]. Such spans are reported
+    /// when they are created, so their data only holds their name and start
+    /// time.
+    MaxDepth,
+}
+
+/// How `f64` field values that are NaN or infinite are recorded as attributes
+/// by an [`OpenTelemetryLayer`].
+///
+/// Some backends reject attributes with non-finite values.
+///
+/// See [`OpenTelemetryLayer::with_nonfinite_float_handling`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum NonFinite {
+    /// Record the value as is.
+    #[default]
+    Keep,
+    /// Record the value as a string, i.e. `NaN`, `inf` or `-inf`.
+    String,
+    /// Do not record the attribute.
+    Drop,
+}
+
+/// How an [`OpenTelemetryLayer`] handles the sampling decision of spans whose
+/// parent is set with [`OpenTelemetrySpanExt::set_parent`] after they were
+/// sampled, e.g. by a call to [`OpenTelemetrySpanExt::context`].
+///
+/// See [`OpenTelemetryLayer::with_late_reparent_policy`].
+///
+/// [`OpenTelemetrySpanExt::set_parent`]: crate::OpenTelemetrySpanExt::set_parent
+/// [`OpenTelemetrySpanExt::context`]: crate::OpenTelemetrySpanExt::context
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum LateReparentPolicy {
+    /// Keep the sampling decision taken before the span was re-parented.
+    KeepDecision,
+    /// Discard the sampling decision, so that the span is sampled again with
+    /// its new parent. This may flip the decision of a span whose context was
+    /// already propagated, e.g. to its children.
+    #[default]
+    Resample,
+}
+
+/// How an [`OpenTelemetryLayer`] handles spans whose explicit parent is
+/// filtered out of the layer, e.g. by a per-layer filter.
+///
+/// See [`OpenTelemetryLayer::with_filtered_parent_mode`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum FilteredParentMode {
+    /// The span becomes the root span of a new trace.
+    #[default]
+    AsRoot,
+    /// The span becomes a child of its nearest ancestor recorded by the
+    /// layer, or a root span if there is none.
+    NearestVisibleAncestor,
+}
+
+/// The attribute names used to record source code locations by an
+/// [`OpenTelemetryLayer`].
+///
+/// See [`OpenTelemetryLayer::with_code_attribute_schema`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum CodeAttributeSchema {
+    /// `code.filepath`, `code.namespace` and `code.lineno`.
+    #[default]
+    Legacy,
+    /// `code.file.path`, `code.namespace` and `code.line.number`, following
+    /// the current OpenTelemetry semantic conventions.
+    Current,
+}
+
+/// The unit of the busy and idle timings recorded by an
+/// [`OpenTelemetryLayer`].
+///
+/// See [`OpenTelemetryLayer::with_inactivity_unit`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum InactivityUnit {
+    /// Integer nanoseconds, recorded as `busy_ns` and `idle_ns`.
+    #[default]
+    Nanos,
+    /// Floating-point seconds, recorded as `busy` and `idle`.
+    Seconds,
+}
+
+impl InactivityUnit {
+    /// Converts a timing in nanoseconds into an attribute value in this unit.
+    fn value(self, nanos: i64) -> Value {
+        match self {
+            InactivityUnit::Nanos => Value::I64(nanos),
+            InactivityUnit::Seconds => Value::F64(nanos as f64 / 1e9),
+        }
+    }
+}
+
+impl NonFinite {
+    /// Converts `value` into an attribute value, or `None` if it should be
+    /// dropped.
+    fn value(self, value: f64) -> Option<Value> {
+        if value.is_finite() {
+            return Some(Value::F64(value));
+        }
+        match self {
+            NonFinite::Keep => Some(Value::F64(value)),
+            NonFinite::String => Some(Value::from(value.to_string())),
+            NonFinite::Drop => None,
+        }
+    }
+}
+
 /// Construct a layer to track spans via [OpenTelemetry].
 ///
 /// [OpenTelemetry]: https://opentelemetry.io
@@ -80,10 +314,15 @@ where
 // types at the callsite.
 //
 // See https://github.com/tokio-rs/tracing/blob/4dad420ee1d4607bad79270c1520673fa6266a3d/tracing-error/src/layer.rs
-pub(crate) struct WithContext(
-    #[allow(clippy::type_complexity)]
-    fn(&tracing::Dispatch, &span::Id, f: &mut dyn FnMut(&mut OtelData, &dyn PreSampledTracer)),
-);
+pub(crate) struct WithContext {
+    get_context: GetContext,
+    get_root_context: GetContext,
+    get_late_reparent_policy: fn(&tracing::Dispatch) -> LateReparentPolicy,
+    validate_parent_context: fn(&tracing::Dispatch, &OtelContext),
+}
+
+type GetContext =
+    fn(&tracing::Dispatch, &span::Id, f: &mut dyn FnMut(&mut OtelData, &dyn PreSampledTracer));
 
 impl WithContext {
     // This function allows a function to be called in the context of the
@@ -94,7 +333,26 @@ impl WithContext {
         id: &span::Id,
         mut f: impl FnMut(&mut OtelData, &dyn PreSampledTracer),
     ) {
-        (self.0)(dispatch, id, &mut f)
+        (self.get_context)(dispatch, id, &mut f)
+    }
+
+    // Same as `with_context`, with the data of the root of the span's tree.
+    pub(crate) fn with_root_context(
+        &self,
+        dispatch: &tracing::Dispatch,
+        id: &span::Id,
+        mut f: impl FnMut(&mut OtelData, &dyn PreSampledTracer),
+    ) {
+        (self.get_root_context)(dispatch, id, &mut f)
+    }
+
+    pub(crate) fn late_reparent_policy(&self, dispatch: &tracing::Dispatch) -> LateReparentPolicy {
+        (self.get_late_reparent_policy)(dispatch)
+    }
+
+    // Reports `cx` to the trace context validation hook if it is invalid.
+    pub(crate) fn validate_parent_context(&self, dispatch: &tracing::Dispatch, cx: &OtelContext) {
+        (self.validate_parent_context)(dispatch, cx)
     }
 }
 
@@ -117,11 +375,65 @@ fn str_to_status(s: &str) -> otel::Status {
     }
 }
 
+fn str_to_span_id(s: &str) -> Option<otel::SpanId> {
+    otel::SpanId::from_hex(s)
+        .ok()
+        .filter(|span_id| *span_id != otel::SpanId::INVALID)
+}
+
+fn str_to_trace_id(s: &str) -> Option<otel::TraceId> {
+    otel::TraceId::from_hex(s)
+        .ok()
+        .filter(|trace_id| *trace_id != otel::TraceId::INVALID)
+}
+
+/// Infers the kind of a span from the semantic convention attributes it
+/// recorded. See [`OpenTelemetryLayer::with_kind_inference`] for the rules.
+fn infer_span_kind(attributes: &[KeyValue]) -> Option<SpanKind> {
+    let get = |key: &str| attributes.iter().find(|kv| kv.key.as_str() == key);
+    let has = |key: &str| get(key).is_some();
+
+    if let Some(operation) = get("messaging.operation") {
+        return match &*operation.value.as_str() {
+            "publish" | "create" => Some(SpanKind::Producer),
+            "receive" | "process" | "deliver" => Some(SpanKind::Consumer),
+            _ => None,
+        };
+    }
+    if has("rpc.system") || has("rpc.service") || has("rpc.method") {
+        return if has("client.address") {
+            Some(SpanKind::Server)
+        } else {
+            Some(SpanKind::Client)
+        };
+    }
+    if has("http.request.method") || has("http.method") {
+        return if has("url.full") || has("http.url") {
+            Some(SpanKind::Client)
+        } else {
+            Some(SpanKind::Server)
+        };
+    }
+    if has("db.system") {
+        return Some(SpanKind::Client);
+    }
+    None
+}
+
 #[derive(Default)]
 struct SpanBuilderUpdates {
     name: Option<Cow<'static, str>>,
     span_kind: Option<SpanKind>,
     status: Option<Status>,
+    status_message: Option<String>,
+    /// Whether `status` was inferred from an error event rather than set
+    /// explicitly, in which case it does not override an `Ok` status.
+    implicit_status: bool,
+    span_id: Option<otel::SpanId>,
+    trace_id: Option<otel::TraceId>,
+    /// Recorded by the layer itself, as the attribute depends on its
+    /// configuration.
+    code_column: Option<i64>,
     attributes: Option<Vec<KeyValue>>,
 }
 
@@ -131,6 +443,11 @@ impl SpanBuilderUpdates {
             name,
             span_kind,
             status,
+            status_message,
+            implicit_status,
+            span_id,
+            trace_id,
+            code_column: _,
             attributes,
         } = self;
 
@@ -140,8 +457,28 @@ impl SpanBuilderUpdates {
         if let Some(span_kind) = span_kind {
             span_builder.span_kind = Some(span_kind);
         }
-        if let Some(status) = status {
-            span_builder.status = status;
+        // The status code and message are recorded by different fields, merge
+        // them so that their order does not matter.
+        match (status, status_message) {
+            _ if implicit_status && span_builder.status == Status::Ok => {}
+            // Keep the description of an existing error status when only the
+            // error code is recorded.
+            (Some(Status::Error { description }), None) => {
+                if !description.is_empty() || !matches!(span_builder.status, Status::Error { .. }) {
+                    span_builder.status = Status::Error { description };
+                }
+            }
+            (Some(Status::Error { .. }) | None, Some(message)) => {
+                span_builder.status = Status::error(message);
+            }
+            (Some(status), _) => span_builder.status = status,
+            (None, None) => {}
+        }
+        if let Some(span_id) = span_id {
+            span_builder.span_id = Some(span_id);
+        }
+        if let Some(trace_id) = trace_id {
+            span_builder.trace_id = Some(trace_id);
         }
         if let Some(attributes) = attributes {
             if let Some(builder_attributes) = &mut span_builder.attributes {
@@ -157,6 +494,40 @@ struct SpanEventVisitor<'a, 'b> {
     event_builder: &'a mut otel::Event,
     span_builder_updates: &'b mut Option<SpanBuilderUpdates>,
     sem_conv_config: SemConvConfig,
+    exception_event_name: &'static str,
+    nonfinite_floats: NonFinite,
+    attribute_filter: Option<&'a (dyn Fn(&str) -> bool + Send + Sync)>,
+    message_as_event_name: bool,
+}
+
+impl<'a, 'b> SpanEventVisitor<'a, 'b> {
+    /// Records the `message` field as the event name, or as an attribute.
+    fn record_message(&mut self, message: String) {
+        if self.message_as_event_name {
+            self.event_builder.name = message.into();
+        } else {
+            self.event_builder
+                .attributes
+                .push(KeyValue::new("message", message));
+        }
+    }
+
+    /// Returns whether a message was recorded for the event.
+    fn has_message(&self) -> bool {
+        if self.message_as_event_name {
+            !self.event_builder.name.is_empty()
+        } else {
+            self.event_builder
+                .attributes
+                .iter()
+                .any(|kv| kv.key.as_str() == "message")
+        }
+    }
+
+    /// Returns whether a field is filtered out of the event attributes.
+    fn skips(&self, name: &str) -> bool {
+        self.attribute_filter.map_or(false, |filter| !filter(name))
+    }
 }
 
 impl<'a, 'b> field::Visit for SpanEventVisitor<'a, 'b> {
@@ -165,10 +536,11 @@ impl<'a, 'b> field::Visit for SpanEventVisitor<'a, 'b> {
     /// [`Span`]: opentelemetry::trace::Span
     fn record_bool(&mut self, field: &field::Field, value: bool) {
         match field.name() {
-            "message" => self.event_builder.name = value.to_string().into(),
+            "message" => self.record_message(value.to_string()),
             // Skip fields that are actually log metadata that have already been handled
             #[cfg(feature = "tracing-log")]
             name if name.starts_with("log.") => (),
+            name if self.skips(name) => (),
             name => {
                 self.event_builder
                     .attributes
@@ -182,14 +554,17 @@ impl<'a, 'b> field::Visit for SpanEventVisitor<'a, 'b> {
     /// [`Span`]: opentelemetry::trace::Span
     fn record_f64(&mut self, field: &field::Field, value: f64) {
         match field.name() {
-            "message" => self.event_builder.name = value.to_string().into(),
+            "message" => self.record_message(value.to_string()),
             // Skip fields that are actually log metadata that have already been handled
             #[cfg(feature = "tracing-log")]
             name if name.starts_with("log.") => (),
+            name if self.skips(name) => (),
             name => {
-                self.event_builder
-                    .attributes
-                    .push(KeyValue::new(name, value));
+                if let Some(value) = self.nonfinite_floats.value(value) {
+                    self.event_builder
+                        .attributes
+                        .push(KeyValue::new(name, value));
+                }
             }
         }
     }
@@ -199,10 +574,11 @@ impl<'a, 'b> field::Visit for SpanEventVisitor<'a, 'b> {
     /// [`Span`]: opentelemetry::trace::Span
     fn record_i64(&mut self, field: &field::Field, value: i64) {
         match field.name() {
-            "message" => self.event_builder.name = value.to_string().into(),
+            "message" => self.record_message(value.to_string()),
             // Skip fields that are actually log metadata that have already been handled
             #[cfg(feature = "tracing-log")]
             name if name.starts_with("log.") => (),
+            name if self.skips(name) => (),
             name => {
                 self.event_builder
                     .attributes
@@ -216,19 +592,20 @@ impl<'a, 'b> field::Visit for SpanEventVisitor<'a, 'b> {
     /// [`Span`]: opentelemetry::trace::Span
     fn record_str(&mut self, field: &field::Field, value: &str) {
         match field.name() {
-            "message" => self.event_builder.name = value.to_string().into(),
+            "message" => self.record_message(value.to_string()),
             // While tracing supports the error primitive, the instrumentation macro does not
             // use the primitive and instead uses the debug or display primitive.
             // In both cases, an event with an empty name and with an error attribute is created.
-            "error" if self.event_builder.name.is_empty() => {
+            "error" if !self.has_message() => {
                 if self.sem_conv_config.error_events_to_status {
-                    self.span_builder_updates
-                        .get_or_insert_with(SpanBuilderUpdates::default)
-                        .status
-                        .replace(otel::Status::error(format!("{:?}", value)));
+                    let updates = self
+                        .span_builder_updates
+                        .get_or_insert_with(SpanBuilderUpdates::default);
+                    updates.status = Some(otel::Status::error(format!("{:?}", value)));
+                    updates.implicit_status = true;
                 }
                 if self.sem_conv_config.error_events_to_exceptions {
-                    self.event_builder.name = EVENT_EXCEPTION_NAME.into();
+                    self.event_builder.name = self.exception_event_name.into();
                     self.event_builder.attributes.push(KeyValue::new(
                         FIELD_EXCEPTION_MESSAGE,
                         format!("{:?}", value),
@@ -242,6 +619,7 @@ impl<'a, 'b> field::Visit for SpanEventVisitor<'a, 'b> {
             // Skip fields that are actually log metadata that have already been handled
             #[cfg(feature = "tracing-log")]
             name if name.starts_with("log.") => (),
+            name if self.skips(name) => (),
             name => {
                 self.event_builder
                     .attributes
@@ -256,19 +634,20 @@ impl<'a, 'b> field::Visit for SpanEventVisitor<'a, 'b> {
     /// [`Span`]: opentelemetry::trace::Span
     fn record_debug(&mut self, field: &field::Field, value: &dyn fmt::Debug) {
         match field.name() {
-            "message" => self.event_builder.name = format!("{:?}", value).into(),
+            "message" => self.record_message(format!("{:?}", value)),
             // While tracing supports the error primitive, the instrumentation macro does not
             // use the primitive and instead uses the debug or display primitive.
             // In both cases, an event with an empty name and with an error attribute is created.
-            "error" if self.event_builder.name.is_empty() => {
+            "error" if !self.has_message() => {
                 if self.sem_conv_config.error_events_to_status {
-                    self.span_builder_updates
-                        .get_or_insert_with(SpanBuilderUpdates::default)
-                        .status
-                        .replace(otel::Status::error(format!("{:?}", value)));
+                    let updates = self
+                        .span_builder_updates
+                        .get_or_insert_with(SpanBuilderUpdates::default);
+                    updates.status = Some(otel::Status::error(format!("{:?}", value)));
+                    updates.implicit_status = true;
                 }
                 if self.sem_conv_config.error_events_to_exceptions {
-                    self.event_builder.name = EVENT_EXCEPTION_NAME.into();
+                    self.event_builder.name = self.exception_event_name.into();
                     self.event_builder.attributes.push(KeyValue::new(
                         FIELD_EXCEPTION_MESSAGE,
                         format!("{:?}", value),
@@ -282,6 +661,7 @@ impl<'a, 'b> field::Visit for SpanEventVisitor<'a, 'b> {
             // Skip fields that are actually log metadata that have already been handled
             #[cfg(feature = "tracing-log")]
             name if name.starts_with("log.") => (),
+            name if self.skips(name) => (),
             name => {
                 self.event_builder
                     .attributes
@@ -303,26 +683,32 @@ impl<'a, 'b> field::Visit for SpanEventVisitor<'a, 'b> {
         let mut next_err = value.source();
 
         while let Some(err) = next_err {
-            chain.push(err.to_string().into());
+            chain.push(
+                truncate_error_message(
+                    err.to_string(),
+                    self.sem_conv_config.max_error_message_length,
+                )
+                .into(),
+            );
             next_err = err.source();
         }
 
-        let error_msg = value.to_string();
+        let error_msg = truncate_error_message(
+            value.to_string(),
+            self.sem_conv_config.max_error_message_length,
+        );
+        let backtrace = self
+            .sem_conv_config
+            .error_backtrace_capture
+            .then(capture_backtrace);
 
         if self.sem_conv_config.error_fields_to_exceptions {
             self.event_builder
                 .attributes
                 .push(Key::new(FIELD_EXCEPTION_MESSAGE).string(error_msg.clone()));
-
-            // NOTE: This is actually not the stacktrace of the exception. This is
-            // the "source chain". It represents the heirarchy of errors from the
-            // app level to the lowest level such as IO. It does not represent all
-            // of the callsites in the code that led to the error happening.
-            // `std::error::Error::backtrace` is a nightly-only API and cannot be
-            // used here until the feature is stabilized.
             self.event_builder
                 .attributes
-                .push(Key::new(FIELD_EXCEPTION_STACKTRACE).array(chain.clone()));
+                .extend(exception_trace(&chain, &backtrace));
         }
 
         if self.sem_conv_config.error_records_to_exceptions {
@@ -336,19 +722,12 @@ impl<'a, 'b> field::Visit for SpanEventVisitor<'a, 'b> {
                 FIELD_EXCEPTION_MESSAGE,
                 Value::String(error_msg.clone().into()),
             ));
-
-            // NOTE: This is actually not the stacktrace of the exception. This is
-            // the "source chain". It represents the heirarchy of errors from the
-            // app level to the lowest level such as IO. It does not represent all
-            // of the callsites in the code that led to the error happening.
-            // `std::error::Error::backtrace` is a nightly-only API and cannot be
-            // used here until the feature is stabilized.
-            attributes.push(KeyValue::new(
-                FIELD_EXCEPTION_STACKTRACE,
-                Value::Array(chain.clone().into()),
-            ));
+            attributes.extend(exception_trace(&chain, &backtrace));
         }
 
+        if self.skips(field.name()) {
+            return;
+        }
         self.event_builder
             .attributes
             .push(Key::new(field.name()).string(error_msg));
@@ -358,6 +737,74 @@ impl<'a, 'b> field::Visit for SpanEventVisitor<'a, 'b> {
     }
 }
 
+/// Serializes attributes as a JSON object.
+#[cfg(feature = "json")]
+fn json_attribute_bundle<'a>(attributes: impl Iterator<Item = &'a KeyValue>) -> String {
+    use opentelemetry::Array;
+    use serde_json::Value as Json;
+
+    fn strings(values: &[StringValue]) -> Json {
+        values.iter().map(|value| value.as_str()).collect()
+    }
+
+    let bundle = attributes
+        .map(|kv| {
+            let value = match &kv.value {
+                Value::Bool(value) => Json::from(*value),
+                Value::I64(value) => Json::from(*value),
+                Value::F64(value) => Json::from(*value),
+                Value::String(value) => Json::from(value.as_str()),
+                Value::Array(Array::Bool(values)) => Json::from(values.clone()),
+                Value::Array(Array::I64(values)) => Json::from(values.clone()),
+                Value::Array(Array::F64(values)) => Json::from(values.clone()),
+                Value::Array(Array::String(values)) => strings(values),
+            };
+            (kv.key.to_string(), value)
+        })
+        .collect::<serde_json::Map<_, _>>();
+    Json::Object(bundle).to_string()
+}
+
+/// Truncates the message of an error to at most `max_length` bytes.
+fn truncate_error_message(mut message: String, max_length: Option<usize>) -> String {
+    if let Some(max_length) = max_length.filter(|max_length| message.len() > *max_length) {
+        let mut end = max_length;
+        while !message.is_char_boundary(end) {
+            end -= 1;
+        }
+        message.truncate(end);
+    }
+    message
+}
+
+/// Captures the backtrace of the current thread, if enabled with the
+/// `RUST_BACKTRACE` or `RUST_LIB_BACKTRACE` environment variables.
+fn capture_backtrace() -> Option<String> {
+    let backtrace = Backtrace::capture();
+    (backtrace.status() == BacktraceStatus::Captured).then(|| backtrace.to_string())
+}
+
+/// Returns the attributes locating an error recorded as an exception.
+///
+/// `backtrace` is `None` when backtraces are not captured, and `Some(None)`
+/// when they are captured but disabled by the environment.
+fn exception_trace(chain: &[StringValue], backtrace: &Option<Option<String>>) -> Vec<KeyValue> {
+    match backtrace {
+        // NOTE: This is actually not the stacktrace of the exception. This is
+        // the "source chain". It represents the heirarchy of errors from the
+        // app level to the lowest level such as IO. It does not represent all
+        // of the callsites in the code that led to the error happening.
+        None => vec![Key::new(FIELD_EXCEPTION_STACKTRACE).array(chain.to_vec())],
+        Some(backtrace) => {
+            let mut attributes = vec![Key::new(FIELD_EXCEPTION_CHAIN).array(chain.to_vec())];
+            if let Some(backtrace) = backtrace {
+                attributes.push(KeyValue::new(FIELD_EXCEPTION_STACKTRACE, backtrace.clone()));
+            }
+            attributes
+        }
+    }
+}
+
 /// Control over the mapping between tracing fields/events and OpenTelemetry conventional status/exception fields
 #[derive(Clone, Copy)]
 struct SemConvConfig {
@@ -383,6 +830,24 @@ struct SemConvConfig {
     /// This is not affected by this setting. Disabling this will only affect the span status.
     error_events_to_status: bool,
 
+    /// If an event with both a `message` and an `error` field is propagated
+    /// to the span status, should the status description be the message
+    /// rather than the error.
+    error_status_description_from_message: bool,
+
+    /// If an error value is recorded as an exception, should a backtrace be
+    /// captured as its stacktrace, with the source chain moved to
+    /// `exception.chain`.
+    error_backtrace_capture: bool,
+
+    /// Events at or above this level set the status of their span to "Error",
+    /// if it is unset.
+    status_from_level: LevelFilter,
+
+    /// Maximum length of the message of an error value, and of each error
+    /// of its source chain.
+    max_error_message_length: Option<usize>,
+
     /// If an event with an empty name and a field named `error` is recorded,
     /// should the event be rewritten to have the name `exception` and the field `exception.message`
     ///
@@ -396,14 +861,47 @@ struct SemConvConfig {
 struct SpanAttributeVisitor<'a> {
     span_builder_updates: &'a mut SpanBuilderUpdates,
     sem_conv_config: SemConvConfig,
+    attribute_namespace: Option<&'a str>,
+    special_field_prefix: &'a str,
+    nonfinite_floats: NonFinite,
+    key_length_limit: Option<&'a KeyLengthLimit>,
+    unknown_span_kind_handler: Option<&'a UnknownSpanKindHandler>,
 }
 
 impl<'a> SpanAttributeVisitor<'a> {
+    /// Parses the value of an `otel.kind` field, falling back to the unknown
+    /// span kind handler for non-standard kinds.
+    fn span_kind(&self, value: &str) -> Option<SpanKind> {
+        str_to_span_kind(value).or_else(|| {
+            let kind = (self.unknown_span_kind_handler?)(value);
+            if cfg!(debug_assertions) && kind.is_none() {
+                global::handle_error(global::Error::Other(format!(
+                    "[tracing-opentelemetry]: `{}` is not a known span kind",
+                    value
+                )));
+            }
+            kind
+        })
+    }
+
     fn record(&mut self, attribute: KeyValue) {
+        let key = match self.key_length_limit {
+            Some(limit) => limit.truncate(&attribute.key).unwrap_or(attribute.key),
+            None => attribute.key,
+        };
         self.span_builder_updates
             .attributes
             .get_or_insert_with(Vec::new)
-            .push(KeyValue::new(attribute.key, attribute.value));
+            .push(KeyValue::new(key, attribute.value));
+    }
+
+    /// Returns the attribute key for a user-recorded field, prefixed with the
+    /// configured namespace.
+    fn field_key(&self, name: &'static str) -> Key {
+        match self.attribute_namespace {
+            Some(namespace) => Key::new(format!("{}{}", namespace, name)),
+            None => Key::new(name),
+        }
     }
 }
 
@@ -412,35 +910,54 @@ impl<'a> field::Visit for SpanAttributeVisitor<'a> {
     ///
     /// [`Span`]: opentelemetry::trace::Span
     fn record_bool(&mut self, field: &field::Field, value: bool) {
-        self.record(KeyValue::new(field.name(), value));
+        self.record(KeyValue::new(self.field_key(field.name()), value));
     }
 
     /// Set attributes on the underlying OpenTelemetry [`Span`] from `f64` values.
     ///
     /// [`Span`]: opentelemetry::trace::Span
     fn record_f64(&mut self, field: &field::Field, value: f64) {
-        self.record(KeyValue::new(field.name(), value));
+        if let Some(value) = self.nonfinite_floats.value(value) {
+            self.record(KeyValue::new(self.field_key(field.name()), value));
+        }
     }
 
     /// Set attributes on the underlying OpenTelemetry [`Span`] from `i64` values.
     ///
     /// [`Span`]: opentelemetry::trace::Span
     fn record_i64(&mut self, field: &field::Field, value: i64) {
-        self.record(KeyValue::new(field.name(), value));
+        match field.name().strip_prefix(self.special_field_prefix) {
+            Some(SPAN_CODE_COLUMN_FIELD) => self.span_builder_updates.code_column = Some(value),
+            _ => self.record(KeyValue::new(self.field_key(field.name()), value)),
+        }
     }
 
     /// Set attributes on the underlying OpenTelemetry [`Span`] from `&str` values.
     ///
     /// [`Span`]: opentelemetry::trace::Span
     fn record_str(&mut self, field: &field::Field, value: &str) {
-        match field.name() {
-            SPAN_NAME_FIELD => self.span_builder_updates.name = Some(value.to_string().into()),
-            SPAN_KIND_FIELD => self.span_builder_updates.span_kind = str_to_span_kind(value),
-            SPAN_STATUS_CODE_FIELD => self.span_builder_updates.status = Some(str_to_status(value)),
-            SPAN_STATUS_MESSAGE_FIELD => {
-                self.span_builder_updates.status = Some(otel::Status::error(value.to_string()))
+        match field.name().strip_prefix(self.special_field_prefix) {
+            Some(SPAN_NAME_FIELD) => {
+                self.span_builder_updates.name = Some(value.to_string().into())
+            }
+            Some(SPAN_KIND_FIELD) => self.span_builder_updates.span_kind = self.span_kind(value),
+            Some(SPAN_STATUS_CODE_FIELD) => {
+                self.span_builder_updates.status = Some(str_to_status(value))
             }
-            _ => self.record(KeyValue::new(field.name(), value.to_string())),
+            Some(SPAN_STATUS_MESSAGE_FIELD) => {
+                self.span_builder_updates.status_message = Some(value.to_string())
+            }
+            Some(SPAN_ID_FIELD) => self.span_builder_updates.span_id = str_to_span_id(value),
+            Some(SPAN_TRACE_ID_FIELD) => {
+                self.span_builder_updates.trace_id = str_to_trace_id(value)
+            }
+            Some(SPAN_CODE_COLUMN_FIELD) => {
+                self.span_builder_updates.code_column = value.parse().ok()
+            }
+            _ => self.record(KeyValue::new(
+                self.field_key(field.name()),
+                value.to_string(),
+            )),
         }
     }
 
@@ -449,18 +966,29 @@ impl<'a> field::Visit for SpanAttributeVisitor<'a> {
     ///
     /// [`Span`]: opentelemetry::trace::Span
     fn record_debug(&mut self, field: &field::Field, value: &dyn fmt::Debug) {
-        match field.name() {
-            SPAN_NAME_FIELD => self.span_builder_updates.name = Some(format!("{:?}", value).into()),
-            SPAN_KIND_FIELD => {
-                self.span_builder_updates.span_kind = str_to_span_kind(&format!("{:?}", value))
+        match field.name().strip_prefix(self.special_field_prefix) {
+            Some(SPAN_NAME_FIELD) => {
+                self.span_builder_updates.name = Some(format!("{:?}", value).into())
             }
-            SPAN_STATUS_CODE_FIELD => {
+            Some(SPAN_KIND_FIELD) => {
+                self.span_builder_updates.span_kind = self.span_kind(&format!("{:?}", value))
+            }
+            Some(SPAN_STATUS_CODE_FIELD) => {
                 self.span_builder_updates.status = Some(str_to_status(&format!("{:?}", value)))
             }
-            SPAN_STATUS_MESSAGE_FIELD => {
-                self.span_builder_updates.status = Some(otel::Status::error(format!("{:?}", value)))
+            Some(SPAN_STATUS_MESSAGE_FIELD) => {
+                self.span_builder_updates.status_message = Some(format!("{:?}", value))
+            }
+            Some(SPAN_ID_FIELD) => {
+                self.span_builder_updates.span_id = str_to_span_id(&format!("{:?}", value))
+            }
+            Some(SPAN_TRACE_ID_FIELD) => {
+                self.span_builder_updates.trace_id = str_to_trace_id(&format!("{:?}", value))
+            }
+            Some(SPAN_CODE_COLUMN_FIELD) => {
+                self.span_builder_updates.code_column = format!("{:?}", value).parse().ok()
             }
-            _ => self.record(Key::new(field.name()).string(format!("{:?}", value))),
+            _ => self.record(self.field_key(field.name()).string(format!("{:?}", value))),
         }
     }
 
@@ -477,26 +1005,35 @@ impl<'a> field::Visit for SpanAttributeVisitor<'a> {
         let mut next_err = value.source();
 
         while let Some(err) = next_err {
-            chain.push(err.to_string().into());
+            chain.push(
+                truncate_error_message(
+                    err.to_string(),
+                    self.sem_conv_config.max_error_message_length,
+                )
+                .into(),
+            );
             next_err = err.source();
         }
 
-        let error_msg = value.to_string();
+        let error_msg = truncate_error_message(
+            value.to_string(),
+            self.sem_conv_config.max_error_message_length,
+        );
 
         if self.sem_conv_config.error_fields_to_exceptions {
             self.record(Key::new(FIELD_EXCEPTION_MESSAGE).string(error_msg.clone()));
-
-            // NOTE: This is actually not the stacktrace of the exception. This is
-            // the "source chain". It represents the heirarchy of errors from the
-            // app level to the lowest level such as IO. It does not represent all
-            // of the callsites in the code that led to the error happening.
-            // `std::error::Error::backtrace` is a nightly-only API and cannot be
-            // used here until the feature is stabilized.
-            self.record(Key::new(FIELD_EXCEPTION_STACKTRACE).array(chain.clone()));
+            let backtrace = self
+                .sem_conv_config
+                .error_backtrace_capture
+                .then(capture_backtrace);
+            for attribute in exception_trace(&chain, &backtrace) {
+                self.record(attribute);
+            }
         }
 
-        self.record(Key::new(field.name()).string(error_msg));
-        self.record(Key::new(format!("{}.chain", field.name())).array(chain));
+        let key = self.field_key(field.name());
+        self.record(Key::new(format!("{}.chain", key)).array(chain));
+        self.record(key.string(error_msg));
     }
 }
 
@@ -538,18 +1075,120 @@ where
             location: true,
             tracked_inactivity: true,
             with_threads: true,
+            export_on_error_only: false,
+            target_versions: HashMap::new(),
+            on_enter_attributes: None,
+            worker_name_fn: None,
+            empty_field_markers: false,
+            filtered_parent_mode: FilteredParentMode::default(),
+            late_reparent_policy: LateReparentPolicy::Resample,
+            skip_unsampled_enrichment: false,
+            span_code_keys: CodeKeys::default(),
+            span_code_column_key: Key::from_static_str(FIELD_CODE_COLUMN),
+            event_code_keys: CodeKeys::default(),
+            inactivity_keys: InactivityKeys::default(),
+            inactivity_unit: InactivityUnit::default(),
+            duration_attribute: false,
+            event_count_attribute: false,
+            scheduling_delay_attribute: false,
+            #[cfg(feature = "json")]
+            json_attribute_bundle: None,
+            #[cfg(feature = "json")]
+            json_attribute_bundle_replacing: false,
+            clock_anomaly_attribute: false,
+            concurrent_span_safe_timings: false,
+            unbalanced_exit_reported: AtomicBool::new(false),
+            sorted_events: false,
+            attribute_priority: Vec::new(),
+            kind_inference: false,
+            span_kind_names: Vec::new(),
+            unknown_span_kind_handler: None,
+            max_span_depth: None,
+            id_format: IdFormat::Hex,
+            span_id_attribute: false,
+            inherited_attributes_denylist: None,
+            dropped_span_callback: None,
+            trace_context_validation: None,
+            event_filter: None,
+            event_attribute_filter: None,
+            event_level: true,
+            message_as_event_name: true,
+            attribute_namespace: None,
+            special_field_prefix: Cow::Borrowed(SPECIAL_FIELD_PREFIX),
+            exception_event_name: EVENT_EXCEPTION_NAME,
+            event_sequence_numbers: false,
+            follows_from_link_type: false,
+            span_target: false,
+            baggage_attributes: false,
+            deterministic_thread_ids: None,
+            eager_span_id: true,
+            span_level: false,
+            span_level_key: Key::from_static_str(FIELD_SPAN_LEVEL),
+            nonfinite_floats: NonFinite::Keep,
+            attributes_headroom: 0,
+            startup_window: None,
+            runtime_attributes: false,
+            runtime_version: None,
+            runtime_description: None,
+            max_attribute_key_length: None,
             sem_conv_config: SemConvConfig {
                 error_fields_to_exceptions: true,
                 error_records_to_exceptions: true,
                 error_events_to_exceptions: true,
                 error_events_to_status: true,
+                error_status_description_from_message: false,
+                error_backtrace_capture: false,
+                status_from_level: LevelFilter::ERROR,
+                max_error_message_length: None,
             },
 
-            get_context: WithContext(Self::get_context),
+            get_context: WithContext {
+                get_context: Self::get_context,
+                get_root_context: Self::get_root_context,
+                get_late_reparent_policy: Self::get_late_reparent_policy,
+                validate_parent_context: Self::validate_parent_context,
+            },
             _registry: marker::PhantomData,
         }
     }
 
+    /// Create a layer using the given [`Tracer`] that only records the fields
+    /// explicitly recorded on spans and events.
+    ///
+    /// This disables all attributes added by the layer itself: source code
+    /// locations (see [`OpenTelemetryLayer::with_location`]), thread
+    /// information (see [`OpenTelemetryLayer::with_threads`]), busy and idle
+    /// timings (see [`OpenTelemetryLayer::with_tracked_inactivity`]) and the
+    /// `level` attribute of events (see
+    /// [`OpenTelemetryLayer::with_event_level`]). This is the lowest-overhead configuration
+    /// of the layer. Individual attributes can be enabled again with the
+    /// corresponding builder methods.
+    ///
+    /// [`Tracer`]: opentelemetry::trace::Tracer
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use opentelemetry::trace::noop::NoopTracer;
+    /// use tracing_opentelemetry::OpenTelemetryLayer;
+    /// use tracing_subscriber::layer::SubscriberExt;
+    /// use tracing_subscriber::Registry;
+    ///
+    /// let otel_layer = OpenTelemetryLayer::minimal(NoopTracer::new());
+    ///
+    /// let subscriber = Registry::default().with(otel_layer);
+    /// # drop(subscriber);
+    /// ```
+    pub fn minimal(tracer: T) -> Self {
+        OpenTelemetryLayer {
+            location: false,
+            tracked_inactivity: false,
+            with_threads: false,
+            event_level: false,
+            ..Self::new(tracer)
+        }
+    }
+
     /// Set the [`Tracer`] that this layer will use to produce and track
     /// OpenTelemetry [`Span`]s.
     ///
@@ -585,8 +1224,69 @@ where
             location: self.location,
             tracked_inactivity: self.tracked_inactivity,
             with_threads: self.with_threads,
+            export_on_error_only: self.export_on_error_only,
+            target_versions: self.target_versions,
+            on_enter_attributes: self.on_enter_attributes,
+            worker_name_fn: self.worker_name_fn,
+            empty_field_markers: self.empty_field_markers,
+            filtered_parent_mode: self.filtered_parent_mode,
+            late_reparent_policy: self.late_reparent_policy,
+            skip_unsampled_enrichment: self.skip_unsampled_enrichment,
+            span_code_keys: self.span_code_keys,
+            span_code_column_key: self.span_code_column_key,
+            event_code_keys: self.event_code_keys,
+            inactivity_keys: self.inactivity_keys,
+            inactivity_unit: self.inactivity_unit,
+            duration_attribute: self.duration_attribute,
+            event_count_attribute: self.event_count_attribute,
+            scheduling_delay_attribute: self.scheduling_delay_attribute,
+            #[cfg(feature = "json")]
+            json_attribute_bundle: self.json_attribute_bundle,
+            #[cfg(feature = "json")]
+            json_attribute_bundle_replacing: self.json_attribute_bundle_replacing,
+            clock_anomaly_attribute: self.clock_anomaly_attribute,
+            concurrent_span_safe_timings: self.concurrent_span_safe_timings,
+            unbalanced_exit_reported: self.unbalanced_exit_reported,
+            sorted_events: self.sorted_events,
+            attribute_priority: self.attribute_priority,
+            kind_inference: self.kind_inference,
+            span_kind_names: self.span_kind_names,
+            unknown_span_kind_handler: self.unknown_span_kind_handler,
+            max_span_depth: self.max_span_depth,
+            id_format: self.id_format,
+            span_id_attribute: self.span_id_attribute,
+            inherited_attributes_denylist: self.inherited_attributes_denylist,
+            dropped_span_callback: self.dropped_span_callback,
+            trace_context_validation: self.trace_context_validation,
+            event_filter: self.event_filter,
+            event_attribute_filter: self.event_attribute_filter,
+            event_level: self.event_level,
+            message_as_event_name: self.message_as_event_name,
+            attribute_namespace: self.attribute_namespace,
+            special_field_prefix: self.special_field_prefix,
+            exception_event_name: self.exception_event_name,
+            event_sequence_numbers: self.event_sequence_numbers,
+            follows_from_link_type: self.follows_from_link_type,
+            span_target: self.span_target,
+            baggage_attributes: self.baggage_attributes,
+            deterministic_thread_ids: self.deterministic_thread_ids,
+            eager_span_id: self.eager_span_id,
+            span_level: self.span_level,
+            span_level_key: self.span_level_key,
+            nonfinite_floats: self.nonfinite_floats,
+            attributes_headroom: self.attributes_headroom,
+            startup_window: self.startup_window,
+            runtime_attributes: self.runtime_attributes,
+            runtime_version: self.runtime_version,
+            runtime_description: self.runtime_description,
+            max_attribute_key_length: self.max_attribute_key_length,
             sem_conv_config: self.sem_conv_config,
-            get_context: WithContext(OpenTelemetryLayer::<S, Tracer>::get_context),
+            get_context: WithContext {
+                get_context: OpenTelemetryLayer::<S, Tracer>::get_context,
+                get_root_context: OpenTelemetryLayer::<S, Tracer>::get_root_context,
+                get_late_reparent_policy: OpenTelemetryLayer::<S, Tracer>::get_late_reparent_policy,
+                validate_parent_context: OpenTelemetryLayer::<S, Tracer>::validate_parent_context,
+            },
             _registry: self._registry,
         }
     }
@@ -660,12 +1360,99 @@ where
         }
     }
 
+    /// Sets whether or not a backtrace is captured when an error value is
+    /// recorded as an exception (see
+    /// [`OpenTelemetryLayer::with_error_fields_to_exceptions`]).
+    ///
+    /// By default, the `exception.stacktrace` attribute holds the source chain
+    /// of the error, as `std::error::Error` does not expose its backtrace on
+    /// stable Rust. When enabled, the backtrace of the thread recording the
+    /// error is recorded as `exception.stacktrace` instead, and the source
+    /// chain as `exception.chain`. Backtraces are only captured when enabled
+    /// with the `RUST_BACKTRACE` or `RUST_LIB_BACKTRACE` environment
+    /// variables, see [`Backtrace::capture`] for details.
+    ///
+    /// By default, backtraces are not captured.
+    pub fn with_error_backtrace_capture(self, error_backtrace_capture: bool) -> Self {
+        Self {
+            sem_conv_config: SemConvConfig {
+                error_backtrace_capture,
+                ..self.sem_conv_config
+            },
+            ..self
+        }
+    }
+
+    /// Truncates the messages of error values recorded as fields to at most
+    /// `max_length` bytes.
+    ///
+    /// This applies to the message of the error itself, including when it is
+    /// recorded as `exception.message`, and to each error of its source chain.
+    /// Verbose errors, e.g. embedding a response body, can otherwise produce
+    /// attributes too large for exporters.
+    ///
+    /// By default, error messages are not truncated.
+    pub fn with_max_error_message_length(self, max_length: usize) -> Self {
+        Self {
+            sem_conv_config: SemConvConfig {
+                max_error_message_length: Some(max_length),
+                ..self.sem_conv_config
+            },
+            ..self
+        }
+    }
+
+    /// Sets the level at or above which events set the status of their span
+    /// to an error with an empty description, if the status is unset.
+    ///
+    /// E.g. with [`LevelFilter::WARN`], both `WARN` and `ERROR` events mark
+    /// their span as failed. [`LevelFilter::OFF`] disables this mapping, in
+    /// which case the status is only set by fields such as
+    /// `otel.status_code`, or by error events (see
+    /// [`OpenTelemetryLayer::with_error_events_to_status`]).
+    ///
+    /// By default, only `ERROR` events set the span status.
+    ///
+    /// [`LevelFilter::WARN`]: tracing_core::LevelFilter::WARN
+    /// [`LevelFilter::OFF`]: tracing_core::LevelFilter::OFF
+    pub fn with_status_from_level(self, status_from_level: LevelFilter) -> Self {
+        Self {
+            sem_conv_config: SemConvConfig {
+                status_from_level,
+                ..self.sem_conv_config
+            },
+            ..self
+        }
+    }
+
+    /// Sets whether or not events with both a `message` and an `error` field
+    /// set the span status to an error described by the message, when
+    /// [`OpenTelemetryLayer::with_error_events_to_status`] is enabled.
+    ///
+    /// This is useful when the message describes the failed operation better
+    /// than the error itself, e.g. `error!(error = %e, "failed to load config")`.
+    ///
+    /// By default, only events without a message use their error as status
+    /// description.
+    pub fn with_error_status_description_from_message(
+        self,
+        error_status_description_from_message: bool,
+    ) -> Self {
+        Self {
+            sem_conv_config: SemConvConfig {
+                error_status_description_from_message,
+                ..self.sem_conv_config
+            },
+            ..self
+        }
+    }
+
     /// Sets whether or not a subset of events following the described schema are mapped to
     /// events following the [OpenTelemetry semantic conventions for
     /// exceptions][conv].
     ///
     /// * Only events without a message field (unnamed events) and at least one field with the name error
-    /// are considered for mapping.
+    ///   are considered for mapping.
     ///
     /// By default, these events are mapped.
     ///
@@ -680,6 +1467,18 @@ where
         }
     }
 
+    /// Sets the name of the events produced by mapping error events to
+    /// exceptions, see [`OpenTelemetryLayer::with_error_events_to_exceptions`].
+    ///
+    /// By default, these events are named `exception`, following the
+    /// OpenTelemetry semantic conventions.
+    pub fn with_exception_event_name(self, exception_event_name: &'static str) -> Self {
+        Self {
+            exception_event_name,
+            ..self
+        }
+    }
+
     /// Sets whether or not reporting an `Error` value on an event will
     /// propagate the OpenTelemetry exception fields such as `exception.message`
     /// and `exception.backtrace` to the corresponding span. You do not need to
@@ -738,7 +1537,8 @@ where
     /// attributes with location information, such as the file, module and line number.
     ///
     /// These attributes follow the [OpenTelemetry semantic conventions for
-    /// source locations][conv].
+    /// source locations][conv]. `tracing` does not record columns, spans only
+    /// include one when it is set with the `otel.code.column` special field.
     ///
     /// By default, locations are enabled.
     ///
@@ -777,276 +1577,1763 @@ where
         }
     }
 
-    /// Sets whether or not spans record additional attributes for the thread
-    /// name and thread ID of the thread they were created on, following the
-    /// [OpenTelemetry semantic conventions for threads][conv].
+    /// Sets the attribute keys used to record busy and idle timings, when
+    /// [`OpenTelemetryLayer::with_tracked_inactivity`] is enabled.
     ///
-    /// By default, thread attributes are enabled.
+    /// Passing an empty key skips recording the corresponding timing.
     ///
-    /// [conv]: https://github.com/open-telemetry/semantic-conventions/blob/main/docs/general/attributes.md#general-thread-attributes/
-    pub fn with_threads(self, threads: bool) -> Self {
+    /// By default, the `busy_ns` and `idle_ns` keys are used.
+    pub fn with_inactivity_attribute_names(
+        self,
+        busy: impl Into<Key>,
+        idle: impl Into<Key>,
+    ) -> Self {
         Self {
-            with_threads: threads,
+            inactivity_keys: InactivityKeys {
+                busy: busy.into(),
+                idle: idle.into(),
+            },
             ..self
         }
     }
 
-    /// Retrieve the parent OpenTelemetry [`Context`] from the current tracing
-    /// [`span`] through the [`Registry`]. This [`Context`] links spans to their
-    /// parent for proper hierarchical visualization.
+    /// Sets the unit of the busy and idle timings, when
+    /// [`OpenTelemetryLayer::with_tracked_inactivity`] is enabled.
     ///
-    /// [`Context`]: opentelemetry::Context
-    /// [`span`]: tracing::Span
-    /// [`Registry`]: tracing_subscriber::Registry
-    fn parent_context(&self, attrs: &Attributes<'_>, ctx: &Context<'_, S>) -> OtelContext {
-        if let Some(parent) = attrs.parent() {
-            // A span can have an _explicit_ parent that is NOT seen by this `Layer` (for which
-            // `Context::span` returns `None`. This happens if the parent span is filtered away
-            // from the layer by a per-layer filter. In that case, we fall-through to the `else`
-            // case, and consider this span a root span.
-            //
-            // This is likely rare, as most users who use explicit parents will configure their
-            // filters so that children and parents are both seen, but it's not guaranteed. Also,
-            // if users configure their filter with a `reload` filter, it's possible that a parent
-            // and child have different filters as they are created with a filter change
-            // in-between.
-            //
-            // In these case, we prefer to emit a smaller span tree instead of panicking.
-            if let Some(span) = ctx.span(parent) {
-                let mut extensions = span.extensions_mut();
-                return extensions
-                    .get_mut::<OtelData>()
-                    .map(|builder| self.tracer.sampled_context(builder))
-                    .unwrap_or_default();
-            }
+    /// With [`InactivityUnit::Seconds`], timings are recorded as `f64`
+    /// seconds under the `busy` and `idle` keys. This resets the keys to the
+    /// defaults of `unit`, so custom keys must be set afterwards with
+    /// [`OpenTelemetryLayer::with_inactivity_attribute_names`].
+    ///
+    /// By default, timings are recorded as `i64` nanoseconds under the
+    /// `busy_ns` and `idle_ns` keys.
+    pub fn with_inactivity_unit(self, unit: InactivityUnit) -> Self {
+        Self {
+            inactivity_keys: InactivityKeys::for_unit(unit),
+            inactivity_unit: unit,
+            ..self
         }
+    }
 
-        // Else if the span is inferred from context, look up any available current span.
-        if attrs.is_contextual() {
-            ctx.lookup_current()
-                .and_then(|span| {
-                    let mut extensions = span.extensions_mut();
-                    extensions
-                        .get_mut::<OtelData>()
-                        .map(|builder| self.tracer.sampled_context(builder))
-                })
-                .unwrap_or_else(OtelContext::current)
-        // Explicit root spans should have no parent context.
-        } else {
-            OtelContext::new()
+    /// Sets whether or not busy and idle timings account for a span being
+    /// entered several times at once, e.g. from multiple threads.
+    ///
+    /// When enabled, a span is considered busy from its first enter until its
+    /// last exit, so overlapping enters are not counted twice. Exits without a
+    /// matching enter are ignored instead of corrupting the timings, and the
+    /// first one is reported to the OpenTelemetry [global error handler]. When
+    /// disabled, every enter/exit pair is accounted for independently.
+    ///
+    /// By default, this is disabled.
+    ///
+    /// [global error handler]: opentelemetry::global::set_error_handler
+    pub fn with_concurrent_span_safe_timings(self, concurrent_span_safe_timings: bool) -> Self {
+        Self {
+            concurrent_span_safe_timings,
+            ..self
         }
     }
 
-    fn get_context(
-        dispatch: &tracing::Dispatch,
-        id: &span::Id,
-        f: &mut dyn FnMut(&mut OtelData, &dyn PreSampledTracer),
-    ) {
-        let subscriber = dispatch
-            .downcast_ref::<S>()
-            .expect("subscriber should downcast to expected type; this is a bug!");
-        let span = subscriber
-            .span(id)
-            .expect("registry should have a span for the current ID");
-        let layer = dispatch
-            .downcast_ref::<OpenTelemetryLayer<S, T>>()
-            .expect("layer should downcast to expected type; this is a bug!");
-
-        let mut extensions = span.extensions_mut();
-        if let Some(builder) = extensions.get_mut::<OtelData>() {
-            f(builder, &layer.tracer);
+    /// Sets whether or not spans should record their total duration, from
+    /// creation to close, in nanoseconds as a `duration_ns` attribute.
+    ///
+    /// This is useful for backends that do not compute the duration of spans
+    /// from their start and end times.
+    ///
+    /// By default, the duration is not recorded.
+    pub fn with_duration_attribute(self, duration_attribute: bool) -> Self {
+        Self {
+            duration_attribute,
+            ..self
         }
     }
 
-    fn extra_span_attrs(&self) -> usize {
-        let mut extra_attrs = 0;
-        if self.location {
-            extra_attrs += 3;
-        }
-        if self.with_threads {
-            extra_attrs += 2;
+    /// Sets whether or not spans should record the number of events they
+    /// hold when they close as an `event.count` attribute.
+    ///
+    /// This allows triaging spans, e.g. finding the noisiest ones, without
+    /// inspecting their events.
+    ///
+    /// By default, the number of events is not recorded.
+    pub fn with_event_count_attribute(self, event_count_attribute: bool) -> Self {
+        Self {
+            event_count_attribute,
+            ..self
         }
-        extra_attrs
     }
-}
-
-thread_local! {
-    static THREAD_ID: unsync::Lazy<u64> = unsync::Lazy::new(|| {
-        // OpenTelemetry's semantic conventions require the thread ID to be
-        // recorded as an integer, but `std::thread::ThreadId` does not expose
-        // the integer value on stable, so we have to convert it to a `usize` by
-        // parsing it. Since this requires allocating a `String`, store it in a
-        // thread local so we only have to do this once.
-        // TODO(eliza): once `std::thread::ThreadId::as_u64` is stabilized
-        // (https://github.com/rust-lang/rust/issues/67939), just use that.
-        thread_id_integer(thread::current().id())
-    });
-}
 
-impl<S, T> Layer<S> for OpenTelemetryLayer<S, T>
-where
-    S: Subscriber + for<'span> LookupSpan<'span>,
-    T: otel::Tracer + PreSampledTracer + 'static,
-{
-    /// Creates an [OpenTelemetry `Span`] for the corresponding [tracing `Span`].
+    /// Sets whether or not spans should record the time between their
+    /// creation and the first time they were entered, in nanoseconds, as a
+    /// `scheduling.delay_ns` attribute.
     ///
-    /// [OpenTelemetry `Span`]: opentelemetry::trace::Span
-    /// [tracing `Span`]: tracing::Span
-    fn on_new_span(&self, attrs: &Attributes<'_>, id: &span::Id, ctx: Context<'_, S>) {
-        let span = ctx.span(id).expect("Span not found, this is a bug");
-        let mut extensions = span.extensions_mut();
-
-        if self.tracked_inactivity && extensions.get_mut::<Timings>().is_none() {
-            extensions.insert(Timings::new());
+    /// This is the part of the _idle time_ spent before the span started, which
+    /// helps detecting scheduling delays, e.g. of tasks waiting for a worker.
+    /// It requires [`OpenTelemetryLayer::with_tracked_inactivity`] to be
+    /// enabled, and is not recorded for spans which were never entered.
+    ///
+    /// By default, the scheduling delay is not recorded.
+    pub fn with_scheduling_delay_attribute(self, scheduling_delay_attribute: bool) -> Self {
+        Self {
+            scheduling_delay_attribute,
+            ..self
         }
+    }
 
-        let parent_cx = self.parent_context(attrs, &ctx);
-        let mut builder = self
-            .tracer
-            .span_builder(attrs.metadata().name())
-            .with_start_time(crate::time::now())
-            // Eagerly assign span id so children have stable parent id
-            .with_span_id(self.tracer.new_span_id());
-
-        // Record new trace id if there is no active parent span
-        if !parent_cx.has_active_span() {
-            builder.trace_id = Some(self.tracer.new_trace_id());
+    /// Sets the key of an attribute holding all the attributes of a span as a
+    /// single JSON object, for ingesters which only accept one attribute per
+    /// span.
+    ///
+    /// The bundle is built when the span closes, from all its attributes except
+    /// the ones whose key starts with the special field prefix, see
+    /// [`OpenTelemetryLayer::with_special_field_prefix`]. Arrays are recorded
+    /// as JSON arrays, and non-finite floats as `null`. When an attribute is
+    /// recorded several times, the last value is kept.
+    ///
+    /// By default, no bundle is recorded.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tracing_subscriber::layer::SubscriberExt;
+    /// use tracing_subscriber::Registry;
+    ///
+    /// let otel_layer = tracing_opentelemetry::layer()
+    ///     .with_json_attribute_bundle(Some("attributes"))
+    ///     .with_json_attribute_bundle_replacing(true);
+    ///
+    /// let subscriber = Registry::default().with(otel_layer);
+    /// # drop(subscriber);
+    /// ```
+    #[cfg(feature = "json")]
+    pub fn with_json_attribute_bundle(self, key: Option<&'static str>) -> Self {
+        Self {
+            json_attribute_bundle: key.map(Key::from_static_str),
+            ..self
         }
+    }
 
-        let builder_attrs = builder.attributes.get_or_insert(Vec::with_capacity(
-            attrs.fields().len() + self.extra_span_attrs(),
-        ));
-
-        if self.location {
-            let meta = attrs.metadata();
-
-            if let Some(filename) = meta.file() {
-                builder_attrs.push(KeyValue::new("code.filepath", filename));
-            }
-
-            if let Some(module) = meta.module_path() {
-                builder_attrs.push(KeyValue::new("code.namespace", module));
-            }
-
-            if let Some(line) = meta.line() {
-                builder_attrs.push(KeyValue::new("code.lineno", line as i64));
-            }
+    /// Sets whether or not the attributes bundled by
+    /// [`OpenTelemetryLayer::with_json_attribute_bundle`] are removed from the
+    /// span, leaving the bundle and the attributes starting with the special
+    /// field prefix.
+    ///
+    /// By default, the bundled attributes are kept.
+    #[cfg(feature = "json")]
+    pub fn with_json_attribute_bundle_replacing(self, replacing: bool) -> Self {
+        Self {
+            json_attribute_bundle_replacing: replacing,
+            ..self
         }
+    }
 
-        if self.with_threads {
-            THREAD_ID.with(|id| builder_attrs.push(KeyValue::new("thread.id", **id as i64)));
-            if let Some(name) = std::thread::current().name() {
-                // TODO(eliza): it's a bummer that we have to allocate here, but
-                // we can't easily get the string as a `static`. it would be
-                // nice if `opentelemetry` could also take `Arc<str>`s as
-                // `String` values...
-                builder_attrs.push(KeyValue::new("thread.name", name.to_string()));
-            }
+    /// Sets whether or not the kind of spans without an explicit `otel.kind`
+    /// is inferred from the [semantic convention attributes][conv] they
+    /// recorded.
+    ///
+    /// The kind is inferred when the span closes, using the first matching
+    /// rule:
+    ///
+    /// * `messaging.operation` of `publish` or `create` is a
+    ///   [`SpanKind::Producer`]; `receive`, `process` or `deliver` is a
+    ///   [`SpanKind::Consumer`].
+    /// * `rpc.system`, `rpc.service` or `rpc.method` is a
+    ///   [`SpanKind::Server`] if `client.address` is set, and a
+    ///   [`SpanKind::Client`] otherwise.
+    /// * `http.request.method` (or `http.method`) is a [`SpanKind::Client`] if
+    ///   the full URL is known through `url.full` (or `http.url`), and a
+    ///   [`SpanKind::Server`] otherwise.
+    /// * `db.system` is a [`SpanKind::Client`].
+    ///
+    /// Spans matching no rule keep the default [`SpanKind::Internal`] kind.
+    ///
+    /// By default, the kind is not inferred.
+    ///
+    /// [conv]: https://github.com/open-telemetry/semantic-conventions
+    /// [`SpanKind::Producer`]: opentelemetry::trace::SpanKind::Producer
+    /// [`SpanKind::Consumer`]: opentelemetry::trace::SpanKind::Consumer
+    /// [`SpanKind::Server`]: opentelemetry::trace::SpanKind::Server
+    /// [`SpanKind::Client`]: opentelemetry::trace::SpanKind::Client
+    /// [`SpanKind::Internal`]: opentelemetry::trace::SpanKind::Internal
+    pub fn with_kind_inference(self, kind_inference: bool) -> Self {
+        Self {
+            kind_inference,
+            ..self
         }
+    }
 
-        let mut updates = SpanBuilderUpdates::default();
-        attrs.record(&mut SpanAttributeVisitor {
-            span_builder_updates: &mut updates,
-            sem_conv_config: self.sem_conv_config,
-        });
-
-        updates.update(&mut builder);
-        extensions.insert(OtelData { builder, parent_cx });
+    /// Sets a table of span name patterns, used to set the kind of spans
+    /// without an explicit `otel.kind` when they are created.
+    ///
+    /// A pattern ending with `*` matches the names starting with the rest of
+    /// the pattern, e.g. `db.*` matches `db.query`, and a pattern starting
+    /// with `*` matches the names ending with the rest of the pattern, e.g.
+    /// `*.send` matches `kafka.send`. Other patterns match the whole name. The
+    /// first matching pattern wins.
+    ///
+    /// By default, no kinds are derived from span names.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use opentelemetry::trace::SpanKind;
+    /// use tracing_subscriber::layer::SubscriberExt;
+    /// use tracing_subscriber::Registry;
+    ///
+    /// let otel_layer = tracing_opentelemetry::layer().with_span_kind_from_name_suffix(vec![
+    ///     ("db.*", SpanKind::Client),
+    ///     ("http.request", SpanKind::Server),
+    ///     ("*.publish", SpanKind::Producer),
+    /// ]);
+    ///
+    /// let subscriber = Registry::default().with(otel_layer);
+    /// # drop(subscriber);
+    /// ```
+    pub fn with_span_kind_from_name_suffix(
+        self,
+        span_kind_names: Vec<(&'static str, SpanKind)>,
+    ) -> Self {
+        Self {
+            span_kind_names,
+            ..self
+        }
     }
 
-    fn on_enter(&self, id: &span::Id, ctx: Context<'_, S>) {
-        if !self.tracked_inactivity {
-            return;
+    /// Sets a function mapping the values of `otel.kind` fields which are not
+    /// one of the standard span kinds, e.g. from a custom taxonomy.
+    ///
+    /// Values for which the function returns `None` are ignored. In debug
+    /// builds, they are also reported to the OpenTelemetry
+    /// [global error handler].
+    ///
+    /// By default, unknown span kinds are silently ignored.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use opentelemetry::trace::SpanKind;
+    /// use tracing_subscriber::layer::SubscriberExt;
+    /// use tracing_subscriber::Registry;
+    ///
+    /// let otel_layer =
+    ///     tracing_opentelemetry::layer().with_unknown_span_kind_handler(|kind| match kind {
+    ///         "db" | "cache" => Some(SpanKind::Client),
+    ///         _ => None,
+    ///     });
+    ///
+    /// let subscriber = Registry::default().with(otel_layer);
+    /// # drop(subscriber);
+    /// ```
+    ///
+    /// [global error handler]: opentelemetry::global::set_error_handler
+    pub fn with_unknown_span_kind_handler<F>(self, unknown_span_kind_handler: F) -> Self
+    where
+        F: Fn(&str) -> Option<SpanKind> + Send + Sync + 'static,
+    {
+        Self {
+            unknown_span_kind_handler: Some(Arc::new(unknown_span_kind_handler)),
+            ..self
         }
+    }
 
-        let span = ctx.span(id).expect("Span not found, this is a bug");
-        let mut extensions = span.extensions_mut();
+    /// Sets the maximum depth of exported span trees, counting the root span
+    /// as the first level.
+    ///
+    /// Spans nested deeper than `max_span_depth` are not exported. Events
+    /// recorded within them are re-parented to their deepest exported
+    /// ancestor. This protects backends from the extremely deep traces
+    /// produced by pathological recursion.
+    ///
+    /// By default, the depth of span trees is not limited.
+    pub fn with_max_span_depth(self, max_span_depth: usize) -> Self {
+        Self {
+            max_span_depth: Some(max_span_depth),
+            ..self
+        }
+    }
 
-        if let Some(timings) = extensions.get_mut::<Timings>() {
-            let now = Instant::now();
-            timings.idle += (now - timings.last).as_nanos() as i64;
-            timings.last = now;
+    /// Sets whether or not span events are sorted by timestamp before the
+    /// span is exported.
+    ///
+    /// Events recorded through different code paths, such as `tracing` events
+    /// and events added directly to the OpenTelemetry span, may otherwise be
+    /// exported out of order. The sort is stable, so events with the same
+    /// timestamp keep their recording order.
+    ///
+    /// By default, events are exported in the order they were recorded.
+    pub fn with_sorted_events(self, sorted_events: bool) -> Self {
+        Self {
+            sorted_events,
+            ..self
         }
     }
 
-    fn on_exit(&self, id: &span::Id, ctx: Context<'_, S>) {
-        if !self.tracked_inactivity {
-            return;
+    /// Sets attribute keys that are moved first, in the given order, when a
+    /// span is exported.
+    ///
+    /// Some collectors apply transformations depending on the order of
+    /// attributes. The other attributes follow the prioritized ones, in the
+    /// order they were recorded.
+    ///
+    /// By default, attributes are exported in the order they were recorded.
+    pub fn with_attribute_priority(self, attribute_priority: Vec<&'static str>) -> Self {
+        Self {
+            attribute_priority,
+            ..self
         }
+    }
 
-        let span = ctx.span(id).expect("Span not found, this is a bug");
-        let mut extensions = span.extensions_mut();
+    /// Sets whether or not events record an `event.sequence` attribute,
+    /// numbering the events of each span from `0` in the order they were
+    /// recorded.
+    ///
+    /// This allows ordering events that share the same timestamp, e.g.
+    /// because of the resolution of the system clock.
+    ///
+    /// By default, sequence numbers are not recorded.
+    pub fn with_event_sequence_numbers(self, event_sequence_numbers: bool) -> Self {
+        Self {
+            event_sequence_numbers,
+            ..self
+        }
+    }
 
-        if let Some(timings) = extensions.get_mut::<Timings>() {
-            let now = Instant::now();
-            timings.busy += (now - timings.last).as_nanos() as i64;
-            timings.last = now;
+    /// Sets whether or not links created from `tracing`'s
+    /// [`follows_from`] record a `link.type = "follows_from"` attribute.
+    ///
+    /// This distinguishes them from links added with
+    /// [`OpenTelemetrySpanExt::add_link`] in the backend.
+    ///
+    /// By default, follows-from links have no attributes.
+    ///
+    /// [`follows_from`]: tracing::Span::follows_from
+    /// [`OpenTelemetrySpanExt::add_link`]: crate::OpenTelemetrySpanExt::add_link
+    pub fn with_follows_from_link_type_attribute(self, follows_from_link_type: bool) -> Self {
+        Self {
+            follows_from_link_type,
+            ..self
         }
     }
 
-    /// Record OpenTelemetry [`attributes`] for the given values.
+    /// Sets whether or not span ids are assigned when spans are created.
     ///
-    /// [`attributes`]: opentelemetry::trace::SpanBuilder::attributes
-    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
-        let span = ctx.span(id).expect("Span not found, this is a bug");
-        let mut updates = SpanBuilderUpdates::default();
-        values.record(&mut SpanAttributeVisitor {
-            span_builder_updates: &mut updates,
-            sem_conv_config: self.sem_conv_config,
-        });
-        let mut extensions = span.extensions_mut();
-        if let Some(data) = extensions.get_mut::<OtelData>() {
-            updates.update(&mut data.builder);
+    /// By default, the layer asks the tracer for a new span id as soon as a
+    /// span is created, so that the context of the span, and thus the parent
+    /// of its children, is known before the span is exported. When disabled,
+    /// the tracer assigns the span id when the span is exported instead, which
+    /// avoids generating ids for spans that end up not being sampled.
+    ///
+    /// **Note**: when disabled, the context of spans that have not been
+    /// exported yet has an invalid span id. Their children are then exported
+    /// as root spans of new traces, and [`OpenTelemetrySpanExt::context`]
+    /// returns an invalid span context. Only disable this when spans are not
+    /// expected to have children or propagate their context.
+    ///
+    /// By default, span ids are assigned eagerly.
+    ///
+    /// [`OpenTelemetrySpanExt::context`]: crate::OpenTelemetrySpanExt::context
+    pub fn with_eager_span_id(self, eager_span_id: bool) -> Self {
+        Self {
+            eager_span_id,
+            ..self
         }
     }
 
-    fn on_follows_from(&self, id: &Id, follows: &Id, ctx: Context<S>) {
-        let span = ctx.span(id).expect("Span not found, this is a bug");
-        let mut extensions = span.extensions_mut();
-        let data = extensions
-            .get_mut::<OtelData>()
-            .expect("Missing otel data span extensions");
+    /// Sets whether or not spans whose end time would be before their start
+    /// time record a `clock.anomaly = true` attribute.
+    ///
+    /// This can happen when the system clock moves backward while a span is
+    /// open, e.g. because of an NTP adjustment. The end time of such spans is
+    /// always clamped to their start time, to avoid exporting spans with a
+    /// negative duration; this setting only controls whether the anomaly is
+    /// reported.
+    ///
+    /// By default, the anomaly is not recorded.
+    pub fn with_span_start_jitter_detection(self, clock_anomaly_attribute: bool) -> Self {
+        Self {
+            clock_anomaly_attribute,
+            ..self
+        }
+    }
 
-        // The follows span may be filtered away (or closed), from this layer,
-        // in which case we just drop the data, as opposed to panicking. This
-        // uses the same reasoning as `parent_context` above.
-        if let Some(follows_span) = ctx.span(follows) {
-            let mut follows_extensions = follows_span.extensions_mut();
-            let follows_data = follows_extensions
-                .get_mut::<OtelData>()
-                .expect("Missing otel data span extensions");
+    /// Sets the [`IdFormat`] used to render trace and span ids wherever the
+    /// layer records them as string attributes.
+    ///
+    /// By default, ids are rendered as lowercase hexadecimal.
+    pub fn with_id_format(self, id_format: IdFormat) -> Self {
+        Self { id_format, ..self }
+    }
 
-            let follows_context = self
-                .tracer
-                .sampled_context(follows_data)
-                .span()
-                .span_context()
-                .clone();
-            let follows_link = otel::Link::new(follows_context, Vec::new());
-            if let Some(ref mut links) = data.builder.links {
-                links.push(follows_link);
-            } else {
-                data.builder.links = Some(vec![follows_link]);
-            }
+    /// Sets whether or not spans record their own span id as an `otel.span_id`
+    /// attribute, along with their trace id as `otel.trace_id` and the id of
+    /// their parent span, if any, as `otel.parent_span_id`. All ids are
+    /// rendered with the configured [`IdFormat`].
+    ///
+    /// This helps debugging span relationships in backends that only display
+    /// attributes.
+    ///
+    /// By default, the span id is not recorded as an attribute.
+    pub fn with_attribute_from_span_id(self, span_id_attribute: bool) -> Self {
+        Self {
+            span_id_attribute,
+            ..self
         }
     }
 
-    /// Records OpenTelemetry [`Event`] data on event.
+    /// Sets whether or not spans record additional attributes for the thread
+    /// name and thread ID of the thread they were created on, following the
+    /// [OpenTelemetry semantic conventions for threads][conv].
     ///
-    /// Note: an [`ERROR`]-level event will also set the OpenTelemetry span status code to
-    /// [`Error`], signaling that an error has occurred.
+    /// The name of a thread is read once, when the first span is created on
+    /// it, so renaming a thread afterwards does not affect its spans. Spans
+    /// created on unnamed threads have no `thread.name` attribute.
     ///
-    /// [`Event`]: opentelemetry::trace::Event
-    /// [`ERROR`]: tracing::Level::ERROR
-    /// [`Error`]: opentelemetry::trace::StatusCode::Error
-    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
-        // Ignore events that are not in the context of a span
-        if let Some(span) = event.parent().and_then(|id| ctx.span(id)).or_else(|| {
+    /// By default, thread attributes are enabled.
+    ///
+    /// [conv]: https://github.com/open-telemetry/semantic-conventions/blob/main/docs/general/attributes.md#general-thread-attributes/
+    pub fn with_threads(self, threads: bool) -> Self {
+        Self {
+            with_threads: threads,
+            ..self
+        }
+    }
+
+    /// Sets whether or not the `thread.id` attribute recorded by
+    /// [`OpenTelemetryLayer::with_threads`] is replaced by a sequential id,
+    /// assigned by this layer to each thread the first time it records a span.
+    ///
+    /// Operating system thread ids differ between runs, so this is useful for
+    /// comparing exported spans against expected output in tests. Sequential
+    /// ids start at `1` and are only stable within a single run.
+    ///
+    /// By default, the actual thread ids are recorded.
+    pub fn with_deterministic_thread_ids(self, deterministic_thread_ids: bool) -> Self {
+        Self {
+            deterministic_thread_ids: deterministic_thread_ids.then(Default::default),
+            ..self
+        }
+    }
+
+    /// Sets how `f64` values that are NaN or infinite are recorded as span
+    /// and event attributes.
+    ///
+    /// By default, these values are recorded as is.
+    pub fn with_nonfinite_float_handling(self, nonfinite_floats: NonFinite) -> Self {
+        Self {
+            nonfinite_floats,
+            ..self
+        }
+    }
+
+    /// Sets the number of additional attributes to allocate room for when a
+    /// span is created.
+    ///
+    /// The attributes of a span are allocated for its fields and the
+    /// attributes recorded by the layer itself. Reserving room for attributes
+    /// added later, e.g. with [`OpenTelemetrySpanExt::set_attribute`], avoids
+    /// reallocating them.
+    ///
+    /// By default, no additional room is allocated.
+    ///
+    /// [`OpenTelemetrySpanExt::set_attribute`]: crate::OpenTelemetrySpanExt::set_attribute
+    pub fn with_span_builder_attributes_capacity(self, attributes_headroom: usize) -> Self {
+        Self {
+            attributes_headroom,
+            ..self
+        }
+    }
+
+    /// Marks spans created within `window` from now with an
+    /// `app.startup = true` attribute.
+    ///
+    /// This distinguishes spans recorded while the application warms up, e.g.
+    /// filling caches or establishing connections, from the steady state.
+    /// Once the window has passed, checking it only costs an atomic load.
+    ///
+    /// By default, spans are not marked.
+    pub fn with_startup_window(self, window: Duration) -> Self {
+        Self {
+            startup_window: Some(StartupWindow::new(window)),
+            ..self
+        }
+    }
+
+    /// Sets whether or not root spans record attributes describing the Rust
+    /// runtime of the process.
+    ///
+    /// These are `process.runtime.name = "rust"`, and
+    /// `process.runtime.description` with the architecture and operating
+    /// system of the process, e.g. `x86_64 linux`. This description is
+    /// deliberately not a target triple, which is only known to build scripts,
+    /// but a triple can be recorded instead with
+    /// [`OpenTelemetryLayer::with_runtime_description`]. The version of the
+    /// compiler is not known to this crate either, but can be recorded as
+    /// `process.runtime.version` with
+    /// [`OpenTelemetryLayer::with_runtime_version`]. These attributes are only
+    /// recorded on root spans, as they are the same for all the spans of a
+    /// process.
+    ///
+    /// By default, these attributes are not recorded.
+    pub fn with_runtime_attributes(self, runtime_attributes: bool) -> Self {
+        Self {
+            runtime_attributes,
+            ..self
+        }
+    }
+
+    /// Sets the `process.runtime.version` recorded by root spans when
+    /// [`OpenTelemetryLayer::with_runtime_attributes`] is enabled, e.g. the
+    /// version of the compiler exposed by the build script of the application.
+    ///
+    /// By default, the runtime version is not recorded.
+    pub fn with_runtime_version(self, runtime_version: impl Into<StringValue>) -> Self {
+        Self {
+            runtime_version: Some(runtime_version.into()),
+            ..self
+        }
+    }
+
+    /// Sets the `process.runtime.description` recorded by root spans when
+    /// [`OpenTelemetryLayer::with_runtime_attributes`] is enabled, e.g. the
+    /// target triple exposed by the build script of the application.
+    ///
+    /// By default, the architecture and operating system of the process are
+    /// recorded, e.g. `x86_64 linux`.
+    pub fn with_runtime_description(self, runtime_description: impl Into<StringValue>) -> Self {
+        Self {
+            runtime_description: Some(runtime_description.into()),
+            ..self
+        }
+    }
+
+    /// Truncates the keys of span and event attributes to at most
+    /// `max_length` bytes, for backends which cap the length of attribute
+    /// keys.
+    ///
+    /// Truncated keys end with `~` followed by the 32-bit FNV-1a hash of the
+    /// full key, so that long keys sharing a prefix remain distinct. The
+    /// first truncation is reported to the OpenTelemetry
+    /// [global error handler].
+    ///
+    /// [global error handler]: opentelemetry::global::set_error_handler
+    ///
+    /// By default, attribute keys are not truncated.
+    pub fn with_max_attribute_key_length(self, max_length: usize) -> Self {
+        Self {
+            max_attribute_key_length: Some(KeyLengthLimit::new(max_length)),
+            ..self
+        }
+    }
+
+    /// Sets whether or not spans record their `tracing` level as an attribute.
+    ///
+    /// The attribute is named `level` by default, like the level of events,
+    /// see [`OpenTelemetryLayer::with_level_field_name`] to change it.
+    ///
+    /// By default, the level of spans is not recorded.
+    pub fn with_level(self, level: bool) -> Self {
+        Self {
+            span_level: level,
+            ..self
+        }
+    }
+
+    /// Sets the name of the attribute recording the level of spans when
+    /// [`OpenTelemetryLayer::with_level`] is enabled.
+    ///
+    /// This does not change the `level` attribute of events, so it can be
+    /// used to distinguish the level of spans from the level of events.
+    ///
+    /// By default, the attribute is named `level`.
+    pub fn with_level_field_name(self, level_field_name: impl Into<Key>) -> Self {
+        Self {
+            span_level_key: level_field_name.into(),
+            ..self
+        }
+    }
+
+    /// Sets whether or not events record their `tracing` level as a `level`
+    /// attribute.
+    ///
+    /// By default, the level of events is recorded.
+    pub fn with_event_level(self, event_level: bool) -> Self {
+        Self {
+            event_level,
+            ..self
+        }
+    }
+
+    /// Sets whether or not spans record their `tracing` target as a `target`
+    /// attribute, like events do.
+    ///
+    /// By default, the target of spans is not recorded.
+    pub fn with_span_target(self, span_target: bool) -> Self {
+        Self {
+            span_target,
+            ..self
+        }
+    }
+
+    /// Sets whether or not the [baggage] of the parent context of spans is
+    /// recorded as span attributes, with keys prefixed by `baggage.`.
+    ///
+    /// This includes baggage of contexts assigned with
+    /// [`OpenTelemetrySpanExt::set_parent`]. Attributes of the span take
+    /// precedence over baggage entries with the same key. Baggage entries are
+    /// recorded after all other attributes, so they are the first to be
+    /// dropped when the span exceeds the attribute limit of the tracer.
+    ///
+    /// By default, baggage is not recorded.
+    ///
+    /// [baggage]: opentelemetry::baggage
+    /// [`OpenTelemetrySpanExt::set_parent`]: crate::OpenTelemetrySpanExt::set_parent
+    pub fn with_baggage_attributes(self, baggage_attributes: bool) -> Self {
+        Self {
+            baggage_attributes,
+            ..self
+        }
+    }
+
+    /// Sets whether or not spans should only be exported if the trace they
+    /// belong to contains an error.
+    ///
+    /// When enabled, spans are not exported as soon as they close. Instead,
+    /// they are buffered on the root span of their local span tree, and when
+    /// the root span closes, the whole tree is exported only if at least one
+    /// of its spans has an [`Error`] status. Traces without errors are
+    /// discarded.
+    ///
+    /// Note that every span of a trace is kept in memory until its root span
+    /// closes, so long-lived root spans with many children will hold on to a
+    /// significant amount of data. At most 1024 spans are buffered per root
+    /// span. This limit is not configurable: spans closed once it is reached
+    /// are discarded, and reported with [`DropReason::BufferFull`] to the
+    /// callback set with [`OpenTelemetryLayer::with_dropped_span_callback`].
+    ///
+    /// By default, this is disabled and all spans are exported.
+    ///
+    /// [`Error`]: opentelemetry::trace::Status::Error
+    pub fn with_export_on_error_only(self, export_on_error_only: bool) -> Self {
+        Self {
+            export_on_error_only,
+            ..self
+        }
+    }
+
+    /// Sets a function called with the data of every span that this layer
+    /// decides not to export, along with the reason why it was dropped.
+    ///
+    /// This gives visibility into how many and what kind of spans are
+    /// discarded by the layer's own filtering, e.g. for cost monitoring. Spans
+    /// dropped by the tracer's sampler are not reported.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// use tracing_subscriber::layer::SubscriberExt;
+    /// use tracing_subscriber::Registry;
+    ///
+    /// static DROPPED: AtomicUsize = AtomicUsize::new(0);
+    ///
+    /// let otel_layer = tracing_opentelemetry::layer()
+    ///     .with_export_on_error_only(true)
+    ///     .with_dropped_span_callback(|_builder, _reason| {
+    ///         DROPPED.fetch_add(1, Ordering::Relaxed);
+    ///     });
+    ///
+    /// let subscriber = Registry::default().with(otel_layer);
+    /// # drop(subscriber);
+    /// ```
+    pub fn with_dropped_span_callback<F>(self, dropped_span_callback: F) -> Self
+    where
+        F: Fn(&SpanBuilder, DropReason) + Send + Sync + 'static,
+    {
+        Self {
+            dropped_span_callback: Some(Arc::new(dropped_span_callback)),
+            ..self
+        }
+    }
+
+    fn drop_span(&self, builder: &SpanBuilder, reason: DropReason) {
+        if let Some(dropped_span_callback) = &self.dropped_span_callback {
+            dropped_span_callback(builder, reason);
+        }
+    }
+
+    /// Sets a function called with the span context of an OpenTelemetry
+    /// context used as a parent when it is invalid, e.g. because it has an
+    /// all-zero trace id. This applies to the current context when a span is
+    /// created, and to contexts assigned with
+    /// [`OpenTelemetrySpanExt::set_parent`].
+    ///
+    /// This allows logging or counting requests carrying malformed trace
+    /// contexts. The span still gets the invalid parent. Note that propagators
+    /// ignore malformed headers, so the contexts they extract from such
+    /// headers have no parent span to validate.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tracing_subscriber::layer::SubscriberExt;
+    /// use tracing_subscriber::Registry;
+    ///
+    /// let otel_layer = tracing_opentelemetry::layer().with_trace_context_validation(|cx| {
+    ///     eprintln!("invalid trace context: {:?}", cx);
+    /// });
+    ///
+    /// let subscriber = Registry::default().with(otel_layer);
+    /// # drop(subscriber);
+    /// ```
+    ///
+    /// [`OpenTelemetrySpanExt::set_parent`]: crate::OpenTelemetrySpanExt::set_parent
+    pub fn with_trace_context_validation<F>(self, trace_context_validation: F) -> Self
+    where
+        F: Fn(&otel::SpanContext) + Send + Sync + 'static,
+    {
+        Self {
+            trace_context_validation: Some(Arc::new(trace_context_validation)),
+            ..self
+        }
+    }
+
+    fn validate_trace_context(&self, cx: &OtelContext) {
+        if let Some(trace_context_validation) = &self.trace_context_validation {
+            if cx.has_active_span() {
+                let span = cx.span();
+                let span_context = span.span_context();
+                if !span_context.is_valid() {
+                    trace_context_validation(span_context);
+                }
+            }
+        }
+    }
+
+    /// Makes spans inherit all the attributes of their parent span, except the
+    /// ones matching `denylist`.
+    ///
+    /// Inherited attributes are copied from the parent when the child span is
+    /// created, so attributes recorded on the parent afterwards are not
+    /// inherited. Attributes set by the child's own fields take precedence.
+    /// Entries of the denylist match keys exactly, unless they end with `*`,
+    /// in which case they match all keys starting with the preceding prefix,
+    /// e.g. `code.*`.
+    ///
+    /// Note that inherited attributes are repeated on every span of a subtree,
+    /// which can significantly increase the size of exported traces. Consider
+    /// denying high-cardinality or large attributes.
+    ///
+    /// By default, spans do not inherit attributes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tracing_subscriber::layer::SubscriberExt;
+    /// use tracing_subscriber::Registry;
+    ///
+    /// // Propagate business attributes, but not locations or thread info
+    /// let otel_layer = tracing_opentelemetry::layer()
+    ///     .with_parent_based_attribute_inheritance(vec!["code.*", "thread.*"]);
+    ///
+    /// let subscriber = Registry::default().with(otel_layer);
+    /// # drop(subscriber);
+    /// ```
+    pub fn with_parent_based_attribute_inheritance<I, K>(self, denylist: I) -> Self
+    where
+        I: IntoIterator<Item = K>,
+        K: Into<Cow<'static, str>>,
+    {
+        Self {
+            inherited_attributes_denylist: Some(denylist.into_iter().map(Into::into).collect()),
+            ..self
+        }
+    }
+
+    /// Sets a namespace prepended to the keys of all span attributes recorded
+    /// from `tracing` fields.
+    ///
+    /// This helps telling application attributes apart from attributes
+    /// following the OpenTelemetry semantic conventions. The namespace is used
+    /// as-is, so it should usually end with a `.`, e.g. `app.` turns a `foo`
+    /// field into an `app.foo` attribute. The special `otel.*` fields and the
+    /// attributes added by the layer itself, such as locations, threads,
+    /// timings and exception fields, are not prefixed.
+    ///
+    /// By default, no namespace is used.
+    pub fn with_attribute_namespace(self, namespace: impl Into<Cow<'static, str>>) -> Self {
+        Self {
+            attribute_namespace: Some(namespace.into()),
+            ..self
+        }
+    }
+
+    /// Sets the prefix of the special fields used to configure OpenTelemetry
+    /// spans, such as `otel.name` or `otel.kind`.
+    ///
+    /// This is useful when `otel.`-prefixed field names are already used for
+    /// other purposes. With a `_otel.` prefix, for instance, the span name is
+    /// set by the `_otel.name` field, while an `otel.name` field is recorded as
+    /// a regular attribute.
+    ///
+    /// By default, the prefix is `otel.`.
+    pub fn with_special_field_prefix(self, prefix: impl Into<Cow<'static, str>>) -> Self {
+        Self {
+            special_field_prefix: prefix.into(),
+            ..self
+        }
+    }
+
+    /// Sets a mapping from target prefixes to crate versions, used to record a
+    /// `code.crate.version` attribute on spans.
+    ///
+    /// `tracing` metadata does not carry the version of the crate that emitted
+    /// a span, which makes it hard to tell apart multiple versions of the same
+    /// dependency. When a span's target starts with one of the given prefixes,
+    /// the corresponding version is recorded. If several prefixes match, the
+    /// longest one wins.
+    ///
+    /// By default, no versions are recorded.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::collections::HashMap;
+    /// use tracing_subscriber::layer::SubscriberExt;
+    /// use tracing_subscriber::Registry;
+    ///
+    /// let versions = HashMap::from([("my_crate", env!("CARGO_PKG_VERSION"))]);
+    /// let otel_layer = tracing_opentelemetry::layer().with_target_version_map(versions);
+    ///
+    /// let subscriber = Registry::default().with(otel_layer);
+    /// # drop(subscriber);
+    /// ```
+    pub fn with_target_version_map(
+        self,
+        target_versions: HashMap<&'static str, &'static str>,
+    ) -> Self {
+        Self {
+            target_versions,
+            ..self
+        }
+    }
+
+    /// Sets a function producing additional attributes to record on a span
+    /// when it is entered for the first time.
+    ///
+    /// This is useful for attributes that are only known once a span starts
+    /// doing work, such as the worker thread that picked up a task. The
+    /// function is only called on the first entry of each span; subsequent
+    /// entries do not record any additional attributes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use opentelemetry::KeyValue;
+    /// use tracing_subscriber::layer::SubscriberExt;
+    /// use tracing_subscriber::Registry;
+    ///
+    /// let otel_layer = tracing_opentelemetry::layer().with_on_enter_attributes(|| {
+    ///     let thread = std::thread::current();
+    ///     vec![KeyValue::new("worker", thread.name().unwrap_or_default().to_owned())]
+    /// });
+    ///
+    /// let subscriber = Registry::default().with(otel_layer);
+    /// # drop(subscriber);
+    /// ```
+    pub fn with_on_enter_attributes<F>(self, on_enter_attributes: F) -> Self
+    where
+        F: Fn() -> Vec<KeyValue> + Send + Sync + 'static,
+    {
+        Self {
+            on_enter_attributes: Some(Arc::new(on_enter_attributes)),
+            ..self
+        }
+    }
+
+    /// Sets a function returning the name of the worker creating a span,
+    /// recorded as a `worker.name` attribute.
+    ///
+    /// This is useful to tell apart the workers of an executor or thread pool
+    /// when their threads are not named, or share the same name. The function
+    /// is called when each span is created, in the thread creating it; no
+    /// attribute is recorded when it returns `None`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tracing_subscriber::layer::SubscriberExt;
+    /// use tracing_subscriber::Registry;
+    ///
+    /// let otel_layer = tracing_opentelemetry::layer()
+    ///     .with_worker_name_fn(|| Some(format!("worker-{:?}", std::thread::current().id())));
+    ///
+    /// let subscriber = Registry::default().with(otel_layer);
+    /// # drop(subscriber);
+    /// ```
+    pub fn with_worker_name_fn<F>(self, worker_name_fn: F) -> Self
+    where
+        F: Fn() -> Option<String> + Send + Sync + 'static,
+    {
+        Self {
+            worker_name_fn: Some(Arc::new(worker_name_fn)),
+            ..self
+        }
+    }
+
+    /// Sets a function deciding whether an event is recorded on its span.
+    ///
+    /// The function receives the event and its metadata (normalized for events
+    /// converted from `log` records). Events for which it returns `false` are
+    /// not attached to their span and do not affect its status. Unlike
+    /// per-layer filters, this only affects how events are recorded; spans are
+    /// unaffected.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tracing_subscriber::layer::SubscriberExt;
+    /// use tracing_subscriber::Registry;
+    ///
+    /// // Do not record events from noisy dependencies on spans
+    /// let otel_layer = tracing_opentelemetry::layer()
+    ///     .with_event_filter(|_event, metadata| !metadata.target().starts_with("hyper"));
+    ///
+    /// let subscriber = Registry::default().with(otel_layer);
+    /// # drop(subscriber);
+    /// ```
+    pub fn with_event_filter<F>(self, event_filter: F) -> Self
+    where
+        F: Fn(&Event<'_>, &Metadata<'_>) -> bool + Send + Sync + 'static,
+    {
+        Self {
+            event_filter: Some(Arc::new(event_filter)),
+            ..self
+        }
+    }
+
+    /// Sets a function deciding which event fields are recorded as attributes
+    /// of span events.
+    ///
+    /// The function receives the name of each field. Fields for which it
+    /// returns `false` are not recorded, e.g. to avoid exporting large debug
+    /// payloads. The `message` field, `error` fields converted to exceptions
+    /// and `log.` metadata fields are always handled as usual.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use tracing_subscriber::layer::SubscriberExt;
+    /// use tracing_subscriber::Registry;
+    ///
+    /// // Do not export request payloads
+    /// let otel_layer = tracing_opentelemetry::layer()
+    ///     .with_event_attribute_filter(|name| name != "payload");
+    ///
+    /// let subscriber = Registry::default().with(otel_layer);
+    /// # drop(subscriber);
+    /// ```
+    pub fn with_event_attribute_filter<F>(self, event_attribute_filter: F) -> Self
+    where
+        F: Fn(&str) -> bool + Send + Sync + 'static,
+    {
+        Self {
+            event_attribute_filter: Some(Arc::new(event_attribute_filter)),
+            ..self
+        }
+    }
+
+    /// Sets whether or not the `message` field of events is used as the name
+    /// of the OpenTelemetry event.
+    ///
+    /// When disabled, the message is recorded as a `message` attribute, and
+    /// events are named after the name of their `tracing` metadata, e.g.
+    /// `event src/main.rs:42`. Events without a message but with an `error`
+    /// field are still mapped to exceptions, see
+    /// [`OpenTelemetryLayer::with_error_events_to_exceptions`].
+    ///
+    /// By default, the message is used as the event name.
+    pub fn with_message_as_event_name(self, message_as_event_name: bool) -> Self {
+        Self {
+            message_as_event_name,
+            ..self
+        }
+    }
+
+    /// Sets whether or not span fields that are declared but never recorded
+    /// should be exported as empty string attributes.
+    ///
+    /// Fields declared with [`tracing::field::Empty`] and never filled in are
+    /// normally not exported at all. When this is enabled, every such field is
+    /// recorded as an empty string attribute when the span closes, so that
+    /// exported spans always share the same set of attribute keys. The special
+    /// `otel.*` fields are never exported as markers.
+    ///
+    /// By default, this is disabled.
+    pub fn with_empty_field_markers(self, empty_field_markers: bool) -> Self {
+        Self {
+            empty_field_markers,
+            ..self
+        }
+    }
+
+    /// Sets the attribute keys used to record the location of events, when
+    /// [`OpenTelemetryLayer::with_location`] is enabled.
+    ///
+    /// This is useful when events are sent to a different pipeline than spans,
+    /// which expects different keys. Span location attributes are not affected.
+    ///
+    /// By default, events use the same `code.filepath`, `code.namespace` and
+    /// `code.lineno` keys as spans.
+    pub fn with_event_code_field_names(
+        self,
+        filepath: impl Into<Key>,
+        namespace: impl Into<Key>,
+        lineno: impl Into<Key>,
+    ) -> Self {
+        Self {
+            event_code_keys: CodeKeys {
+                filepath: filepath.into(),
+                namespace: namespace.into(),
+                lineno: lineno.into(),
+            },
+            ..self
+        }
+    }
+
+    /// Sets how spans whose explicit parent is filtered out of this layer, e.g.
+    /// by a per-layer filter, are attached to a trace.
+    ///
+    /// By default, such spans become [root spans] of new traces, splitting the
+    /// trace in smaller trees.
+    ///
+    /// [root spans]: FilteredParentMode::AsRoot
+    pub fn with_filtered_parent_mode(self, filtered_parent_mode: FilteredParentMode) -> Self {
+        Self {
+            filtered_parent_mode,
+            ..self
+        }
+    }
+
+    /// Sets how the sampling decision of a span is handled when its parent is
+    /// set with [`OpenTelemetrySpanExt::set_parent`] after it was sampled.
+    ///
+    /// Spans are sampled lazily, when their context is first needed, e.g. by
+    /// [`OpenTelemetrySpanExt::context`] or to create a child span.
+    ///
+    /// By default, the span is [sampled again] with its new parent.
+    ///
+    /// [`OpenTelemetrySpanExt::set_parent`]: crate::OpenTelemetrySpanExt::set_parent
+    /// [`OpenTelemetrySpanExt::context`]: crate::OpenTelemetrySpanExt::context
+    /// [sampled again]: LateReparentPolicy::Resample
+    pub fn with_late_reparent_policy(self, late_reparent_policy: LateReparentPolicy) -> Self {
+        Self {
+            late_reparent_policy,
+            ..self
+        }
+    }
+
+    /// Sets whether or not spans are sampled when they are created, so that
+    /// the spans dropped by the tracer's sampler skip the work of recording
+    /// their attributes, events and timings.
+    ///
+    /// This lowers the overhead of unsampled spans for services running at
+    /// low sample rates. However, the sampler then only sees the name, ids and
+    /// parent of spans, not their fields, kind or the attributes added by this
+    /// layer. The fields recorded when dropped spans are created, including
+    /// special fields such as `otel.trace_id`, are ignored.
+    ///
+    /// **Note** that spans are sampled with the parent they have when they are
+    /// created, so this does not combine well with assigning remote parents
+    /// with [`OpenTelemetrySpanExt::set_parent`], e.g.
+    /// `info_span!(..).set_parent(extracted_cx)`:
+    ///
+    /// * With [`LateReparentPolicy::KeepDecision`], the sampled flag of the
+    ///   remote parent is ignored.
+    /// * With [`LateReparentPolicy::Resample`], the default, the span is
+    ///   sampled again with its remote parent, but a span dropped at creation
+    ///   and sampled then is exported without the attributes, events and
+    ///   timings it skipped.
+    ///
+    /// Prefer creating such spans within the remote context, e.g. by attaching
+    /// it while creating the span.
+    ///
+    /// By default, spans are sampled lazily, when their context is first
+    /// needed or when they close.
+    ///
+    /// [`OpenTelemetrySpanExt::set_parent`]: crate::OpenTelemetrySpanExt::set_parent
+    pub fn with_skip_unsampled_enrichment(self, skip_unsampled_enrichment: bool) -> Self {
+        Self {
+            skip_unsampled_enrichment,
+            ..self
+        }
+    }
+
+    /// Returns whether the work of recording the data of a span is skipped,
+    /// because it was dropped by the sampler.
+    fn skips_enrichment(&self, data: &OtelData) -> bool {
+        self.skip_unsampled_enrichment
+            && data
+                .builder
+                .sampling_result
+                .as_ref()
+                .map_or(false, |result| {
+                    result.decision == otel::SamplingDecision::Drop
+                })
+    }
+
+    /// Sets the attribute names used to record the location of spans and
+    /// events, when [`OpenTelemetryLayer::with_location`] is enabled.
+    ///
+    /// Newer versions of the OpenTelemetry semantic conventions renamed the
+    /// source code attributes, and backends validating attributes against
+    /// these conventions may ignore the legacy names. This replaces names set
+    /// by [`OpenTelemetryLayer::with_event_code_field_names`] beforehand.
+    ///
+    /// By default, the [legacy names] are used.
+    ///
+    /// [legacy names]: CodeAttributeSchema::Legacy
+    pub fn with_code_attribute_schema(self, schema: CodeAttributeSchema) -> Self {
+        let keys = CodeKeys::for_schema(schema);
+        let column = match schema {
+            CodeAttributeSchema::Legacy => FIELD_CODE_COLUMN,
+            CodeAttributeSchema::Current => FIELD_CODE_COLUMN_NUMBER,
+        };
+        Self {
+            span_code_keys: keys.clone(),
+            span_code_column_key: Key::from_static_str(column),
+            event_code_keys: keys,
+            ..self
+        }
+    }
+
+    /// Returns the kind of the first pattern of
+    /// [`OpenTelemetryLayer::with_span_kind_from_name_suffix`] matching `name`.
+    fn span_kind_from_name(&self, name: &str) -> Option<SpanKind> {
+        self.span_kind_names
+            .iter()
+            .find(|(pattern, _)| {
+                if let Some(prefix) = pattern.strip_suffix('*') {
+                    name.starts_with(prefix)
+                } else if let Some(suffix) = pattern.strip_prefix('*') {
+                    name.ends_with(suffix)
+                } else {
+                    name == *pattern
+                }
+            })
+            .map(|(_, kind)| kind.clone())
+    }
+
+    fn target_version(&self, target: &str) -> Option<&'static str> {
+        self.target_versions
+            .iter()
+            .filter(|(prefix, _)| target.starts_with(*prefix))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, version)| *version)
+    }
+
+    /// Retrieve the parent OpenTelemetry [`Context`] from the current tracing
+    /// [`span`] through the [`Registry`]. This [`Context`] links spans to their
+    /// parent for proper hierarchical visualization.
+    ///
+    /// [`Context`]: opentelemetry::Context
+    /// [`span`]: tracing::Span
+    /// [`Registry`]: tracing_subscriber::Registry
+    fn parent_context(
+        &self,
+        attrs: &Attributes<'_>,
+        id: &span::Id,
+        ctx: &Context<'_, S>,
+    ) -> OtelContext {
+        if let Some(parent) = attrs.parent() {
+            // A span can have an _explicit_ parent that is NOT seen by this `Layer` (for which
+            // `Context::span` returns `None`. This happens if the parent span is filtered away
+            // from the layer by a per-layer filter. In that case, we fall-through to the `else`
+            // case, and consider this span a root span.
+            //
+            // This is likely rare, as most users who use explicit parents will configure their
+            // filters so that children and parents are both seen, but it's not guaranteed. Also,
+            // if users configure their filter with a `reload` filter, it's possible that a parent
+            // and child have different filters as they are created with a filter change
+            // in-between.
+            //
+            // In these case, we prefer to emit a smaller span tree instead of panicking,
+            // unless configured to attach the span to its nearest visible ancestor, which
+            // `SpanRef::parent` finds by skipping the spans filtered away from this layer.
+            let span = match self.filtered_parent_mode {
+                FilteredParentMode::AsRoot => ctx.span(parent),
+                FilteredParentMode::NearestVisibleAncestor => ctx
+                    .span(parent)
+                    .or_else(|| ctx.span(id).and_then(|span| span.parent())),
+            };
+            if let Some(span) = span {
+                let mut extensions = span.extensions_mut();
+                return extensions
+                    .get_mut::<OtelData>()
+                    .map(|builder| self.tracer.sampled_context(builder))
+                    .unwrap_or_default();
+            }
+        }
+
+        // Else if the span is inferred from context, look up any available current span.
+        if attrs.is_contextual() {
+            ctx.lookup_current()
+                .and_then(|span| {
+                    let mut extensions = span.extensions_mut();
+                    extensions
+                        .get_mut::<OtelData>()
+                        .map(|builder| self.tracer.sampled_context(builder))
+                })
+                .unwrap_or_else(|| {
+                    let cx = OtelContext::current();
+                    self.validate_trace_context(&cx);
+                    cx
+                })
+        // Explicit root spans should have no parent context.
+        } else {
+            OtelContext::new()
+        }
+    }
+
+    fn get_context(
+        dispatch: &tracing::Dispatch,
+        id: &span::Id,
+        f: &mut dyn FnMut(&mut OtelData, &dyn PreSampledTracer),
+    ) {
+        let subscriber = dispatch
+            .downcast_ref::<S>()
+            .expect("subscriber should downcast to expected type; this is a bug!");
+        // The span may have been closed already, e.g. when it is only
+        // referenced by an ID to link to.
+        let Some(span) = subscriber.span(id) else {
+            return;
+        };
+        let layer = dispatch
+            .downcast_ref::<OpenTelemetryLayer<S, T>>()
+            .expect("layer should downcast to expected type; this is a bug!");
+
+        let mut extensions = span.extensions_mut();
+        if let Some(builder) = extensions.get_mut::<OtelData>() {
+            f(builder, &layer.tracer);
+        }
+    }
+
+    fn get_root_context(
+        dispatch: &tracing::Dispatch,
+        id: &span::Id,
+        f: &mut dyn FnMut(&mut OtelData, &dyn PreSampledTracer),
+    ) {
+        let subscriber = dispatch
+            .downcast_ref::<S>()
+            .expect("subscriber should downcast to expected type; this is a bug!");
+        let Some(span) = subscriber.span(id) else {
+            return;
+        };
+        let layer = dispatch
+            .downcast_ref::<OpenTelemetryLayer<S, T>>()
+            .expect("layer should downcast to expected type; this is a bug!");
+
+        // Spans filtered out of this layer have no data and are skipped.
+        let root = span
+            .scope()
+            .take(MAX_ROOT_CONTEXT_DEPTH)
+            .filter(|span| span.extensions().get::<OtelData>().is_some())
+            .last();
+        if let Some(root) = root {
+            let mut extensions = root.extensions_mut();
+            if let Some(builder) = extensions.get_mut::<OtelData>() {
+                f(builder, &layer.tracer);
+            }
+        }
+    }
+
+    fn get_late_reparent_policy(dispatch: &tracing::Dispatch) -> LateReparentPolicy {
+        dispatch
+            .downcast_ref::<OpenTelemetryLayer<S, T>>()
+            .expect("layer should downcast to expected type; this is a bug!")
+            .late_reparent_policy
+    }
+
+    fn validate_parent_context(dispatch: &tracing::Dispatch, cx: &OtelContext) {
+        dispatch
+            .downcast_ref::<OpenTelemetryLayer<S, T>>()
+            .expect("layer should downcast to expected type; this is a bug!")
+            .validate_trace_context(cx)
+    }
+
+    /// Returns whether `name` is a special field, which is not recorded as an
+    /// attribute.
+    fn is_special_field(&self, name: &str) -> bool {
+        matches!(
+            name.strip_prefix(&*self.special_field_prefix),
+            Some(
+                SPAN_NAME_FIELD
+                    | SPAN_KIND_FIELD
+                    | SPAN_STATUS_CODE_FIELD
+                    | SPAN_STATUS_MESSAGE_FIELD
+                    | SPAN_ID_FIELD
+                    | SPAN_TRACE_ID_FIELD
+                    | SPAN_CODE_COLUMN_FIELD
+            )
+        )
+    }
+
+    /// Estimates the number of attributes of a new span.
+    fn span_attrs_capacity(&self, attrs: &Attributes<'_>) -> usize {
+        let fields = attrs
+            .fields()
+            .iter()
+            .filter(|field| {
+                let name = field.name();
+                !self.is_special_field(name)
+                    || self.location
+                        && name.strip_prefix(&*self.special_field_prefix)
+                            == Some(SPAN_CODE_COLUMN_FIELD)
+            })
+            .count();
+        fields + self.extra_span_attrs() + self.attributes_headroom
+    }
+
+    fn extra_span_attrs(&self) -> usize {
+        let mut extra_attrs = 0;
+        if self.location {
+            extra_attrs += 3;
+        }
+        if self.startup_window.is_some() {
+            extra_attrs += 1;
+        }
+        if self.runtime_attributes {
+            extra_attrs += 3;
+        }
+        if self.with_threads {
+            extra_attrs += 2;
+        }
+        if self.worker_name_fn.is_some() {
+            extra_attrs += 1;
+        }
+        if !self.target_versions.is_empty() {
+            extra_attrs += 1;
+        }
+        if self.span_target {
+            extra_attrs += 1;
+        }
+        if self.span_level {
+            extra_attrs += 1;
+        }
+        extra_attrs
+    }
+}
+
+/// The `process.runtime.description` of root spans, shared by all of them.
+static RUNTIME_DESCRIPTION: once_cell::sync::Lazy<StringValue> = once_cell::sync::Lazy::new(|| {
+    let description = format!("{} {}", std::env::consts::ARCH, std::env::consts::OS);
+    Arc::<str>::from(description).into()
+});
+
+/// The id and name of a thread, recorded as span attributes.
+struct ThreadInfo {
+    id: u64,
+    name: Option<StringValue>,
+}
+
+thread_local! {
+    static THREAD_INFO: unsync::Lazy<ThreadInfo> = unsync::Lazy::new(|| {
+        let thread = thread::current();
+        ThreadInfo {
+            // OpenTelemetry's semantic conventions require the thread ID to be
+            // recorded as an integer, but `std::thread::ThreadId` does not expose
+            // the integer value on stable, so we have to convert it to a `usize` by
+            // parsing it. Since this requires allocating a `String`, store it in a
+            // thread local so we only have to do this once.
+            // TODO(eliza): once `std::thread::ThreadId::as_u64` is stabilized
+            // (https://github.com/rust-lang/rust/issues/67939), just use that.
+            id: thread_id_integer(thread.id()),
+            // Shared by all the spans of the thread, so that it is only
+            // allocated once.
+            name: thread.name().map(|name| Arc::<str>::from(name).into()),
+        }
+    });
+}
+
+impl<S, T> Layer<S> for OpenTelemetryLayer<S, T>
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+    T: otel::Tracer + PreSampledTracer + 'static,
+{
+    /// Creates an [OpenTelemetry `Span`] for the corresponding [tracing `Span`].
+    ///
+    /// [OpenTelemetry `Span`]: opentelemetry::trace::Span
+    /// [tracing `Span`]: tracing::Span
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &span::Id, ctx: Context<'_, S>) {
+        let span = ctx.span(id).expect("Span not found, this is a bug");
+
+        if let Some(max_span_depth) = self.max_span_depth {
+            let depth = span
+                .parent()
+                .and_then(|parent| parent.extensions().get::<SpanDepth>().map(|d| d.0))
+                .unwrap_or(0)
+                + 1;
+            span.extensions_mut().insert(SpanDepth(depth));
+            if depth > max_span_depth {
+                if self.dropped_span_callback.is_some() {
+                    let builder = self
+                        .tracer
+                        .span_builder(attrs.metadata().name())
+                        .with_start_time(crate::time::now());
+                    self.drop_span(&builder, DropReason::MaxDepth);
+                }
+                return;
+            }
+        }
+
+        let mut extensions = span.extensions_mut();
+
+        let parent_cx = self.parent_context(attrs, id, &ctx);
+        let mut builder = self
+            .tracer
+            .span_builder(attrs.metadata().name())
+            .with_start_time(crate::time::now());
+
+        // Eagerly assign span id so children have stable parent id
+        if self.eager_span_id {
+            builder.span_id = Some(self.tracer.new_span_id());
+        }
+
+        // Record new trace id if there is no active parent span
+        if !parent_cx.has_active_span() {
+            builder.trace_id = Some(self.tracer.new_trace_id());
+        }
+
+        let (mut builder, parent_cx) = if self.skip_unsampled_enrichment {
+            let mut data = OtelData { builder, parent_cx };
+            self.tracer.sampled_context(&mut data);
+            if self.skips_enrichment(&data) {
+                extensions.insert(data);
+                return;
+            }
+            (data.builder, data.parent_cx)
+        } else {
+            (builder, parent_cx)
+        };
+
+        if self.tracked_inactivity && extensions.get_mut::<Timings>().is_none() {
+            extensions.insert(Timings::new());
+        }
+
+        let builder_attrs = builder
+            .attributes
+            .get_or_insert(Vec::with_capacity(self.span_attrs_capacity(attrs)));
+
+        if self.location {
+            let meta = attrs.metadata();
+
+            let keys = &self.span_code_keys;
+            if let Some(filename) = meta.file() {
+                builder_attrs.push(KeyValue::new(keys.filepath.clone(), filename));
+            }
+
+            if let Some(module) = meta.module_path() {
+                builder_attrs.push(KeyValue::new(keys.namespace.clone(), module));
+            }
+
+            if let Some(line) = meta.line() {
+                builder_attrs.push(KeyValue::new(keys.lineno.clone(), line as i64));
+            }
+        }
+
+        if self.with_threads {
+            THREAD_INFO.with(|info| {
+                let id = match &self.deterministic_thread_ids {
+                    Some(thread_ids) => thread_ids.id(info.id),
+                    None => info.id as i64,
+                };
+                builder_attrs.push(KeyValue::new("thread.id", id));
+                if let Some(name) = &info.name {
+                    builder_attrs.push(KeyValue::new("thread.name", name.clone()));
+                }
+            });
+        }
+
+        if let Some(worker_name) = self.worker_name_fn.as_ref().and_then(|f| f()) {
+            builder_attrs.push(KeyValue::new("worker.name", worker_name));
+        }
+
+        if let Some(version) = self.target_version(attrs.metadata().target()) {
+            builder_attrs.push(KeyValue::new("code.crate.version", version));
+        }
+
+        if self.span_target {
+            builder_attrs.push(KeyValue::new("target", attrs.metadata().target()));
+        }
+
+        if self.span_level {
+            builder_attrs.push(KeyValue::new(
+                self.span_level_key.clone(),
+                attrs.metadata().level().as_str(),
+            ));
+        }
+
+        if let Some(startup_window) = &self.startup_window {
+            if startup_window.is_open() {
+                builder_attrs.push(KeyValue::new(FIELD_APP_STARTUP, true));
+            }
+        }
+
+        if self.runtime_attributes && !parent_cx.has_active_span() {
+            builder_attrs.push(KeyValue::new(FIELD_PROCESS_RUNTIME_NAME, "rust"));
+            if let Some(version) = &self.runtime_version {
+                builder_attrs.push(KeyValue::new(
+                    FIELD_PROCESS_RUNTIME_VERSION,
+                    version.clone(),
+                ));
+            }
+            let description = match &self.runtime_description {
+                Some(description) => description.clone(),
+                None => RUNTIME_DESCRIPTION.clone(),
+            };
+            builder_attrs.push(KeyValue::new(
+                FIELD_PROCESS_RUNTIME_DESCRIPTION,
+                description,
+            ));
+        }
+
+        let mut updates = SpanBuilderUpdates::default();
+        attrs.record(&mut SpanAttributeVisitor {
+            span_builder_updates: &mut updates,
+            sem_conv_config: self.sem_conv_config,
+            attribute_namespace: self.attribute_namespace.as_deref(),
+            special_field_prefix: &self.special_field_prefix,
+            nonfinite_floats: self.nonfinite_floats,
+            key_length_limit: self.max_attribute_key_length.as_ref(),
+            unknown_span_kind_handler: self.unknown_span_kind_handler.as_ref(),
+        });
+
+        // Only root spans may pin their trace id, children always belong to
+        // the trace of their parent.
+        if parent_cx.has_active_span() {
+            updates.trace_id = None;
+        }
+        if let Some(column) = updates.code_column.filter(|_| self.location) {
+            builder
+                .attributes
+                .get_or_insert_with(Vec::new)
+                .push(KeyValue::new(self.span_code_column_key.clone(), column));
+        }
+        updates.update(&mut builder);
+        if builder.span_kind.is_none() {
+            builder.span_kind = self.span_kind_from_name(&builder.name);
+        }
+
+        if let Some(denylist) = &self.inherited_attributes_denylist {
+            let parent = attrs.parent().and_then(|id| ctx.span(id)).or_else(|| {
+                attrs
+                    .is_contextual()
+                    .then(|| ctx.lookup_current())
+                    .flatten()
+            });
+            if let Some(parent) = parent {
+                let parent_extensions = parent.extensions();
+                let parent_attributes = parent_extensions
+                    .get::<OtelData>()
+                    .and_then(|data| data.builder.attributes.as_ref());
+                if let Some(parent_attributes) = parent_attributes {
+                    let attributes = builder.attributes.get_or_insert_with(Vec::new);
+                    let inherited = parent_attributes
+                        .iter()
+                        .filter(|kv| {
+                            let key = kv.key.as_str();
+                            !denylist
+                                .iter()
+                                .any(|denied| match denied.strip_suffix('*') {
+                                    Some(prefix) => key.starts_with(prefix),
+                                    None => key == denied,
+                                })
+                                && !attributes.iter().any(|own| own.key == kv.key)
+                        })
+                        .cloned()
+                        .collect::<Vec<_>>();
+                    attributes.extend(inherited);
+                }
+            }
+        }
+
+        extensions.insert(OtelData { builder, parent_cx });
+    }
+
+    fn on_enter(&self, id: &span::Id, ctx: Context<'_, S>) {
+        if !self.tracked_inactivity && self.on_enter_attributes.is_none() {
+            return;
+        }
+
+        let span = ctx.span(id).expect("Span not found, this is a bug");
+        let mut extensions = span.extensions_mut();
+
+        if self.tracked_inactivity {
+            if let Some(timings) = extensions.get_mut::<Timings>() {
+                if self.concurrent_span_safe_timings {
+                    timings.entered_count += 1;
+                }
+                if !self.concurrent_span_safe_timings || timings.entered_count == 1 {
+                    let now = Instant::now();
+                    timings.add_idle(now - timings.last);
+                    timings.last = now;
+                    timings.scheduling_delay.get_or_insert(timings.idle);
+                }
+            }
+        }
+
+        if let Some(on_enter_attributes) = &self.on_enter_attributes {
+            if extensions.get_mut::<FirstEntered>().is_some() {
+                return;
+            }
+            extensions.insert(FirstEntered);
+
+            // Release the lock while calling user code, which may itself
+            // access this span's extensions.
+            drop(extensions);
+            let attributes = on_enter_attributes();
+
+            let mut extensions = span.extensions_mut();
+            if let Some(data) = extensions.get_mut::<OtelData>() {
+                data.builder
+                    .attributes
+                    .get_or_insert_with(Vec::new)
+                    .extend(attributes);
+            }
+        }
+    }
+
+    fn on_exit(&self, id: &span::Id, ctx: Context<'_, S>) {
+        if !self.tracked_inactivity {
+            return;
+        }
+
+        let span = ctx.span(id).expect("Span not found, this is a bug");
+        let mut extensions = span.extensions_mut();
+
+        if let Some(timings) = extensions.get_mut::<Timings>() {
+            if self.concurrent_span_safe_timings {
+                if timings.entered_count == 0 {
+                    if !self.unbalanced_exit_reported.swap(true, Ordering::Relaxed) {
+                        global::handle_error(global::Error::Other(format!(
+                            "[tracing-opentelemetry]: span {:?} exited without being \
+                            entered, ignoring this exit for busy/idle timings. Further \
+                            unbalanced exits are ignored silently.",
+                            id
+                        )));
+                    }
+                    return;
+                }
+                timings.entered_count -= 1;
+                if timings.entered_count > 0 {
+                    return;
+                }
+            }
+
+            let now = Instant::now();
+            timings.add_busy(now - timings.last);
+            timings.last = now;
+        }
+    }
+
+    /// Record OpenTelemetry [`attributes`] for the given values.
+    ///
+    /// [`attributes`]: opentelemetry::trace::SpanBuilder::attributes
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        let span = ctx.span(id).expect("Span not found, this is a bug");
+        if let Some(data) = span.extensions().get::<OtelData>() {
+            if self.skips_enrichment(data) {
+                return;
+            }
+        }
+        let mut updates = SpanBuilderUpdates::default();
+        values.record(&mut SpanAttributeVisitor {
+            span_builder_updates: &mut updates,
+            sem_conv_config: self.sem_conv_config,
+            attribute_namespace: self.attribute_namespace.as_deref(),
+            special_field_prefix: &self.special_field_prefix,
+            nonfinite_floats: self.nonfinite_floats,
+            key_length_limit: self.max_attribute_key_length.as_ref(),
+            unknown_span_kind_handler: self.unknown_span_kind_handler.as_ref(),
+        });
+        // The span and trace ids may already have been handed out to children,
+        // so they can only be pinned when the span is created.
+        updates.span_id = None;
+        updates.trace_id = None;
+        let mut extensions = span.extensions_mut();
+        if let Some(data) = extensions.get_mut::<OtelData>() {
+            updates.update(&mut data.builder);
+        }
+    }
+
+    fn on_follows_from(&self, id: &Id, follows: &Id, ctx: Context<S>) {
+        let span = ctx.span(id).expect("Span not found, this is a bug");
+        let mut extensions = span.extensions_mut();
+        // Spans nested deeper than the maximum span depth have no data, and
+        // are not exported, so links from or to them are dropped.
+        let Some(data) = extensions.get_mut::<OtelData>() else {
+            return;
+        };
+
+        // The follows span may be filtered away (or closed), from this layer,
+        // in which case we just drop the data, as opposed to panicking. This
+        // uses the same reasoning as `parent_context` above.
+        if let Some(follows_span) = ctx.span(follows) {
+            let mut follows_extensions = follows_span.extensions_mut();
+            let Some(follows_data) = follows_extensions.get_mut::<OtelData>() else {
+                return;
+            };
+
+            let follows_context = self
+                .tracer
+                .sampled_context(follows_data)
+                .span()
+                .span_context()
+                .clone();
+            let attributes = if self.follows_from_link_type {
+                vec![KeyValue::new(FIELD_LINK_TYPE, "follows_from")]
+            } else {
+                Vec::new()
+            };
+            let follows_link = otel::Link::new(follows_context, attributes);
+            if let Some(ref mut links) = data.builder.links {
+                links.push(follows_link);
+            } else {
+                data.builder.links = Some(vec![follows_link]);
+            }
+        }
+    }
+
+    /// Records OpenTelemetry [`Event`] data on event.
+    ///
+    /// Note: an [`ERROR`]-level event will also set the OpenTelemetry span status code to
+    /// [`Error`], signaling that an error has occurred. The level can be changed with
+    /// [`OpenTelemetryLayer::with_status_from_level`].
+    ///
+    /// [`Event`]: opentelemetry::trace::Event
+    /// [`ERROR`]: tracing::Level::ERROR
+    /// [`Error`]: opentelemetry::trace::StatusCode::Error
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        // Ignore events that are not in the context of a span
+        if let Some(span) = event.parent().and_then(|id| ctx.span(id)).or_else(|| {
             event
                 .is_contextual()
                 .then(|| ctx.lookup_current())
                 .flatten()
         }) {
+            // Events of spans beyond the maximum depth are recorded on their
+            // deepest exported ancestor.
+            let span = match self.max_span_depth {
+                Some(_) => span
+                    .scope()
+                    .find(|span| span.extensions().get::<OtelData>().is_some())
+                    .unwrap_or(span),
+                None => span,
+            };
+            if let Some(data) = span.extensions().get::<OtelData>() {
+                if self.skips_enrichment(data) {
+                    return;
+                }
+            }
+
             // Performing read operations before getting a write lock to avoid a deadlock
             // See https://github.com/tokio-rs/tracing/issues/763
             #[cfg(feature = "tracing-log")]
@@ -1056,385 +3343,2123 @@ where
             #[cfg(not(feature = "tracing-log"))]
             let meta = event.metadata();
 
-            let target = Key::new("target");
+            if let Some(event_filter) = &self.event_filter {
+                if !event_filter(event, meta) {
+                    return;
+                }
+            }
+
+            let target = Key::new("target");
+
+            #[cfg(feature = "tracing-log")]
+            let target = if normalized_meta.is_some() {
+                target.string(meta.target().to_owned())
+            } else {
+                target.string(event.metadata().target())
+            };
+
+            #[cfg(not(feature = "tracing-log"))]
+            let target = target.string(meta.target());
+
+            let mut event_attributes = Vec::with_capacity(2);
+            if self.event_level {
+                event_attributes.push(Key::new("level").string(meta.level().as_str()));
+            }
+            event_attributes.push(target);
+            let mut otel_event =
+                otel::Event::new(String::new(), crate::time::now(), event_attributes, 0);
+
+            let mut builder_updates = None;
+            event.record(&mut SpanEventVisitor {
+                event_builder: &mut otel_event,
+                span_builder_updates: &mut builder_updates,
+                sem_conv_config: self.sem_conv_config,
+                exception_event_name: self.exception_event_name,
+                nonfinite_floats: self.nonfinite_floats,
+                attribute_filter: self.event_attribute_filter.as_deref(),
+                message_as_event_name: self.message_as_event_name,
+            });
+            if let Some(limit) = &self.max_attribute_key_length {
+                for attribute in &mut otel_event.attributes {
+                    if let Some(key) = limit.truncate(&attribute.key) {
+                        attribute.key = key;
+                    }
+                }
+            }
+
+            let fields = event.metadata().fields();
+            if self.sem_conv_config.error_events_to_status
+                && self.sem_conv_config.error_status_description_from_message
+                && fields.field("error").is_some()
+                && fields.field("message").is_some()
+            {
+                let message = if self.message_as_event_name {
+                    otel_event.name.clone()
+                } else {
+                    otel_event
+                        .attributes
+                        .iter()
+                        .find(|kv| kv.key.as_str() == "message")
+                        .map(|kv| kv.value.as_str().into_owned().into())
+                        .unwrap_or_default()
+                };
+                let updates = builder_updates.get_or_insert_with(SpanBuilderUpdates::default);
+                updates.status = Some(otel::Status::error(message));
+                updates.implicit_status = true;
+            }
+            if otel_event.name.is_empty() && !self.message_as_event_name {
+                otel_event.name = event.metadata().name().into();
+            }
+
+            let mut extensions = span.extensions_mut();
+
+            if self.event_sequence_numbers {
+                let sequence = match extensions.get_mut::<EventSequence>() {
+                    Some(EventSequence(next)) => {
+                        *next += 1;
+                        *next - 1
+                    }
+                    None => {
+                        extensions.insert(EventSequence(1));
+                        0
+                    }
+                };
+                otel_event
+                    .attributes
+                    .push(KeyValue::new(FIELD_EVENT_SEQUENCE, sequence));
+            }
+
+            let otel_data = extensions.get_mut::<OtelData>();
+
+            if let Some(otel_data) = otel_data {
+                let builder = &mut otel_data.builder;
+
+                if builder.status == otel::Status::Unset
+                    && *meta.level() <= self.sem_conv_config.status_from_level
+                {
+                    builder.status = otel::Status::error("")
+                }
+
+                if let Some(builder_updates) = builder_updates {
+                    builder_updates.update(builder);
+                }
+
+                if self.location {
+                    #[cfg(not(feature = "tracing-log"))]
+                    let normalized_meta: Option<tracing_core::Metadata<'_>> = None;
+                    let (file, module) = match &normalized_meta {
+                        Some(meta) => (
+                            meta.file().map(|s| Value::from(s.to_owned())),
+                            meta.module_path().map(|s| Value::from(s.to_owned())),
+                        ),
+                        None => (
+                            event.metadata().file().map(Value::from),
+                            event.metadata().module_path().map(Value::from),
+                        ),
+                    };
+
+                    let keys = &self.event_code_keys;
+                    if let Some(file) = file {
+                        otel_event
+                            .attributes
+                            .push(KeyValue::new(keys.filepath.clone(), file));
+                    }
+                    if let Some(module) = module {
+                        otel_event
+                            .attributes
+                            .push(KeyValue::new(keys.namespace.clone(), module));
+                    }
+                    if let Some(line) = meta.line() {
+                        otel_event
+                            .attributes
+                            .push(KeyValue::new(keys.lineno.clone(), line as i64));
+                    }
+                }
+
+                if let Some(ref mut events) = builder.events {
+                    events.push(otel_event);
+                } else {
+                    builder.events = Some(vec![otel_event]);
+                }
+            }
+        };
+    }
+
+    /// Exports an OpenTelemetry [`Span`] on close.
+    ///
+    /// [`Span`]: opentelemetry::trace::Span
+    fn on_close(&self, id: span::Id, ctx: Context<'_, S>) {
+        let span = ctx.span(&id).expect("Span not found, this is a bug");
+        let mut extensions = span.extensions_mut();
+
+        if let Some(OtelData {
+            mut builder,
+            parent_cx,
+        }) = extensions.remove::<OtelData>()
+        {
+            if self.tracked_inactivity {
+                // Append busy/idle timings when enabled.
+                if let Some(timings) = extensions.get_mut::<Timings>() {
+                    let InactivityKeys { busy, idle } = &self.inactivity_keys;
+
+                    let attributes = builder
+                        .attributes
+                        .get_or_insert_with(|| Vec::with_capacity(2));
+                    let unit = self.inactivity_unit;
+                    if !busy.as_str().is_empty() {
+                        attributes.push(KeyValue::new(busy.clone(), unit.value(timings.busy)));
+                    }
+                    if !idle.as_str().is_empty() {
+                        attributes.push(KeyValue::new(idle.clone(), unit.value(timings.idle)));
+                    }
+                    if let Some(delay) = timings
+                        .scheduling_delay
+                        .filter(|_| self.scheduling_delay_attribute)
+                    {
+                        attributes.push(KeyValue::new(FIELD_SCHEDULING_DELAY, delay));
+                    }
+                }
+            }
+
+            let mut end_time = crate::time::now();
+            if let Some(start_time) = builder.start_time {
+                // Guard against the clock moving backward while the span was open.
+                if end_time < start_time {
+                    end_time = start_time;
+                    if self.clock_anomaly_attribute {
+                        builder
+                            .attributes
+                            .get_or_insert_with(Vec::new)
+                            .push(KeyValue::new("clock.anomaly", true));
+                    }
+                }
+            }
+
+            if self.span_id_attribute {
+                let parent_span = parent_cx.span();
+                let parent = parent_span.span_context();
+                let trace_id = if parent.is_valid() {
+                    Some(parent.trace_id())
+                } else {
+                    builder.trace_id
+                };
+                let format = self.id_format;
+                let ids = [
+                    builder
+                        .span_id
+                        .map(|id| KeyValue::new("otel.span_id", format.span_id(id))),
+                    trace_id.map(|id| KeyValue::new("otel.trace_id", format.trace_id(id))),
+                    parent.is_valid().then(|| {
+                        KeyValue::new("otel.parent_span_id", format.span_id(parent.span_id()))
+                    }),
+                ];
+                builder
+                    .attributes
+                    .get_or_insert_with(Vec::new)
+                    .extend(IntoIterator::into_iter(ids).flatten());
+            }
+
+            if self.duration_attribute {
+                let duration = builder
+                    .start_time
+                    .and_then(|start_time| end_time.duration_since(start_time).ok())
+                    .unwrap_or_default();
+                builder
+                    .attributes
+                    .get_or_insert_with(Vec::new)
+                    .push(KeyValue::new("duration_ns", duration.as_nanos() as i64));
+            }
+
+            if self.event_count_attribute {
+                let count = builder.events.as_ref().map_or(0, Vec::len);
+                builder
+                    .attributes
+                    .get_or_insert_with(Vec::new)
+                    .push(KeyValue::new(FIELD_EVENT_COUNT, count as i64));
+            }
+
+            if self.empty_field_markers {
+                let attributes = builder.attributes.get_or_insert_with(Vec::new);
+                for field in span.metadata().fields() {
+                    let name = field.name();
+                    if self.is_special_field(name) {
+                        continue;
+                    }
+                    let key = match &self.attribute_namespace {
+                        Some(namespace) => Key::new(format!("{}{}", namespace, name)),
+                        None => Key::new(name),
+                    };
+                    if !attributes.iter().any(|kv| kv.key == key) {
+                        attributes.push(KeyValue::new(key, ""));
+                    }
+                }
+            }
+
+            if self.baggage_attributes {
+                let baggage = parent_cx.baggage();
+                if !baggage.is_empty() {
+                    let attributes = builder.attributes.get_or_insert_with(Vec::new);
+                    for (key, (value, _metadata)) in baggage {
+                        let key = Key::new(format!("{}{}", BAGGAGE_ATTRIBUTE_PREFIX, key));
+                        if !attributes.iter().any(|kv| kv.key == key) {
+                            attributes.push(KeyValue::new(key, value.clone()));
+                        }
+                    }
+                }
+            }
+
+            if self.kind_inference && builder.span_kind.is_none() {
+                if let Some(attributes) = &builder.attributes {
+                    builder.span_kind = infer_span_kind(attributes);
+                }
+            }
+
+            #[cfg(feature = "json")]
+            if let Some(key) = &self.json_attribute_bundle {
+                let attributes = builder.attributes.get_or_insert_with(Vec::new);
+                let prefix = &*self.special_field_prefix;
+                let bundle = json_attribute_bundle(
+                    attributes
+                        .iter()
+                        .filter(|kv| !kv.key.as_str().starts_with(prefix)),
+                );
+                if self.json_attribute_bundle_replacing {
+                    attributes.retain(|kv| kv.key.as_str().starts_with(prefix));
+                }
+                attributes.push(KeyValue::new(key.clone(), bundle));
+            }
+
+            if self.sorted_events {
+                if let Some(events) = builder.events.as_mut() {
+                    events.sort_by_key(|event| event.timestamp);
+                }
+            }
+
+            if !self.attribute_priority.is_empty() {
+                if let Some(attributes) = builder.attributes.as_mut() {
+                    let priority = &self.attribute_priority;
+                    attributes.sort_by_key(|kv| {
+                        priority
+                            .iter()
+                            .position(|key| *key == kv.key.as_str())
+                            .unwrap_or(priority.len())
+                    });
+                }
+            }
+
+            // Assign end time, build and start span, drop span to export
+            let builder = builder.with_end_time(end_time);
+            if !self.export_on_error_only {
+                builder.start_with_context(&self.tracer, &parent_cx);
+                return;
+            }
+
+            let has_error = matches!(builder.status, otel::Status::Error { .. });
+            let root = span
+                .scope()
+                .last()
+                .expect("scope should contain at least the current span");
+            if root.id() == id {
+                let buffer = extensions.remove::<ErrorOnlyBuffer>().unwrap_or_default();
+                drop(extensions);
+                if has_error || buffer.has_error {
+                    for (builder, parent_cx) in buffer.spans {
+                        builder.start_with_context(&self.tracer, &parent_cx);
+                    }
+                    builder.start_with_context(&self.tracer, &parent_cx);
+                } else {
+                    for (builder, _) in &buffer.spans {
+                        self.drop_span(builder, DropReason::NoErrorInTrace);
+                    }
+                    self.drop_span(&builder, DropReason::NoErrorInTrace);
+                }
+            } else {
+                drop(extensions);
+                let mut root_extensions = root.extensions_mut();
+                if root_extensions.get_mut::<ErrorOnlyBuffer>().is_none() {
+                    root_extensions.insert(ErrorOnlyBuffer::default());
+                }
+                let buffer = root_extensions
+                    .get_mut::<ErrorOnlyBuffer>()
+                    .expect("buffer was just inserted");
+                buffer.has_error |= has_error;
+                if buffer.spans.len() < MAX_BUFFERED_SPANS_PER_TRACE {
+                    buffer.spans.push((builder, parent_cx));
+                } else {
+                    drop(root_extensions);
+                    self.drop_span(&builder, DropReason::BufferFull);
+                }
+            }
+        }
+    }
+
+    // SAFETY: this is safe because the `WithContext` function pointer is valid
+    // for the lifetime of `&self`.
+    unsafe fn downcast_raw(&self, id: TypeId) -> Option<*const ()> {
+        match id {
+            id if id == TypeId::of::<Self>() => Some(self as *const _ as *const ()),
+            id if id == TypeId::of::<WithContext>() => {
+                Some(&self.get_context as *const _ as *const ())
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Attribute keys used to record source code locations.
+#[derive(Clone)]
+struct CodeKeys {
+    filepath: Key,
+    namespace: Key,
+    lineno: Key,
+}
+
+impl CodeKeys {
+    fn for_schema(schema: CodeAttributeSchema) -> Self {
+        match schema {
+            CodeAttributeSchema::Legacy => CodeKeys::default(),
+            CodeAttributeSchema::Current => CodeKeys {
+                filepath: Key::from_static_str(FIELD_CODE_FILE_PATH),
+                namespace: Key::from_static_str(FIELD_CODE_NAMESPACE),
+                lineno: Key::from_static_str(FIELD_CODE_LINE_NUMBER),
+            },
+        }
+    }
+}
+
+impl Default for CodeKeys {
+    fn default() -> Self {
+        CodeKeys {
+            filepath: Key::from_static_str(FIELD_CODE_FILEPATH),
+            namespace: Key::from_static_str(FIELD_CODE_NAMESPACE),
+            lineno: Key::from_static_str(FIELD_CODE_LINENO),
+        }
+    }
+}
+
+/// Attribute keys used to record busy and idle timings.
+struct InactivityKeys {
+    busy: Key,
+    idle: Key,
+}
+
+impl InactivityKeys {
+    fn for_unit(unit: InactivityUnit) -> Self {
+        let (busy, idle) = match unit {
+            InactivityUnit::Nanos => ("busy_ns", "idle_ns"),
+            InactivityUnit::Seconds => ("busy", "idle"),
+        };
+        InactivityKeys {
+            busy: Key::from_static_str(busy),
+            idle: Key::from_static_str(idle),
+        }
+    }
+}
+
+impl Default for InactivityKeys {
+    fn default() -> Self {
+        InactivityKeys::for_unit(InactivityUnit::default())
+    }
+}
+
+/// Sequential ids assigned to threads in the order they are first seen.
+#[derive(Default)]
+struct DeterministicThreadIds(Mutex<HashMap<u64, i64>>);
+
+impl DeterministicThreadIds {
+    /// Returns the sequential id of the thread with the given
+    /// [`ThreadInfo`] id, assigning the next one the first time the thread is
+    /// seen.
+    fn id(&self, thread_id: u64) -> i64 {
+        let mut ids = self.0.lock().unwrap();
+        let next = ids.len() as i64 + 1;
+        *ids.entry(thread_id).or_insert(next)
+    }
+}
+
+/// Time window after which spans are no longer marked as startup spans.
+struct StartupWindow {
+    end: Instant,
+    closed: AtomicBool,
+}
+
+impl StartupWindow {
+    fn new(window: Duration) -> Self {
+        StartupWindow {
+            end: Instant::now() + window,
+            closed: AtomicBool::new(false),
+        }
+    }
+
+    fn is_open(&self) -> bool {
+        if self.closed.load(Ordering::Relaxed) {
+            return false;
+        }
+        if Instant::now() < self.end {
+            return true;
+        }
+        self.closed.store(true, Ordering::Relaxed);
+        false
+    }
+}
+
+/// Maximum length of attribute keys.
+struct KeyLengthLimit {
+    max_length: usize,
+    reported: AtomicBool,
+}
+
+impl KeyLengthLimit {
+    /// Length of the `~` separator and hex-encoded hash ending truncated keys.
+    const HASH_SUFFIX_LEN: usize = 9;
+
+    fn new(max_length: usize) -> Self {
+        KeyLengthLimit {
+            max_length,
+            reported: AtomicBool::new(false),
+        }
+    }
+
+    /// Returns the truncated key, or `None` if the key is short enough.
+    fn truncate(&self, key: &Key) -> Option<Key> {
+        let key = key.as_str();
+        if key.len() <= self.max_length {
+            return None;
+        }
+        if !self.reported.swap(true, Ordering::Relaxed) {
+            global::handle_error(global::Error::Other(format!(
+                "[tracing-opentelemetry]: attribute key `{}` is longer than {} bytes \
+                and was truncated. Other long keys will be truncated silently.",
+                key, self.max_length
+            )));
+        }
+
+        // Limits too short to hold the hash cut keys off, at the risk of collisions.
+        let hashed = self.max_length > Self::HASH_SUFFIX_LEN;
+        let mut end = if hashed {
+            self.max_length - Self::HASH_SUFFIX_LEN
+        } else {
+            self.max_length
+        };
+        while !key.is_char_boundary(end) {
+            end -= 1;
+        }
+        if !hashed {
+            return Some(Key::new(key[..end].to_owned()));
+        }
+
+        Some(Key::new(format!("{}~{:08x}", &key[..end], fnv1a(key))))
+    }
+}
+
+/// Marks spans whose on-enter attributes have already been recorded.
+struct FirstEntered;
+
+/// Depth of a span in its tree, with root spans at depth 1. Only tracked when
+/// a maximum span depth is configured.
+struct SpanDepth(usize);
+
+/// Sequence number of the next event recorded on a span.
+struct EventSequence(i64);
+
+/// Closed spans of a trace, buffered on the root span until it closes when
+/// only traces containing errors are exported.
+#[derive(Default)]
+struct ErrorOnlyBuffer {
+    spans: Vec<(SpanBuilder, OtelContext)>,
+    has_error: bool,
+}
+
+struct Timings {
+    idle: i64,
+    busy: i64,
+    last: Instant,
+    entered_count: u64,
+    /// The idle time when the span was first entered.
+    scheduling_delay: Option<i64>,
+}
+
+impl Timings {
+    fn new() -> Self {
+        Self {
+            idle: 0,
+            busy: 0,
+            last: Instant::now(),
+            entered_count: 0,
+            scheduling_delay: None,
+        }
+    }
+
+    fn add_idle(&mut self, elapsed: Duration) {
+        self.idle = self.idle.saturating_add(saturating_nanos(elapsed));
+    }
+
+    fn add_busy(&mut self, elapsed: Duration) {
+        self.busy = self.busy.saturating_add(saturating_nanos(elapsed));
+    }
+}
+
+/// Returns the number of nanoseconds in `duration`, clamped to `i64::MAX`.
+fn saturating_nanos(duration: Duration) -> i64 {
+    i64::try_from(duration.as_nanos()).unwrap_or(i64::MAX)
+}
+
+/// Returns the 32-bit FNV-1a hash of `value`, which unlike the hashers of
+/// the standard library is fixed by definition.
+fn fnv1a(value: &str) -> u32 {
+    value.bytes().fold(0x811c_9dc5, |hash, byte| {
+        (hash ^ u32::from(byte)).wrapping_mul(0x0100_0193)
+    })
+}
+
+fn thread_id_integer(id: thread::ThreadId) -> u64 {
+    parse_thread_id(&format!("{:?}", id)).unwrap_or_else(|| {
+        // The `Debug` format of `ThreadId` is not guaranteed, fall back to a
+        // hash of the ID, which is still unique enough to tell threads apart.
+        let mut hasher = DefaultHasher::new();
+        id.hash(&mut hasher);
+        hasher.finish()
+    })
+}
+
+/// Parses the integer value of a `ThreadId` from its `Debug` output, e.g.
+/// `ThreadId(1)`.
+fn parse_thread_id(thread_id: &str) -> Option<u64> {
+    thread_id
+        .strip_prefix("ThreadId(")?
+        .strip_suffix(')')?
+        .parse()
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use opentelemetry::trace::TraceFlags;
+    use std::{
+        collections::HashMap,
+        error::Error,
+        fmt::Display,
+        sync::{Arc, Mutex},
+        time::SystemTime,
+    };
+    use tracing::Level;
+    use tracing_subscriber::prelude::*;
+
+    #[derive(Debug, Clone)]
+    struct TestTracer(Arc<Mutex<Option<OtelData>>>);
+    impl otel::Tracer for TestTracer {
+        type Span = noop::NoopSpan;
+        fn start_with_context<T>(&self, _name: T, _context: &OtelContext) -> Self::Span
+        where
+            T: Into<Cow<'static, str>>,
+        {
+            noop::NoopSpan::DEFAULT
+        }
+        fn span_builder<T>(&self, name: T) -> otel::SpanBuilder
+        where
+            T: Into<Cow<'static, str>>,
+        {
+            otel::SpanBuilder::from_name(name)
+        }
+        fn build_with_context(
+            &self,
+            builder: otel::SpanBuilder,
+            parent_cx: &OtelContext,
+        ) -> Self::Span {
+            *self.0.lock().unwrap() = Some(OtelData {
+                builder,
+                parent_cx: parent_cx.clone(),
+            });
+            noop::NoopSpan::DEFAULT
+        }
+    }
+
+    impl PreSampledTracer for TestTracer {
+        fn sampled_context(&self, _builder: &mut crate::OtelData) -> OtelContext {
+            OtelContext::new()
+        }
+        fn new_trace_id(&self) -> otel::TraceId {
+            otel::TraceId::INVALID
+        }
+        fn new_span_id(&self) -> otel::SpanId {
+            otel::SpanId::INVALID
+        }
+    }
+
+    impl TestTracer {
+        fn with_data<T>(&self, f: impl FnOnce(&OtelData) -> T) -> T {
+            let lock = self.0.lock().unwrap();
+            let data = lock.as_ref().expect("no span data has been recorded yet");
+            f(data)
+        }
+    }
+
+    /// Moves the last timing checkpoint of `span` back by `elapsed`, as if that
+    /// much time had passed since the span was created, entered or exited.
+    fn advance_timings(span: &tracing::Span, elapsed: Duration) {
+        span.with_subscriber(|(id, dispatch)| {
+            let registry = dispatch
+                .downcast_ref::<tracing_subscriber::Registry>()
+                .unwrap();
+            let span = registry.span(id).unwrap();
+            let mut extensions = span.extensions_mut();
+            let timings = extensions.get_mut::<Timings>().unwrap();
+            timings.last = timings.last.checked_sub(elapsed).unwrap();
+        });
+    }
+
+    #[derive(Debug, Clone)]
+    struct TestSpan(otel::SpanContext);
+    impl otel::Span for TestSpan {
+        fn add_event_with_timestamp<T: Into<Cow<'static, str>>>(
+            &mut self,
+            _: T,
+            _: SystemTime,
+            _: Vec<KeyValue>,
+        ) {
+        }
+        fn span_context(&self) -> &otel::SpanContext {
+            &self.0
+        }
+        fn is_recording(&self) -> bool {
+            false
+        }
+        fn set_attribute(&mut self, _attribute: KeyValue) {}
+        fn set_status(&mut self, _status: otel::Status) {}
+        fn update_name<T: Into<Cow<'static, str>>>(&mut self, _new_name: T) {}
+        fn end_with_timestamp(&mut self, _timestamp: SystemTime) {}
+    }
+
+    #[derive(Debug)]
+    struct TestDynError {
+        msg: &'static str,
+        source: Option<Box<TestDynError>>,
+    }
+    impl Display for TestDynError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "{}", self.msg)
+        }
+    }
+    impl Error for TestDynError {
+        fn source(&self) -> Option<&(dyn Error + 'static)> {
+            match &self.source {
+                Some(source) => Some(source),
+                None => None,
+            }
+        }
+    }
+    impl TestDynError {
+        fn new(msg: &'static str) -> Self {
+            Self { msg, source: None }
+        }
+        fn with_parent(self, parent_msg: &'static str) -> Self {
+            Self {
+                msg: parent_msg,
+                source: Some(Box::new(self)),
+            }
+        }
+    }
+
+    #[test]
+    fn dynamic_span_names() {
+        let dynamic_name = "GET http://example.com".to_string();
+        let tracer = TestTracer(Arc::new(Mutex::new(None)));
+        let subscriber = tracing_subscriber::registry().with(layer().with_tracer(tracer.clone()));
+
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::debug_span!("static_name", otel.name = dynamic_name.as_str());
+        });
+
+        let recorded_name = tracer
+            .0
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map(|b| b.builder.name.clone());
+        assert_eq!(recorded_name, Some(dynamic_name.into()))
+    }
+
+    #[test]
+    fn attribute_namespace() {
+        let tracer = TestTracer(Arc::new(Mutex::new(None)));
+        let subscriber = tracing_subscriber::registry().with(
+            layer()
+                .with_tracer(tracer.clone())
+                .with_attribute_namespace("app."),
+        );
+
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::debug_span!("request", foo = 1, bar = tracing::field::Empty);
+            span.record("bar", "value");
+        });
+
+        let attributes = tracer.with_data(|data| data.builder.attributes.as_ref().unwrap().clone());
+        let keys = attributes
+            .iter()
+            .map(|kv| kv.key.as_str())
+            .collect::<Vec<&str>>();
+        assert!(keys.contains(&"app.foo"));
+        assert!(keys.contains(&"app.bar"));
+        assert!(keys.contains(&"code.filepath"));
+        assert!(!keys.contains(&"foo"));
+    }
+
+    #[test]
+    fn special_field_prefix() {
+        let tracer = TestTracer(Arc::new(Mutex::new(None)));
+        let subscriber = tracing_subscriber::registry().with(
+            layer()
+                .with_tracer(tracer.clone())
+                .with_special_field_prefix("_otel."),
+        );
+
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::debug_span!("request", _otel.name = "renamed", otel.name = "business");
+        });
+
+        let (name, attributes) = tracer.with_data(|data| {
+            (
+                data.builder.name.clone(),
+                data.builder.attributes.as_ref().unwrap().clone(),
+            )
+        });
+        assert_eq!(name, "renamed");
+        assert!(attributes
+            .iter()
+            .any(|kv| kv.key.as_str() == "otel.name" && kv.value == Value::from("business")));
+        assert!(!attributes.iter().any(|kv| kv.key.as_str() == "_otel.name"));
+    }
+
+    #[test]
+    fn infers_span_kind() {
+        let infer = |attributes: &[(&'static str, &'static str)]| {
+            let attributes = attributes
+                .iter()
+                .map(|(key, value)| KeyValue::new(*key, *value))
+                .collect::<Vec<_>>();
+            infer_span_kind(&attributes)
+        };
+
+        assert_eq!(
+            infer(&[
+                ("messaging.system", "kafka"),
+                ("messaging.operation", "publish")
+            ]),
+            Some(SpanKind::Producer)
+        );
+        assert_eq!(
+            infer(&[("messaging.operation", "process")]),
+            Some(SpanKind::Consumer)
+        );
+        assert_eq!(infer(&[("rpc.system", "grpc")]), Some(SpanKind::Client));
+        assert_eq!(
+            infer(&[("rpc.system", "grpc"), ("client.address", "10.0.0.1")]),
+            Some(SpanKind::Server)
+        );
+        assert_eq!(
+            infer(&[
+                ("http.request.method", "GET"),
+                ("url.full", "https://example.com")
+            ]),
+            Some(SpanKind::Client)
+        );
+        assert_eq!(
+            infer(&[("http.request.method", "GET"), ("http.route", "/users")]),
+            Some(SpanKind::Server)
+        );
+        assert_eq!(
+            infer(&[("db.system", "postgresql")]),
+            Some(SpanKind::Client)
+        );
+        assert_eq!(infer(&[("foo", "bar")]), None);
+    }
+
+    #[test]
+    fn span_kind_from_name() {
+        let kind = |name: &'static str, explicit: Option<&str>| {
+            let tracer = TestTracer(Arc::new(Mutex::new(None)));
+            let subscriber = tracing_subscriber::registry().with(
+                layer()
+                    .with_tracer(tracer.clone())
+                    .with_span_kind_from_name_suffix(vec![
+                        ("db.*", SpanKind::Client),
+                        ("*.publish", SpanKind::Producer),
+                        ("request", SpanKind::Server),
+                    ]),
+            );
+            tracing::subscriber::with_default(subscriber, || match explicit {
+                Some(kind) => {
+                    tracing::debug_span!("span", otel.name = name, otel.kind = kind);
+                }
+                None => {
+                    tracing::debug_span!("span", otel.name = name);
+                }
+            });
+            tracer.with_data(|data| data.builder.span_kind.clone())
+        };
+
+        assert_eq!(kind("db.query", None), Some(SpanKind::Client));
+        assert_eq!(kind("orders.publish", None), Some(SpanKind::Producer));
+        assert_eq!(kind("request", None), Some(SpanKind::Server));
+        assert_eq!(kind("request.body", None), None);
+        assert_eq!(kind("db.query", Some("internal")), Some(SpanKind::Internal));
+    }
+
+    #[test]
+    fn kind_inference_keeps_explicit_kind() {
+        let tracer = TestTracer(Arc::new(Mutex::new(None)));
+        let subscriber = tracing_subscriber::registry().with(
+            layer()
+                .with_tracer(tracer.clone())
+                .with_kind_inference(true),
+        );
+
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::debug_span!("inferred", http.request.method = "GET");
+        });
+        let kind = tracer.with_data(|data| data.builder.span_kind.clone());
+        assert_eq!(kind, Some(SpanKind::Server));
+
+        tracing::subscriber::with_default(
+            tracing_subscriber::registry().with(
+                layer()
+                    .with_tracer(tracer.clone())
+                    .with_kind_inference(true),
+            ),
+            || {
+                tracing::debug_span!(
+                    "explicit",
+                    otel.kind = "internal",
+                    http.request.method = "GET"
+                );
+            },
+        );
+        let kind = tracer.with_data(|data| data.builder.span_kind.clone());
+        assert_eq!(kind, Some(SpanKind::Internal));
+    }
+
+    #[test]
+    fn inherits_parent_attributes() {
+        let tracer = TestTracer(Arc::new(Mutex::new(None)));
+        let subscriber = tracing_subscriber::registry().with(
+            layer()
+                .with_tracer(tracer.clone())
+                .with_parent_based_attribute_inheritance(vec!["secret", "code.*"]),
+        );
+
+        let mut attributes = Vec::new();
+        tracing::subscriber::with_default(subscriber, || {
+            let _parent =
+                tracing::debug_span!("parent", tenant = "acme", secret = "hunter2", user = "a")
+                    .entered();
+            let child = tracing::debug_span!("child", user = "b");
+            child.with_subscriber(|(id, subscriber)| {
+                let get_context = subscriber.downcast_ref::<WithContext>().unwrap();
+                get_context.with_context(subscriber, id, |data, _tracer| {
+                    attributes = data.builder.attributes.clone().unwrap();
+                });
+            });
+        });
+
+        let values = |key: &str| {
+            attributes
+                .iter()
+                .filter(|kv| kv.key.as_str() == key)
+                .map(|kv| kv.value.clone())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(values("tenant"), vec![Value::from("acme")]);
+        assert_eq!(values("user"), vec![Value::from("b")]);
+        assert!(values("secret").is_empty());
+        assert_eq!(values("code.filepath").len(), 1);
+    }
+
+    #[test]
+    fn span_kind() {
+        let tracer = TestTracer(Arc::new(Mutex::new(None)));
+        let subscriber = tracing_subscriber::registry().with(layer().with_tracer(tracer.clone()));
+
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::debug_span!("request", otel.kind = "server");
+        });
+
+        let recorded_kind = tracer.with_data(|data| data.builder.span_kind.clone());
+        assert_eq!(recorded_kind, Some(otel::SpanKind::Server))
+    }
+
+    #[test]
+    fn unknown_span_kind_handler() {
+        let span_kind = |kind: &str| {
+            let tracer = TestTracer(Arc::new(Mutex::new(None)));
+            let subscriber = tracing_subscriber::registry().with(
+                layer()
+                    .with_tracer(tracer.clone())
+                    .with_unknown_span_kind_handler(|kind| {
+                        (kind == "db").then_some(otel::SpanKind::Client)
+                    }),
+            );
+
+            tracing::subscriber::with_default(subscriber, || {
+                tracing::debug_span!("request", otel.kind = kind);
+            });
+            tracer.with_data(|data| data.builder.span_kind.clone())
+        };
+
+        assert_eq!(span_kind("db"), Some(otel::SpanKind::Client));
+        assert_eq!(span_kind("server"), Some(otel::SpanKind::Server));
+        assert_eq!(span_kind("queue"), None);
+    }
+
+    #[test]
+    fn pinned_span_id() {
+        let tracer = TestTracer(Arc::new(Mutex::new(None)));
+        let subscriber = tracing_subscriber::registry().with(layer().with_tracer(tracer.clone()));
+
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::debug_span!("pinned", otel.span_id = "00f067aa0ba902b7");
+        });
+        let pinned = tracer.with_data(|data| data.builder.span_id);
+        assert_eq!(pinned, Some(otel::SpanId::from(0x00f0_67aa_0ba9_02b7)));
+
+        tracing::subscriber::with_default(
+            tracing_subscriber::registry().with(layer().with_tracer(tracer.clone())),
+            || {
+                tracing::debug_span!("invalid", otel.span_id = "not hex");
+            },
+        );
+        let generated = tracer.with_data(|data| data.builder.span_id);
+        assert_eq!(generated, Some(otel::SpanId::INVALID));
+    }
+
+    #[test]
+    fn id_format_applies_to_all_ids() {
+        let tracer = TestTracer(Arc::new(Mutex::new(None)));
+        let subscriber = tracing_subscriber::registry().with(
+            layer()
+                .with_tracer(tracer.clone())
+                .with_attribute_from_span_id(true)
+                .with_id_format(IdFormat::Decimal),
+        );
+        let parent_cx = OtelContext::current_with_span(TestSpan(otel::SpanContext::new(
+            otel::TraceId::from(42u128),
+            otel::SpanId::from(7u64),
+            TraceFlags::default(),
+            false,
+            Default::default(),
+        )));
+        let _g = parent_cx.attach();
+
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::debug_span!("request", otel.span_id = "0000000000000010");
+        });
+
+        let attributes = tracer.with_data(|data| data.builder.attributes.clone().unwrap());
+        let attribute = |key: &str| {
+            attributes
+                .iter()
+                .find(|kv| kv.key.as_str() == key)
+                .map(|kv| kv.value.clone())
+        };
+        assert_eq!(attribute("otel.span_id"), Some(Value::from("16")));
+        assert_eq!(attribute("otel.trace_id"), Some(Value::from("42")));
+        assert_eq!(attribute("otel.parent_span_id"), Some(Value::from("7")));
+    }
+
+    #[test]
+    fn span_status_code() {
+        let tracer = TestTracer(Arc::new(Mutex::new(None)));
+        let subscriber = tracing_subscriber::registry().with(layer().with_tracer(tracer.clone()));
+
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::debug_span!("request", otel.status_code = ?otel::Status::Ok);
+        });
+
+        let recorded_status = tracer.with_data(|data| data.builder.status.clone());
+        assert_eq!(recorded_status, otel::Status::Ok)
+    }
+
+    #[test]
+    fn span_status_message() {
+        let tracer = TestTracer(Arc::new(Mutex::new(None)));
+        let subscriber = tracing_subscriber::registry().with(layer().with_tracer(tracer.clone()));
+
+        let message = "message";
+
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::debug_span!("request", otel.status_message = message);
+        });
+
+        let recorded_status_message = tracer
+            .0
+            .lock()
+            .unwrap()
+            .as_ref()
+            .unwrap()
+            .builder
+            .status
+            .clone();
+
+        assert_eq!(recorded_status_message, otel::Status::error(message))
+    }
+
+    #[test]
+    fn explicit_ok_status_wins_over_error_events() {
+        let tracer = TestTracer(Arc::new(Mutex::new(None)));
+        let subscriber = tracing_subscriber::registry().with(layer().with_tracer(tracer.clone()));
+
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::debug_span!("ok_first", otel.status_code = "ok").in_scope(|| {
+                tracing::error!(error = "recovered");
+            });
+            let status = tracer.with_data(|data| data.builder.status.clone());
+            assert_eq!(status, otel::Status::Ok);
+
+            let span = tracing::debug_span!("ok_last", otel.status_code = tracing::field::Empty);
+            span.in_scope(|| tracing::error!(error = "recovered"));
+            span.record("otel.status_code", "ok");
+            drop(span);
+            let status = tracer.with_data(|data| data.builder.status.clone());
+            assert_eq!(status, otel::Status::Ok);
+        });
+    }
+
+    #[test]
+    fn error_status_description_from_message() {
+        let status = |from_message: bool| {
+            let tracer = TestTracer(Arc::new(Mutex::new(None)));
+            let subscriber = tracing_subscriber::registry().with(
+                layer()
+                    .with_tracer(tracer.clone())
+                    .with_error_status_description_from_message(from_message),
+            );
+            tracing::subscriber::with_default(subscriber, || {
+                tracing::debug_span!("request").in_scope(|| {
+                    tracing::error!(error = "connection refused", "failed to load config");
+                });
+            });
+            tracer.with_data(|data| data.builder.status.clone())
+        };
+
+        assert_eq!(status(false), otel::Status::error(""));
+        assert_eq!(status(true), otel::Status::error("failed to load config"));
+    }
+
+    #[test]
+    fn span_status_code_and_message_in_any_order() {
+        let tracer = TestTracer(Arc::new(Mutex::new(None)));
+        let subscriber = tracing_subscriber::registry().with(layer().with_tracer(tracer.clone()));
+
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::debug_span!(
+                "request",
+                otel.status_code = "error",
+                otel.status_message = "boom"
+            );
+            let status = tracer.with_data(|data| data.builder.status.clone());
+            assert_eq!(status, otel::Status::error("boom"));
+
+            tracing::debug_span!(
+                "request",
+                otel.status_message = "boom",
+                otel.status_code = "error"
+            );
+            let status = tracer.with_data(|data| data.builder.status.clone());
+            assert_eq!(status, otel::Status::error("boom"));
+        });
+    }
+
+    #[test]
+    fn trace_id_from_existing_context() {
+        let tracer = TestTracer(Arc::new(Mutex::new(None)));
+        let subscriber = tracing_subscriber::registry().with(layer().with_tracer(tracer.clone()));
+        let trace_id = otel::TraceId::from(42u128);
+        let existing_cx = OtelContext::current_with_span(TestSpan(otel::SpanContext::new(
+            trace_id,
+            otel::SpanId::from(1u64),
+            TraceFlags::default(),
+            false,
+            Default::default(),
+        )));
+        let _g = existing_cx.attach();
+
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::debug_span!("request", otel.kind = "server");
+        });
+
+        let recorded_trace_id =
+            tracer.with_data(|data| data.parent_cx.span().span_context().trace_id());
+        assert_eq!(recorded_trace_id, trace_id)
+    }
+
+    #[test]
+    fn includes_timings() {
+        let tracer = TestTracer(Arc::new(Mutex::new(None)));
+        let subscriber = tracing_subscriber::registry().with(
+            layer()
+                .with_tracer(tracer.clone())
+                .with_tracked_inactivity(true),
+        );
+
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::debug_span!("request");
+        });
+
+        let attributes = tracer.with_data(|data| data.builder.attributes.as_ref().unwrap().clone());
+        let keys = attributes
+            .iter()
+            .map(|kv| kv.key.as_str())
+            .collect::<Vec<&str>>();
+        assert!(keys.contains(&"idle_ns"));
+        assert!(keys.contains(&"busy_ns"));
+    }
+
+    #[test]
+    fn timings_in_seconds() {
+        let tracer = TestTracer(Arc::new(Mutex::new(None)));
+        let subscriber = tracing_subscriber::registry().with(
+            layer()
+                .with_tracer(tracer.clone())
+                .with_tracked_inactivity(true)
+                .with_inactivity_unit(InactivityUnit::Seconds),
+        );
+
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::debug_span!("request");
+            let _guard = span.enter();
+            advance_timings(&span, Duration::from_millis(1500));
+        });
+
+        let timing = |key: &str| {
+            tracer.with_data(|data| {
+                data.builder
+                    .attributes
+                    .as_ref()
+                    .unwrap()
+                    .iter()
+                    .find(|kv| kv.key.as_str() == key)
+                    .map(|kv| kv.value.clone())
+            })
+        };
+        let Some(Value::F64(busy)) = timing("busy") else {
+            panic!("expected busy attribute, got {:?}", timing("busy"));
+        };
+        assert!((1.5..2.0).contains(&busy));
+        assert!(matches!(timing("idle"), Some(Value::F64(idle)) if idle >= 0.0));
+        assert_eq!(timing("busy_ns"), None);
+
+        let Value::F64(seconds) = InactivityUnit::Seconds.value(1_500_000_000) else {
+            unreachable!();
+        };
+        assert!((seconds - 1.5).abs() < f64::EPSILON);
+        assert_eq!(InactivityUnit::Nanos.value(1_500), Value::I64(1_500));
+    }
+
+    #[test]
+    fn custom_timing_names() {
+        let tracer = TestTracer(Arc::new(Mutex::new(None)));
+        let subscriber = tracing_subscriber::registry().with(
+            layer()
+                .with_tracer(tracer.clone())
+                .with_tracked_inactivity(true)
+                .with_inactivity_attribute_names("timing.busy", ""),
+        );
+
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::debug_span!("request");
+        });
+
+        let attributes = tracer.with_data(|data| data.builder.attributes.as_ref().unwrap().clone());
+        let keys = attributes
+            .iter()
+            .map(|kv| kv.key.as_str())
+            .collect::<Vec<&str>>();
+        assert!(keys.contains(&"timing.busy"));
+        assert!(!keys.contains(&"busy_ns"));
+        assert!(!keys.contains(&"idle_ns"));
+        assert!(!keys.contains(&""));
+    }
 
-            #[cfg(feature = "tracing-log")]
-            let target = if normalized_meta.is_some() {
-                target.string(meta.target().to_owned())
-            } else {
-                target.string(event.metadata().target())
-            };
+    #[test]
+    fn unbalanced_exit_does_not_corrupt_timings() {
+        let tracer = TestTracer(Arc::new(Mutex::new(None)));
+        let subscriber = tracing_subscriber::registry().with(
+            layer()
+                .with_tracer(tracer.clone())
+                .with_tracked_inactivity(true)
+                .with_concurrent_span_safe_timings(true),
+        );
 
-            #[cfg(not(feature = "tracing-log"))]
-            let target = target.string(meta.target());
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::debug_span!("request");
+            let id = span.id().unwrap();
+            // Exit a span that was never entered
+            tracing::dispatcher::get_default(|dispatch| dispatch.exit(&id));
+
+            let _guard = span.enter();
+            advance_timings(&span, Duration::from_secs(1));
+        });
+
+        let timing = |key: &str| {
+            tracer.with_data(|data| {
+                match data
+                    .builder
+                    .attributes
+                    .as_ref()
+                    .unwrap()
+                    .iter()
+                    .find(|kv| kv.key.as_str() == key)
+                    .map(|kv| kv.value.clone())
+                {
+                    Some(Value::I64(value)) => value,
+                    value => panic!("expected {} attribute, got {:?}", key, value),
+                }
+            })
+        };
+        // The balanced entry is still timed
+        assert!(timing("busy_ns") >= 1_000_000_000);
+        assert!(timing("idle_ns") >= 0);
+    }
+
+    #[test]
+    fn includes_duration() {
+        let tracer = TestTracer(Arc::new(Mutex::new(None)));
+        let subscriber = tracing_subscriber::registry().with(
+            layer()
+                .with_tracer(tracer.clone())
+                .with_duration_attribute(true),
+        );
+
+        let before = SystemTime::now();
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::debug_span!("request").in_scope(|| {});
+        });
+        let elapsed = before.elapsed().unwrap();
+
+        let (duration, start_time) = tracer.with_data(|data| {
+            let duration = data
+                .builder
+                .attributes
+                .as_ref()
+                .unwrap()
+                .iter()
+                .find(|kv| kv.key.as_str() == "duration_ns")
+                .map(|kv| kv.value.clone());
+            (duration, data.builder.start_time.unwrap())
+        });
+        let Some(Value::I64(duration)) = duration else {
+            panic!("expected duration_ns attribute, got {:?}", duration);
+        };
+        // The duration is measured between the start and end times of the
+        // span, which are both within the scope of the subscriber.
+        assert!(start_time >= before);
+        assert!(duration >= 0);
+        assert!(duration <= saturating_nanos(elapsed));
+    }
+
+    #[test]
+    fn includes_event_count() {
+        let tracer = TestTracer(Arc::new(Mutex::new(None)));
+        let subscriber = tracing_subscriber::registry().with(
+            layer()
+                .with_tracer(tracer.clone())
+                .with_event_count_attribute(true),
+        );
+
+        tracing::subscriber::with_default(subscriber, || {
+            let _guard = tracing::debug_span!("request").entered();
+            tracing::debug!("first");
+            tracing::debug!("second");
+            tracing::debug!("third");
+        });
 
-            let mut otel_event = otel::Event::new(
-                String::new(),
-                crate::time::now(),
-                vec![Key::new("level").string(meta.level().as_str()), target],
-                0,
+        let count = tracer.with_data(|data| {
+            data.builder
+                .attributes
+                .as_ref()
+                .unwrap()
+                .iter()
+                .find(|kv| kv.key.as_str() == FIELD_EVENT_COUNT)
+                .map(|kv| kv.value.clone())
+        });
+        assert_eq!(count, Some(Value::I64(3)));
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn includes_json_attribute_bundle() {
+        let attributes = |replacing| {
+            let tracer = TestTracer(Arc::new(Mutex::new(None)));
+            let subscriber = tracing_subscriber::registry().with(
+                layer()
+                    .with_tracer(tracer.clone())
+                    .with_location(false)
+                    .with_threads(false)
+                    .with_tracked_inactivity(false)
+                    .with_attribute_from_span_id(true)
+                    .with_json_attribute_bundle(Some("attributes"))
+                    .with_json_attribute_bundle_replacing(replacing),
             );
 
-            let mut builder_updates = None;
-            event.record(&mut SpanEventVisitor {
-                event_builder: &mut otel_event,
-                span_builder_updates: &mut builder_updates,
-                sem_conv_config: self.sem_conv_config,
+            tracing::subscriber::with_default(subscriber, || {
+                tracing::debug_span!(
+                    "request",
+                    user = "alice",
+                    retries = 2,
+                    cached = false,
+                    ratio = f64::NAN,
+                );
             });
 
-            let mut extensions = span.extensions_mut();
-            let otel_data = extensions.get_mut::<OtelData>();
+            tracer.with_data(|data| {
+                data.builder
+                    .attributes
+                    .clone()
+                    .unwrap()
+                    .into_iter()
+                    .map(|kv| (kv.key.to_string(), kv.value))
+                    .collect::<Vec<_>>()
+            })
+        };
 
-            if let Some(otel_data) = otel_data {
-                let builder = &mut otel_data.builder;
+        let bundle = |attributes: &[(String, Value)]| {
+            let (_, bundle) = attributes.iter().find(|(key, _)| key == "attributes")?;
+            serde_json::from_str::<serde_json::Value>(&bundle.as_str()).ok()
+        };
+        let expected = serde_json::json!({
+            "user": "alice",
+            "retries": 2,
+            "cached": false,
+            "ratio": null,
+        });
 
-                if builder.status == otel::Status::Unset
-                    && *meta.level() == tracing_core::Level::ERROR
-                {
-                    builder.status = otel::Status::error("")
-                }
+        let kept = attributes(false);
+        assert_eq!(bundle(&kept), Some(expected.clone()));
+        assert!(kept.iter().any(|(key, _)| key == "user"));
 
-                if let Some(builder_updates) = builder_updates {
-                    builder_updates.update(builder);
-                }
+        let replaced = attributes(true);
+        assert_eq!(bundle(&replaced), Some(expected));
+        let mut keys = replaced
+            .iter()
+            .map(|(key, _)| key.as_str())
+            .collect::<Vec<_>>();
+        keys.sort_unstable();
+        assert_eq!(keys, ["attributes", "otel.span_id", "otel.trace_id"]);
+    }
 
-                if self.location {
-                    #[cfg(not(feature = "tracing-log"))]
-                    let normalized_meta: Option<tracing_core::Metadata<'_>> = None;
-                    let (file, module) = match &normalized_meta {
-                        Some(meta) => (
-                            meta.file().map(|s| Value::from(s.to_owned())),
-                            meta.module_path().map(|s| Value::from(s.to_owned())),
-                        ),
-                        None => (
-                            event.metadata().file().map(Value::from),
-                            event.metadata().module_path().map(Value::from),
-                        ),
-                    };
+    #[test]
+    fn includes_scheduling_delay() {
+        let tracer = TestTracer(Arc::new(Mutex::new(None)));
+        let subscriber = tracing_subscriber::registry().with(
+            layer()
+                .with_tracer(tracer.clone())
+                .with_scheduling_delay_attribute(true),
+        );
 
-                    if let Some(file) = file {
-                        otel_event
-                            .attributes
-                            .push(KeyValue::new("code.filepath", file));
-                    }
-                    if let Some(module) = module {
-                        otel_event
-                            .attributes
-                            .push(KeyValue::new("code.namespace", module));
-                    }
-                    if let Some(line) = meta.line() {
-                        otel_event
-                            .attributes
-                            .push(KeyValue::new("code.lineno", line as i64));
-                    }
-                }
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::debug_span!("task");
+            advance_timings(&span, Duration::from_secs(1));
+            span.in_scope(|| {});
+            advance_timings(&span, Duration::from_secs(1));
+            span.in_scope(|| {});
+        });
 
-                if let Some(ref mut events) = builder.events {
-                    events.push(otel_event);
-                } else {
-                    builder.events = Some(vec![otel_event]);
-                }
-            }
+        let attribute = |key: &str| {
+            tracer.with_data(|data| {
+                data.builder
+                    .attributes
+                    .as_ref()
+                    .unwrap()
+                    .iter()
+                    .find(|kv| kv.key.as_str() == key)
+                    .and_then(|kv| match kv.value {
+                        Value::I64(value) => Some(value),
+                        _ => None,
+                    })
+            })
         };
+        // Only the idle time before the first entry is a scheduling delay
+        let delay = attribute(FIELD_SCHEDULING_DELAY).unwrap();
+        assert!((1_000_000_000..2_000_000_000).contains(&delay));
+        assert!(attribute("idle_ns").unwrap() >= 2_000_000_000);
     }
 
-    /// Exports an OpenTelemetry [`Span`] on close.
-    ///
-    /// [`Span`]: opentelemetry::trace::Span
-    fn on_close(&self, id: span::Id, ctx: Context<'_, S>) {
-        let span = ctx.span(&id).expect("Span not found, this is a bug");
-        let mut extensions = span.extensions_mut();
+    #[test]
+    fn clamps_end_time_before_start_time() {
+        let tracer = TestTracer(Arc::new(Mutex::new(None)));
+        let subscriber = tracing_subscriber::registry().with(
+            layer()
+                .with_tracer(tracer.clone())
+                .with_span_start_jitter_detection(true),
+        );
 
-        if let Some(OtelData {
-            mut builder,
-            parent_cx,
-        }) = extensions.remove::<OtelData>()
-        {
-            if self.tracked_inactivity {
-                // Append busy/idle timings when enabled.
-                if let Some(timings) = extensions.get_mut::<Timings>() {
-                    let busy_ns = Key::new("busy_ns");
-                    let idle_ns = Key::new("idle_ns");
+        let start_time = SystemTime::now() + std::time::Duration::from_secs(3600);
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::debug_span!("request");
+            // Simulate the clock moving backward by starting the span in the future
+            span.with_subscriber(|(id, subscriber)| {
+                let get_context = subscriber.downcast_ref::<WithContext>().unwrap();
+                get_context.with_context(subscriber, id, |data, _tracer| {
+                    data.builder.start_time = Some(start_time);
+                });
+            });
+        });
 
-                    let attributes = builder
+        let (end_time, anomaly) = tracer.with_data(|data| {
+            let anomaly = data
+                .builder
+                .attributes
+                .as_ref()
+                .unwrap()
+                .iter()
+                .find(|kv| kv.key.as_str() == "clock.anomaly")
+                .map(|kv| kv.value.clone());
+            (data.builder.end_time, anomaly)
+        });
+        assert_eq!(end_time, Some(start_time));
+        assert_eq!(anomaly, Some(Value::Bool(true)));
+    }
+
+    #[test]
+    fn prioritizes_attributes() {
+        let tracer = TestTracer(Arc::new(Mutex::new(None)));
+        let subscriber = tracing_subscriber::registry().with(
+            layer()
+                .with_tracer(tracer.clone())
+                .with_location(false)
+                .with_threads(false)
+                .with_tracked_inactivity(false)
+                .with_attribute_priority(vec!["http.route", "http.request.method"]),
+        );
+
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::debug_span!(
+                "request",
+                user = "alice",
+                http.request.method = "GET",
+                retries = 2,
+                http.route = "/users"
+            );
+        });
+
+        let keys = tracer.with_data(|data| {
+            data.builder
+                .attributes
+                .as_ref()
+                .unwrap()
+                .iter()
+                .map(|kv| kv.key.as_str().to_owned())
+                .collect::<Vec<_>>()
+        });
+        assert_eq!(
+            keys,
+            ["http.route", "http.request.method", "user", "retries"]
+        );
+    }
+
+    #[test]
+    fn sorts_events_by_timestamp() {
+        let tracer = TestTracer(Arc::new(Mutex::new(None)));
+        let subscriber = tracing_subscriber::registry()
+            .with(layer().with_tracer(tracer.clone()).with_sorted_events(true));
+
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::debug_span!("request");
+            let _guard = span.enter();
+            tracing::debug!("recorded");
+            // Add an event that happened before the recorded one
+            span.with_subscriber(|(id, subscriber)| {
+                let get_context = subscriber.downcast_ref::<WithContext>().unwrap();
+                get_context.with_context(subscriber, id, |data, _tracer| {
+                    data.builder.events.as_mut().unwrap().push(otel::Event::new(
+                        "earlier",
+                        SystemTime::UNIX_EPOCH,
+                        Vec::new(),
+                        0,
+                    ));
+                });
+            });
+        });
+
+        let names = tracer.with_data(|data| {
+            data.builder
+                .events
+                .as_ref()
+                .unwrap()
+                .iter()
+                .map(|event| event.name.to_string())
+                .collect::<Vec<_>>()
+        });
+        assert_eq!(names, vec!["earlier", "recorded"]);
+    }
+
+    #[test]
+    fn numbers_events_per_span() {
+        let tracer = TestTracer(Arc::new(Mutex::new(None)));
+        let subscriber = tracing_subscriber::registry().with(
+            layer()
+                .with_tracer(tracer.clone())
+                .with_event_sequence_numbers(true),
+        );
+
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::debug_span!("request");
+            let _guard = span.enter();
+            tracing::debug!("first");
+            tracing::debug!("second");
+            tracing::debug!("third");
+        });
+
+        let sequences = tracer.with_data(|data| {
+            data.builder
+                .events
+                .as_ref()
+                .unwrap()
+                .iter()
+                .map(|event| {
+                    event
                         .attributes
-                        .get_or_insert_with(|| Vec::with_capacity(2));
-                    attributes.push(KeyValue::new(busy_ns, timings.busy));
-                    attributes.push(KeyValue::new(idle_ns, timings.idle));
-                }
-            }
+                        .iter()
+                        .find(|kv| kv.key.as_str() == "event.sequence")
+                        .map(|kv| kv.value.clone())
+                        .unwrap()
+                })
+                .collect::<Vec<_>>()
+        });
+        assert_eq!(sequences, vec![Value::I64(0), Value::I64(1), Value::I64(2)]);
+    }
+
+    #[test]
+    fn records_span_target() {
+        let tracer = TestTracer(Arc::new(Mutex::new(None)));
+        let subscriber = tracing_subscriber::registry()
+            .with(layer().with_tracer(tracer.clone()).with_span_target(true));
+
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::debug_span!(target: "my_crate::db", "query");
+        });
+
+        let attributes = tracer.with_data(|data| data.builder.attributes.as_ref().unwrap().clone());
+        let target = attributes
+            .iter()
+            .find(|kv| kv.key.as_str() == "target")
+            .map(|kv| kv.value.as_str().into_owned());
+        assert_eq!(target.as_deref(), Some("my_crate::db"));
+    }
+
+    #[test]
+    fn records_baggage_attributes() {
+        let tracer = TestTracer(Arc::new(Mutex::new(None)));
+        let subscriber = tracing_subscriber::registry().with(
+            layer()
+                .with_tracer(tracer.clone())
+                .with_baggage_attributes(true),
+        );
+
+        let _guard = OtelContext::current_with_baggage(vec![
+            KeyValue::new("tenant.id", "acme"),
+            KeyValue::new("request.id", "from-baggage"),
+        ])
+        .attach();
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::debug_span!("request", baggage.request.id = "from-span");
+        });
+
+        let attributes = tracer.with_data(|data| data.builder.attributes.as_ref().unwrap().clone());
+        let values = |key: &str| {
+            attributes
+                .iter()
+                .filter(|kv| kv.key.as_str() == key)
+                .map(|kv| kv.value.as_str().into_owned())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(values("baggage.tenant.id"), vec!["acme"]);
+        assert_eq!(values("baggage.request.id"), vec!["from-span"]);
+    }
+
+    #[test]
+    fn deterministic_thread_ids() {
+        let tracer = TestTracer(Arc::new(Mutex::new(None)));
+        let dispatch = tracing::Dispatch::new(
+            tracing_subscriber::registry().with(
+                layer()
+                    .with_tracer(tracer.clone())
+                    .with_deterministic_thread_ids(true),
+            ),
+        );
+
+        let thread_id = || {
+            tracer.with_data(|data| {
+                data.builder
+                    .attributes
+                    .as_ref()
+                    .unwrap()
+                    .iter()
+                    .find(|kv| kv.key.as_str() == "thread.id")
+                    .map(|kv| kv.value.clone())
+            })
+        };
+        tracing::dispatcher::with_default(&dispatch, || {
+            tracing::debug_span!("first");
+            assert_eq!(thread_id(), Some(Value::I64(1)));
+            tracing::debug_span!("second");
+            assert_eq!(thread_id(), Some(Value::I64(1)));
+        });
+        thread::scope(|scope| {
+            scope.spawn(|| {
+                tracing::dispatcher::with_default(&dispatch, || tracing::debug_span!("spawned"));
+            });
+        });
+        assert_eq!(thread_id(), Some(Value::I64(2)));
+    }
+
+    #[test]
+    fn lazy_span_id() {
+        let tracer = TestTracer(Arc::new(Mutex::new(None)));
+        let subscriber = tracing_subscriber::registry().with(
+            layer()
+                .with_tracer(tracer.clone())
+                .with_eager_span_id(false),
+        );
+
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::debug_span!("request");
+        });
+
+        let span_id = tracer.with_data(|data| data.builder.span_id);
+        assert_eq!(span_id, None);
+    }
+
+    #[test]
+    fn records_span_level() {
+        let tracer = TestTracer(Arc::new(Mutex::new(None)));
+        let subscriber = tracing_subscriber::registry().with(
+            layer()
+                .with_tracer(tracer.clone())
+                .with_level(true)
+                .with_level_field_name("otel.span.level"),
+        );
 
-            // Assign end time, build and start span, drop span to export
-            builder
-                .with_end_time(crate::time::now())
-                .start_with_context(&self.tracer, &parent_cx);
-        }
-    }
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::warn_span!("request").in_scope(|| tracing::info!("event"));
+        });
 
-    // SAFETY: this is safe because the `WithContext` function pointer is valid
-    // for the lifetime of `&self`.
-    unsafe fn downcast_raw(&self, id: TypeId) -> Option<*const ()> {
-        match id {
-            id if id == TypeId::of::<Self>() => Some(self as *const _ as *const ()),
-            id if id == TypeId::of::<WithContext>() => {
-                Some(&self.get_context as *const _ as *const ())
-            }
-            _ => None,
-        }
+        let (span_level, event_level) = tracer.with_data(|data| {
+            let find = |attributes: &[KeyValue], key: &str| {
+                attributes
+                    .iter()
+                    .find(|kv| kv.key.as_str() == key)
+                    .map(|kv| kv.value.as_str().into_owned())
+            };
+            (
+                find(data.builder.attributes.as_ref().unwrap(), "otel.span.level"),
+                find(
+                    &data.builder.events.as_ref().unwrap()[0].attributes,
+                    "level",
+                ),
+            )
+        });
+        assert_eq!(span_level.as_deref(), Some("WARN"));
+        assert_eq!(event_level.as_deref(), Some("INFO"));
     }
-}
 
-struct Timings {
-    idle: i64,
-    busy: i64,
-    last: Instant,
-}
+    #[test]
+    fn nonfinite_float_handling() {
+        let attribute = |handling| {
+            let tracer = TestTracer(Arc::new(Mutex::new(None)));
+            let subscriber = tracing_subscriber::registry().with(
+                layer()
+                    .with_tracer(tracer.clone())
+                    .with_nonfinite_float_handling(handling),
+            );
+            tracing::subscriber::with_default(subscriber, || {
+                tracing::debug_span!("request", ratio = f64::NAN);
+            });
+            tracer.with_data(|data| {
+                data.builder
+                    .attributes
+                    .as_ref()
+                    .unwrap()
+                    .iter()
+                    .find(|kv| kv.key.as_str() == "ratio")
+                    .map(|kv| kv.value.clone())
+            })
+        };
 
-impl Timings {
-    fn new() -> Self {
-        Self {
-            idle: 0,
-            busy: 0,
-            last: Instant::now(),
-        }
+        assert!(matches!(attribute(NonFinite::Keep), Some(Value::F64(v)) if v.is_nan()));
+        assert_eq!(attribute(NonFinite::String), Some(Value::from("NaN")));
+        assert_eq!(attribute(NonFinite::Drop), None);
     }
-}
 
-fn thread_id_integer(id: thread::ThreadId) -> u64 {
-    let thread_id = format!("{:?}", id);
-    thread_id
-        .trim_start_matches("ThreadId(")
-        .trim_end_matches(')')
-        .parse::<u64>()
-        .expect("thread ID should parse as an integer")
-}
+    #[test]
+    fn skip_unsampled_enrichment() {
+        use opentelemetry::trace::TracerProvider as _;
+        use opentelemetry_sdk::trace::{config, Sampler, TracerProvider};
+
+        let recorded = |sampler, skip_unsampled_enrichment| {
+            let provider = TracerProvider::builder()
+                .with_config(config().with_sampler(sampler))
+                .build();
+            let subscriber = tracing_subscriber::registry().with(
+                layer()
+                    .with_tracer(provider.tracer("test"))
+                    .with_location(true)
+                    .with_skip_unsampled_enrichment(skip_unsampled_enrichment),
+            );
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use opentelemetry::trace::TraceFlags;
-    use std::{
-        collections::HashMap,
-        error::Error,
-        fmt::Display,
-        sync::{Arc, Mutex},
-        time::SystemTime,
-    };
-    use tracing_subscriber::prelude::*;
+            tracing::subscriber::with_default(subscriber, || {
+                let span = tracing::debug_span!("request", user = "alice", retries = 0);
+                span.record("retries", 1);
+                span.in_scope(|| tracing::debug!("event"));
+
+                let mut recorded = None;
+                span.with_subscriber(|(id, subscriber)| {
+                    let get_context = subscriber.downcast_ref::<WithContext>().unwrap();
+                    get_context.with_context(subscriber, id, |data, _tracer| {
+                        recorded = Some((
+                            data.builder.attributes.as_ref().map_or(0, Vec::len),
+                            data.builder.events.as_ref().map_or(0, Vec::len),
+                        ));
+                    });
+                });
+                recorded
+            })
+        };
 
-    #[derive(Debug, Clone)]
-    struct TestTracer(Arc<Mutex<Option<OtelData>>>);
-    impl otel::Tracer for TestTracer {
-        type Span = noop::NoopSpan;
-        fn start_with_context<T>(&self, _name: T, _context: &OtelContext) -> Self::Span
-        where
-            T: Into<Cow<'static, str>>,
-        {
-            noop::NoopSpan::DEFAULT
-        }
-        fn span_builder<T>(&self, name: T) -> otel::SpanBuilder
-        where
-            T: Into<Cow<'static, str>>,
-        {
-            otel::SpanBuilder::from_name(name)
-        }
-        fn build_with_context(
-            &self,
-            builder: otel::SpanBuilder,
-            parent_cx: &OtelContext,
-        ) -> Self::Span {
-            *self.0.lock().unwrap() = Some(OtelData {
-                builder,
-                parent_cx: parent_cx.clone(),
-            });
-            noop::NoopSpan::DEFAULT
-        }
+        let enriched = recorded(Sampler::AlwaysOff, false);
+        assert!(matches!(enriched, Some((attributes, 1)) if attributes > 2));
+        assert_eq!(recorded(Sampler::AlwaysOff, true), Some((0, 0)));
+        assert_eq!(recorded(Sampler::AlwaysOn, true), enriched);
     }
 
-    impl PreSampledTracer for TestTracer {
-        fn sampled_context(&self, _builder: &mut crate::OtelData) -> OtelContext {
-            OtelContext::new()
-        }
-        fn new_trace_id(&self) -> otel::TraceId {
-            otel::TraceId::INVALID
-        }
-        fn new_span_id(&self) -> otel::SpanId {
-            otel::SpanId::INVALID
-        }
-    }
+    #[test]
+    fn span_attributes_capacity() {
+        let tracer = TestTracer(Arc::new(Mutex::new(None)));
+        let subscriber = tracing_subscriber::registry().with(
+            OpenTelemetryLayer::minimal(tracer.clone()).with_span_builder_attributes_capacity(4),
+        );
 
-    impl TestTracer {
-        fn with_data<T>(&self, f: impl FnOnce(&OtelData) -> T) -> T {
-            let lock = self.0.lock().unwrap();
-            let data = lock.as_ref().expect("no span data has been recorded yet");
-            f(data)
-        }
-    }
+        let mut capacity = None;
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::debug_span!("request", otel.name = "renamed", user = "alice");
+            span.with_subscriber(|(id, subscriber)| {
+                let get_context = subscriber.downcast_ref::<WithContext>().unwrap();
+                get_context.with_context(subscriber, id, |data, _tracer| {
+                    capacity = data.builder.attributes.as_ref().map(Vec::capacity);
+                });
+            });
+        });
 
-    #[derive(Debug, Clone)]
-    struct TestSpan(otel::SpanContext);
-    impl otel::Span for TestSpan {
-        fn add_event_with_timestamp<T: Into<Cow<'static, str>>>(
-            &mut self,
-            _: T,
-            _: SystemTime,
-            _: Vec<KeyValue>,
-        ) {
-        }
-        fn span_context(&self) -> &otel::SpanContext {
-            &self.0
-        }
-        fn is_recording(&self) -> bool {
-            false
-        }
-        fn set_attribute(&mut self, _attribute: KeyValue) {}
-        fn set_status(&mut self, _status: otel::Status) {}
-        fn update_name<T: Into<Cow<'static, str>>>(&mut self, _new_name: T) {}
-        fn end_with_timestamp(&mut self, _timestamp: SystemTime) {}
+        // One field, without `otel.name`, and the configured headroom
+        assert_eq!(capacity, Some(5));
     }
 
-    #[derive(Debug)]
-    struct TestDynError {
-        msg: &'static str,
-        source: Option<Box<TestDynError>>,
-    }
-    impl Display for TestDynError {
-        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-            write!(f, "{}", self.msg)
-        }
-    }
-    impl Error for TestDynError {
-        fn source(&self) -> Option<&(dyn Error + 'static)> {
-            match &self.source {
-                Some(source) => Some(source),
-                None => None,
-            }
-        }
+    #[test]
+    fn code_attribute_schema() {
+        let keys = |schema| {
+            let tracer = TestTracer(Arc::new(Mutex::new(None)));
+            let subscriber = tracing_subscriber::registry().with(
+                layer()
+                    .with_tracer(tracer.clone())
+                    .with_code_attribute_schema(schema),
+            );
+            tracing::subscriber::with_default(subscriber, || {
+                tracing::debug_span!("request").in_scope(|| tracing::debug!("event"));
+            });
+            tracer.with_data(|data| {
+                let code_keys = |attributes: &[KeyValue]| {
+                    attributes
+                        .iter()
+                        .map(|kv| kv.key.as_str().to_owned())
+                        .filter(|key| key.starts_with("code."))
+                        .collect::<Vec<_>>()
+                };
+                (
+                    code_keys(data.builder.attributes.as_ref().unwrap()),
+                    code_keys(&data.builder.events.as_ref().unwrap()[0].attributes),
+                )
+            })
+        };
+
+        let legacy: Vec<String> = ["code.filepath", "code.namespace", "code.lineno"]
+            .map(String::from)
+            .to_vec();
+        assert_eq!(keys(CodeAttributeSchema::Legacy), (legacy.clone(), legacy));
+        let current: Vec<String> = ["code.file.path", "code.namespace", "code.line.number"]
+            .map(String::from)
+            .to_vec();
+        assert_eq!(
+            keys(CodeAttributeSchema::Current),
+            (current.clone(), current)
+        );
     }
-    impl TestDynError {
-        fn new(msg: &'static str) -> Self {
-            Self { msg, source: None }
-        }
-        fn with_parent(self, parent_msg: &'static str) -> Self {
-            Self {
-                msg: parent_msg,
-                source: Some(Box::new(self)),
-            }
-        }
+
+    #[test]
+    fn records_code_column() {
+        let column = |layer: OpenTelemetryLayer<_, _>, column: Option<i64>| {
+            let tracer = TestTracer(Arc::new(Mutex::new(None)));
+            let subscriber = tracing_subscriber::registry().with(layer.with_tracer(tracer.clone()));
+            tracing::subscriber::with_default(subscriber, || match column {
+                Some(column) => tracing::debug_span!("request", otel.code.column = column),
+                None => tracing::debug_span!("request"),
+            });
+            tracer.with_data(|data| {
+                data.builder
+                    .attributes
+                    .as_ref()
+                    .unwrap()
+                    .iter()
+                    .filter(|kv| kv.key.as_str().starts_with("code.column"))
+                    .map(|kv| (kv.key.as_str().to_owned(), kv.value.clone()))
+                    .collect::<Vec<_>>()
+            })
+        };
+
+        assert_eq!(
+            column(layer(), Some(12)),
+            [("code.column".to_owned(), Value::I64(12))]
+        );
+        assert_eq!(
+            column(
+                layer().with_code_attribute_schema(CodeAttributeSchema::Current),
+                Some(12)
+            ),
+            [("code.column.number".to_owned(), Value::I64(12))]
+        );
+        assert_eq!(column(layer(), None), []);
+        assert_eq!(column(layer().with_location(false), Some(12)), []);
     }
 
     #[test]
-    fn dynamic_span_names() {
-        let dynamic_name = "GET http://example.com".to_string();
+    fn filters_event_attributes() {
         let tracer = TestTracer(Arc::new(Mutex::new(None)));
-        let subscriber = tracing_subscriber::registry().with(layer().with_tracer(tracer.clone()));
+        let subscriber = tracing_subscriber::registry().with(
+            layer()
+                .with_tracer(tracer.clone())
+                .with_event_attribute_filter(|name| name != "payload"),
+        );
 
         tracing::subscriber::with_default(subscriber, || {
-            tracing::debug_span!("static_name", otel.name = dynamic_name.as_str());
+            tracing::debug_span!("request").in_scope(|| {
+                tracing::info!(payload = ?vec![0u8; 64], id = 1, "received");
+            });
         });
 
-        let recorded_name = tracer
-            .0
-            .lock()
-            .unwrap()
-            .as_ref()
-            .map(|b| b.builder.name.clone());
-        assert_eq!(recorded_name, Some(dynamic_name.into()))
+        let event = tracer.with_data(|data| data.builder.events.as_ref().unwrap()[0].clone());
+        assert_eq!(event.name, "received");
+        let keys = event
+            .attributes
+            .iter()
+            .map(|kv| kv.key.as_str())
+            .collect::<Vec<_>>();
+        assert!(keys.contains(&"id"));
+        assert!(!keys.contains(&"payload"));
     }
 
     #[test]
-    fn span_kind() {
-        let tracer = TestTracer(Arc::new(Mutex::new(None)));
-        let subscriber = tracing_subscriber::registry().with(layer().with_tracer(tracer.clone()));
+    fn marks_startup_spans() {
+        let startup = |window| {
+            let tracer = TestTracer(Arc::new(Mutex::new(None)));
+            let subscriber = tracing_subscriber::registry().with(
+                layer()
+                    .with_tracer(tracer.clone())
+                    .with_startup_window(window),
+            );
 
-        tracing::subscriber::with_default(subscriber, || {
-            tracing::debug_span!("request", otel.kind = "server");
-        });
+            tracing::subscriber::with_default(subscriber, || {
+                tracing::debug_span!("request").in_scope(|| {});
+            });
 
-        let recorded_kind = tracer.with_data(|data| data.builder.span_kind.clone());
-        assert_eq!(recorded_kind, Some(otel::SpanKind::Server))
+            tracer.with_data(|data| {
+                data.builder
+                    .attributes
+                    .as_ref()
+                    .unwrap()
+                    .iter()
+                    .find(|kv| kv.key.as_str() == "app.startup")
+                    .map(|kv| kv.value.clone())
+            })
+        };
+
+        assert_eq!(startup(Duration::from_secs(3600)), Some(Value::Bool(true)));
+        assert_eq!(startup(Duration::ZERO), None);
     }
 
     #[test]
-    fn span_status_code() {
+    fn truncates_deep_spans() {
         let tracer = TestTracer(Arc::new(Mutex::new(None)));
-        let subscriber = tracing_subscriber::registry().with(layer().with_tracer(tracer.clone()));
+        let subscriber = tracing_subscriber::registry()
+            .with(layer().with_tracer(tracer.clone()).with_max_span_depth(2));
 
+        let mut exported = Vec::new();
         tracing::subscriber::with_default(subscriber, || {
-            tracing::debug_span!("request", otel.status_code = ?otel::Status::Ok);
+            let root = tracing::debug_span!("root");
+            let _root = root.enter();
+            let child = tracing::debug_span!("child");
+            let _child = child.enter();
+            let grandchild = tracing::debug_span!("grandchild");
+            let _grandchild = grandchild.enter();
+            let great_grandchild = tracing::debug_span!("great_grandchild");
+            great_grandchild.in_scope(|| tracing::debug!("deep event"));
+
+            for span in [&root, &child, &grandchild, &great_grandchild] {
+                span.with_subscriber(|(id, subscriber)| {
+                    let get_context = subscriber.downcast_ref::<WithContext>().unwrap();
+                    get_context.with_context(subscriber, id, |data, _tracer| {
+                        let events = data
+                            .builder
+                            .events
+                            .iter()
+                            .flatten()
+                            .map(|event| event.name.to_string())
+                            .collect::<Vec<_>>();
+                        exported.push((data.builder.name.to_string(), events));
+                    });
+                });
+            }
         });
 
-        let recorded_status = tracer.with_data(|data| data.builder.status.clone());
-        assert_eq!(recorded_status, otel::Status::Ok)
+        // Only the first two levels are exported, and the deepest exported span
+        // holds the events of its truncated descendants.
+        assert_eq!(
+            exported,
+            vec![
+                ("root".to_string(), vec![]),
+                ("child".to_string(), vec!["deep event".to_string()]),
+            ]
+        );
     }
 
     #[test]
-    fn span_status_message() {
+    fn dropped_span_callback_reports_reason() {
+        let dropped = Arc::new(Mutex::new(Vec::new()));
         let tracer = TestTracer(Arc::new(Mutex::new(None)));
-        let subscriber = tracing_subscriber::registry().with(layer().with_tracer(tracer.clone()));
-
-        let message = "message";
+        let subscriber = tracing_subscriber::registry().with(
+            layer()
+                .with_tracer(tracer.clone())
+                .with_export_on_error_only(true)
+                .with_dropped_span_callback({
+                    let dropped = dropped.clone();
+                    move |builder, reason| {
+                        dropped
+                            .lock()
+                            .unwrap()
+                            .push((builder.name.to_string(), reason))
+                    }
+                }),
+        );
 
         tracing::subscriber::with_default(subscriber, || {
-            tracing::debug_span!("request", otel.status_message = message);
+            let _root = tracing::debug_span!("root").entered();
+            let _child = tracing::debug_span!("child").entered();
         });
 
-        let recorded_status_message = tracer
-            .0
-            .lock()
-            .unwrap()
-            .as_ref()
-            .unwrap()
-            .builder
-            .status
-            .clone();
-
-        assert_eq!(recorded_status_message, otel::Status::error(message))
+        assert!(tracer.0.lock().unwrap().is_none());
+        assert_eq!(
+            *dropped.lock().unwrap(),
+            vec![
+                ("child".to_string(), DropReason::NoErrorInTrace),
+                ("root".to_string(), DropReason::NoErrorInTrace),
+            ]
+        );
     }
 
     #[test]
-    fn trace_id_from_existing_context() {
+    fn follows_from_truncated_spans() {
         let tracer = TestTracer(Arc::new(Mutex::new(None)));
-        let subscriber = tracing_subscriber::registry().with(layer().with_tracer(tracer.clone()));
-        let trace_id = otel::TraceId::from(42u128);
-        let existing_cx = OtelContext::current_with_span(TestSpan(otel::SpanContext::new(
-            trace_id,
-            otel::SpanId::from(1u64),
-            TraceFlags::default(),
-            false,
-            Default::default(),
-        )));
-        let _g = existing_cx.attach();
+        let subscriber = tracing_subscriber::registry()
+            .with(layer().with_tracer(tracer.clone()).with_max_span_depth(1));
+
+        tracing::subscriber::with_default(subscriber, || {
+            let root = tracing::debug_span!("root");
+            let truncated = root.in_scope(|| tracing::debug_span!("truncated"));
+            truncated.follows_from(&root);
+            root.follows_from(&truncated);
+        });
+
+        let (name, links) = tracer.with_data(|data| {
+            (
+                data.builder.name.clone(),
+                data.builder.links.clone().unwrap_or_default(),
+            )
+        });
+        assert_eq!(name, "root");
+        assert!(links.is_empty());
+    }
+
+    #[test]
+    fn reports_spans_beyond_max_depth() {
+        let dropped = Arc::new(Mutex::new(Vec::new()));
+        let tracer = TestTracer(Arc::new(Mutex::new(None)));
+        let subscriber = tracing_subscriber::registry().with(
+            layer()
+                .with_tracer(tracer.clone())
+                .with_max_span_depth(1)
+                .with_dropped_span_callback({
+                    let dropped = dropped.clone();
+                    move |builder, reason| {
+                        dropped
+                            .lock()
+                            .unwrap()
+                            .push((builder.name.to_string(), reason))
+                    }
+                }),
+        );
 
         tracing::subscriber::with_default(subscriber, || {
-            tracing::debug_span!("request", otel.kind = "server");
+            tracing::debug_span!("root").in_scope(|| tracing::debug_span!("child"));
         });
 
-        let recorded_trace_id =
-            tracer.with_data(|data| data.parent_cx.span().span_context().trace_id());
-        assert_eq!(recorded_trace_id, trace_id)
+        assert_eq!(
+            *dropped.lock().unwrap(),
+            vec![("child".to_string(), DropReason::MaxDepth)]
+        );
     }
 
     #[test]
-    fn includes_timings() {
+    fn filters_events() {
+        struct Skipped(bool);
+
+        impl field::Visit for Skipped {
+            fn record_bool(&mut self, field: &field::Field, value: bool) {
+                if field.name() == "skip" {
+                    self.0 = value;
+                }
+            }
+
+            fn record_debug(&mut self, _field: &field::Field, _value: &dyn fmt::Debug) {}
+        }
+
         let tracer = TestTracer(Arc::new(Mutex::new(None)));
         let subscriber = tracing_subscriber::registry().with(
             layer()
                 .with_tracer(tracer.clone())
-                .with_tracked_inactivity(true),
+                .with_event_filter(|event, _metadata| {
+                    let mut skipped = Skipped(false);
+                    event.record(&mut skipped);
+                    !skipped.0
+                }),
         );
 
         tracing::subscriber::with_default(subscriber, || {
-            tracing::debug_span!("request");
+            let _guard = tracing::debug_span!("request").entered();
+            tracing::debug!(skip = true, "skipped");
+            tracing::error!(skip = true, "skipped error");
+            tracing::debug!(skip = false, "recorded");
         });
 
-        let attributes = tracer.with_data(|data| data.builder.attributes.as_ref().unwrap().clone());
-        let keys = attributes
-            .iter()
-            .map(|kv| kv.key.as_str())
-            .collect::<Vec<&str>>();
-        assert!(keys.contains(&"idle_ns"));
-        assert!(keys.contains(&"busy_ns"));
+        let (names, status) = tracer.with_data(|data| {
+            let names = data
+                .builder
+                .events
+                .iter()
+                .flatten()
+                .map(|event| event.name.to_string())
+                .collect::<Vec<_>>();
+            (names, data.builder.status.clone())
+        });
+        assert_eq!(names, vec!["recorded"]);
+        assert_eq!(status, otel::Status::Unset);
     }
 
     #[test]
@@ -1495,6 +5520,81 @@ mod tests {
         );
     }
 
+    #[test]
+    fn records_error_backtrace() {
+        let tracer = TestTracer(Arc::new(Mutex::new(None)));
+        let subscriber = tracing_subscriber::registry().with(
+            layer()
+                .with_error_backtrace_capture(true)
+                .with_tracer(tracer.clone()),
+        );
+
+        let err = TestDynError::new("base error").with_parent("user error");
+
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::debug_span!(
+                "request",
+                error = &err as &(dyn std::error::Error + 'static)
+            );
+        });
+
+        let key_values = tracer.with_data(|data| {
+            data.builder
+                .attributes
+                .as_ref()
+                .unwrap()
+                .iter()
+                .map(|kv| (kv.key.as_str().to_owned(), kv.value.clone()))
+                .collect::<HashMap<_, _>>()
+        });
+
+        assert_eq!(
+            key_values[FIELD_EXCEPTION_CHAIN],
+            Value::Array(vec![StringValue::from("base error")].into())
+        );
+        // Only captured when enabled by the environment
+        if let Some(stacktrace) = key_values.get(FIELD_EXCEPTION_STACKTRACE) {
+            assert!(matches!(stacktrace, Value::String(_)));
+        }
+    }
+
+    #[test]
+    fn truncates_error_messages() {
+        let tracer = TestTracer(Arc::new(Mutex::new(None)));
+        let subscriber = tracing_subscriber::registry().with(
+            layer()
+                .with_max_error_message_length(8)
+                .with_tracer(tracer.clone()),
+        );
+
+        let err = TestDynError::new("connection reset").with_parent("request to upstream failed");
+
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::debug_span!(
+                "request",
+                error = &err as &(dyn std::error::Error + 'static)
+            );
+        });
+
+        let key_values = tracer.with_data(|data| {
+            data.builder
+                .attributes
+                .as_ref()
+                .unwrap()
+                .iter()
+                .map(|kv| (kv.key.as_str().to_owned(), kv.value.clone()))
+                .collect::<HashMap<_, _>>()
+        });
+
+        assert_eq!(key_values["error"].as_str(), "request ");
+        assert_eq!(key_values[FIELD_EXCEPTION_MESSAGE].as_str(), "request ");
+        assert_eq!(
+            key_values["error.chain"],
+            Value::Array(vec![StringValue::from("connecti")].into())
+        );
+        assert_eq!(truncate_error_message("héllo".to_owned(), Some(2)), "h");
+    }
+
     #[test]
     fn records_no_error_fields() {
         let tracer = TestTracer(Arc::new(Mutex::new(None)));
@@ -1597,6 +5697,76 @@ mod tests {
         assert!(!keys.contains(&"code.lineno"));
     }
 
+    #[test]
+    fn includes_event_location_with_custom_keys() {
+        let tracer = TestTracer(Arc::new(Mutex::new(None)));
+        let subscriber = tracing_subscriber::registry().with(
+            layer()
+                .with_tracer(tracer.clone())
+                .with_location(true)
+                .with_event_code_field_names("event.file", "event.module", "event.line"),
+        );
+
+        tracing::subscriber::with_default(subscriber, || {
+            let _guard = tracing::debug_span!("request").entered();
+            tracing::info!("event");
+        });
+
+        let (span_attributes, event_attributes) = tracer.with_data(|data| {
+            (
+                data.builder.attributes.clone().unwrap(),
+                data.builder.events.as_ref().unwrap()[0].attributes.clone(),
+            )
+        });
+        let span_keys = span_attributes
+            .iter()
+            .map(|kv| kv.key.as_str())
+            .collect::<Vec<&str>>();
+        let event_keys = event_attributes
+            .iter()
+            .map(|kv| kv.key.as_str())
+            .collect::<Vec<&str>>();
+        assert!(span_keys.contains(&"code.filepath"));
+        assert!(event_keys.contains(&"event.file"));
+        assert!(event_keys.contains(&"event.module"));
+        assert!(event_keys.contains(&"event.line"));
+        assert!(!event_keys.contains(&"code.filepath"));
+    }
+
+    #[test]
+    fn minimal_records_only_user_fields() {
+        let tracer = TestTracer(Arc::new(Mutex::new(None)));
+        let subscriber =
+            tracing_subscriber::registry().with(OpenTelemetryLayer::minimal(tracer.clone()));
+
+        tracing::subscriber::with_default(subscriber, || {
+            let _guard = tracing::debug_span!("request", user_field = 1).entered();
+            tracing::info!("event");
+        });
+
+        let (attributes, event_attributes) = tracer.with_data(|data| {
+            (
+                data.builder.attributes.clone().unwrap(),
+                data.builder.events.as_ref().unwrap()[0].attributes.clone(),
+            )
+        });
+        assert_eq!(attributes, vec![KeyValue::new("user_field", 1)]);
+        assert!(!event_attributes.iter().any(|kv| kv.key.as_str() == "level"));
+
+        let subscriber = tracing_subscriber::registry()
+            .with(OpenTelemetryLayer::minimal(tracer.clone()).with_event_level(true));
+        tracing::subscriber::with_default(subscriber, || {
+            let _guard = tracing::debug_span!("request").entered();
+            tracing::info!("event");
+        });
+
+        let event_attributes =
+            tracer.with_data(|data| data.builder.events.as_ref().unwrap()[0].attributes.clone());
+        assert!(event_attributes
+            .iter()
+            .any(|kv| kv.key.as_str() == "level" && kv.value == Value::from("INFO")));
+    }
+
     #[test]
     fn includes_thread() {
         let thread = thread::current();
@@ -1622,6 +5792,40 @@ mod tests {
         assert_eq!(attributes.get("thread.id"), Some(&expected_id));
     }
 
+    #[test]
+    fn includes_thread_name_of_spawned_threads() {
+        let thread_name = |builder: thread::Builder| {
+            builder
+                .spawn(|| {
+                    let tracer = TestTracer(Arc::new(Mutex::new(None)));
+                    let subscriber = tracing_subscriber::registry()
+                        .with(layer().with_tracer(tracer.clone()).with_threads(true));
+                    tracing::subscriber::with_default(subscriber, || {
+                        tracing::debug_span!("first");
+                        tracing::debug_span!("second");
+                    });
+                    tracer.with_data(|data| {
+                        data.builder
+                            .attributes
+                            .as_ref()
+                            .unwrap()
+                            .iter()
+                            .find(|kv| kv.key.as_str() == "thread.name")
+                            .map(|kv| kv.value.clone())
+                    })
+                })
+                .unwrap()
+                .join()
+                .unwrap()
+        };
+
+        assert_eq!(
+            thread_name(thread::Builder::new().name("worker".to_owned())),
+            Some(Value::from("worker"))
+        );
+        assert_eq!(thread_name(thread::Builder::new()), None);
+    }
+
     #[test]
     fn excludes_thread() {
         let tracer = TestTracer(Arc::new(Mutex::new(None)));
@@ -1641,6 +5845,283 @@ mod tests {
         assert!(!keys.contains(&"thread.id"));
     }
 
+    #[test]
+    fn includes_target_version() {
+        let tracer = TestTracer(Arc::new(Mutex::new(None)));
+        let subscriber = tracing_subscriber::registry().with(
+            layer()
+                .with_tracer(tracer.clone())
+                .with_target_version_map(HashMap::from([
+                    ("my_crate", "1.0.0"),
+                    ("my_crate::inner", "2.0.0"),
+                    ("other_crate", "3.0.0"),
+                ])),
+        );
+
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::debug_span!(target: "my_crate::inner::module", "request");
+        });
+
+        let attributes = tracer
+            .with_data(|data| data.builder.attributes.as_ref().unwrap().clone())
+            .drain(..)
+            .map(|kv| (kv.key.as_str().to_string(), kv.value))
+            .collect::<HashMap<_, _>>();
+        assert_eq!(
+            attributes.get("code.crate.version"),
+            Some(&Value::from("2.0.0"))
+        );
+    }
+
+    #[test]
+    fn timings_saturate() {
+        let mut timings = Timings::new();
+        timings.busy = i64::MAX - 1;
+        timings.add_busy(Duration::from_secs(1));
+        assert_eq!(timings.busy, i64::MAX);
+
+        timings.add_idle(Duration::MAX);
+        assert_eq!(timings.idle, i64::MAX);
+        timings.add_idle(Duration::from_nanos(1));
+        assert_eq!(timings.idle, i64::MAX);
+    }
+
+    #[test]
+    fn parses_thread_ids() {
+        assert_eq!(parse_thread_id("ThreadId(12)"), Some(12));
+        assert_eq!(parse_thread_id("ThreadId { .. }"), None);
+        assert_eq!(parse_thread_id("ThreadId(main)"), None);
+        assert_eq!(parse_thread_id("12"), None);
+
+        let id = thread::current().id();
+        assert_eq!(thread_id_integer(id), thread_id_integer(id));
+    }
+
+    #[test]
+    fn records_worker_name() {
+        let tracer = TestTracer(Arc::new(Mutex::new(None)));
+        let subscriber = tracing_subscriber::registry().with(
+            layer()
+                .with_tracer(tracer.clone())
+                .with_worker_name_fn(|| Some("worker-1".to_owned())),
+        );
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::debug_span!("request");
+        });
+        let worker_name = |tracer: &TestTracer| {
+            tracer.with_data(|data| {
+                data.builder
+                    .attributes
+                    .as_ref()
+                    .unwrap()
+                    .iter()
+                    .find(|kv| kv.key.as_str() == "worker.name")
+                    .map(|kv| kv.value.clone())
+            })
+        };
+        assert_eq!(worker_name(&tracer), Some(Value::from("worker-1")));
+
+        let tracer = TestTracer(Arc::new(Mutex::new(None)));
+        let subscriber = tracing_subscriber::registry().with(
+            layer()
+                .with_tracer(tracer.clone())
+                .with_worker_name_fn(|| None),
+        );
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::debug_span!("request");
+        });
+        assert_eq!(worker_name(&tracer), None);
+    }
+
+    #[test]
+    fn truncates_attribute_keys() {
+        let tracer = TestTracer(Arc::new(Mutex::new(None)));
+        let subscriber = tracing_subscriber::registry().with(
+            layer()
+                .with_tracer(tracer.clone())
+                .with_max_attribute_key_length(20)
+                .with_tracked_inactivity(false)
+                .with_threads(false),
+        );
+        tracing::subscriber::with_default(subscriber, || {
+            let _span = tracing::debug_span!(
+                "request",
+                short = 1,
+                request.header.x_forwarded_for = 2,
+                request.header.x_forwarded_host = 3
+            )
+            .entered();
+            tracing::debug!(request.header.x_forwarded_for = 4, "forwarded");
+        });
+
+        let (attribute_keys, event_keys) = tracer.with_data(|data| {
+            let keys = |attributes: &[KeyValue]| {
+                attributes
+                    .iter()
+                    .map(|kv| kv.key.as_str().to_owned())
+                    .collect::<Vec<_>>()
+            };
+            (
+                keys(data.builder.attributes.as_ref().unwrap()),
+                keys(&data.builder.events.as_ref().unwrap()[0].attributes),
+            )
+        });
+        let truncated = attribute_keys
+            .iter()
+            .filter(|key| key.starts_with("request.hea~"))
+            .collect::<Vec<_>>();
+        assert!(attribute_keys.contains(&"short".to_owned()));
+        // The hash is stable across toolchains, so are truncated keys.
+        assert_eq!(truncated, ["request.hea~85c96144", "request.hea~ff77c35b"]);
+        assert!(event_keys.contains(truncated[0]));
+    }
+
+    #[test]
+    fn message_as_event_name() {
+        let events = |message_as_event_name: bool| {
+            let tracer = TestTracer(Arc::new(Mutex::new(None)));
+            let subscriber = tracing_subscriber::registry().with(
+                layer()
+                    .with_tracer(tracer.clone())
+                    .with_message_as_event_name(message_as_event_name),
+            );
+            tracing::subscriber::with_default(subscriber, || {
+                tracing::debug_span!("request").in_scope(|| {
+                    tracing::debug!("hello");
+                    tracing::debug!(error = "timeout");
+                    tracing::debug!(error = "timeout", "failed");
+                });
+            });
+            tracer.with_data(|data| {
+                data.builder
+                    .events
+                    .as_ref()
+                    .unwrap()
+                    .iter()
+                    .map(|event| {
+                        let attribute = |key: &str| {
+                            event
+                                .attributes
+                                .iter()
+                                .find(|kv| kv.key.as_str() == key)
+                                .map(|kv| kv.value.as_str().into_owned())
+                        };
+                        (event.name.to_string(), attribute("message"))
+                    })
+                    .collect::<Vec<_>>()
+            })
+        };
+
+        assert_eq!(
+            events(true),
+            vec![
+                ("hello".to_owned(), None),
+                ("exception".to_owned(), None),
+                ("failed".to_owned(), None),
+            ]
+        );
+
+        let events = events(false);
+        assert!(events[0].0.starts_with("event src/layer.rs:"));
+        assert_eq!(events[0].1.as_deref(), Some("hello"));
+        assert_eq!(events[1], ("exception".to_owned(), None));
+        assert!(events[2].0.starts_with("event src/layer.rs:"));
+        assert_eq!(events[2].1.as_deref(), Some("failed"));
+    }
+
+    #[test]
+    fn status_from_level() {
+        let status = |status_from_level: LevelFilter, level: Level| {
+            let tracer = TestTracer(Arc::new(Mutex::new(None)));
+            let subscriber = tracing_subscriber::registry().with(
+                layer()
+                    .with_tracer(tracer.clone())
+                    .with_status_from_level(status_from_level),
+            );
+            tracing::subscriber::with_default(subscriber, || {
+                tracing::debug_span!("request").in_scope(|| match level {
+                    Level::ERROR => tracing::error!("failed"),
+                    Level::WARN => tracing::warn!("degraded"),
+                    _ => tracing::info!("done"),
+                });
+            });
+            tracer.with_data(|data| data.builder.status.clone())
+        };
+
+        assert_eq!(status(LevelFilter::ERROR, Level::ERROR), Status::error(""));
+        assert_eq!(status(LevelFilter::ERROR, Level::WARN), Status::Unset);
+        assert_eq!(status(LevelFilter::WARN, Level::ERROR), Status::error(""));
+        assert_eq!(status(LevelFilter::WARN, Level::WARN), Status::error(""));
+        assert_eq!(status(LevelFilter::WARN, Level::INFO), Status::Unset);
+        assert_eq!(status(LevelFilter::OFF, Level::ERROR), Status::Unset);
+    }
+
+    #[test]
+    fn includes_on_enter_attributes_once() {
+        let tracer = TestTracer(Arc::new(Mutex::new(None)));
+        let entries = Arc::new(std::sync::atomic::AtomicI64::new(0));
+        let counter = entries.clone();
+        let subscriber = tracing_subscriber::registry().with(
+            layer()
+                .with_tracer(tracer.clone())
+                .with_on_enter_attributes(move || {
+                    let entry = counter.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                    vec![KeyValue::new("entry", entry)]
+                }),
+        );
+
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::debug_span!("request");
+            span.in_scope(|| {});
+            span.in_scope(|| {});
+        });
+
+        let attributes = tracer.with_data(|data| data.builder.attributes.as_ref().unwrap().clone());
+        let entries = attributes
+            .iter()
+            .filter(|kv| kv.key.as_str() == "entry")
+            .map(|kv| kv.value.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(entries, vec![Value::I64(0)]);
+    }
+
+    #[test]
+    fn includes_empty_field_markers() {
+        let tracer = TestTracer(Arc::new(Mutex::new(None)));
+        let subscriber = tracing_subscriber::registry().with(
+            layer()
+                .with_tracer(tracer.clone())
+                .with_empty_field_markers(true),
+        );
+
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::debug_span!(
+                "request",
+                empty = tracing::field::Empty,
+                filled = tracing::field::Empty,
+                otel.status_code = tracing::field::Empty,
+            );
+            span.record("filled", "value");
+        });
+
+        let attributes = tracer.with_data(|data| data.builder.attributes.as_ref().unwrap().clone());
+        let empty = attributes
+            .iter()
+            .filter(|kv| kv.key.as_str() == "empty")
+            .map(|kv| kv.value.clone())
+            .collect::<Vec<_>>();
+        let filled = attributes
+            .iter()
+            .filter(|kv| kv.key.as_str() == "filled")
+            .map(|kv| kv.value.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(empty, vec![Value::from("")]);
+        assert_eq!(filled, vec![Value::from("value")]);
+        assert!(!attributes
+            .iter()
+            .any(|kv| kv.key.as_str() == SPAN_STATUS_CODE_FIELD));
+    }
+
     #[test]
     fn propagates_error_fields_from_event_to_span() {
         let tracer = TestTracer(Arc::new(Mutex::new(None)));
@@ -1689,6 +6170,25 @@ mod tests {
         );
     }
 
+    #[test]
+    fn custom_exception_event_name() {
+        let tracer = TestTracer(Arc::new(Mutex::new(None)));
+        let subscriber = tracing_subscriber::registry().with(
+            layer()
+                .with_tracer(tracer.clone())
+                .with_exception_event_name("error"),
+        );
+
+        tracing::subscriber::with_default(subscriber, || {
+            let _guard = tracing::debug_span!("request").entered();
+
+            tracing::error!(error = "connection reset");
+        });
+
+        let name = tracer.with_data(|data| data.builder.events.as_ref().unwrap()[0].name.clone());
+        assert_eq!(name, "error");
+    }
+
     #[test]
     fn propagates_no_error_fields_from_event_to_span() {
         let tracer = TestTracer(Arc::new(Mutex::new(None)));
@@ -1758,7 +6258,7 @@ mod tests {
             let context = tracing_error::SpanTrace::capture();
 
             // This can cause a deadlock if `on_record` locks extensions while attributes are visited
-            span.record("exception", &tracing::field::debug(&context));
+            span.record("exception", tracing::field::debug(&context));
             // This can cause a deadlock if `on_event` locks extensions while the event is visited
             tracing::info!(exception = &tracing::field::debug(&context), "hello");
         });
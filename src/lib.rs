@@ -20,11 +20,25 @@
 //! special fields are:
 //!
 //! * `otel.name`: Override the span name sent to OpenTelemetry exporters.
-//! Setting this field is useful if you want to display non-static information
-//! in your span name.
+//!   Setting this field is useful if you want to display non-static information
+//!   in your span name.
 //! * `otel.kind`: Set the span kind to one of the supported OpenTelemetry [span kinds].
 //! * `otel.status_code`: Set the span status code to one of the supported OpenTelemetry [span status codes].
 //! * `otel.status_message`: Set the span status message.
+//! * `otel.span_id`: Pin the span id to the given hex-encoded value, e.g. to
+//!   reconstruct spans from an external system. Only taken into account when
+//!   the span is created; invalid values are ignored. Pinned ids are not
+//!   checked for uniqueness, so reusing an id will produce colliding spans.
+//! * `otel.trace_id`: Pin the trace id of a root span to the given hex-encoded
+//!   value, e.g. for deterministic tests or to correlate with other systems.
+//!   Ignored on spans with a parent and on invalid values.
+//! * `otel.code.column`: Set the column of the span in its source file, which
+//!   `tracing` does not record. Only taken into account when the span is
+//!   created, if [`OpenTelemetryLayer::with_location`] is enabled.
+//!
+//! The `otel.` prefix can be changed with
+//! [`OpenTelemetryLayer::with_special_field_prefix`] if it collides with
+//! existing field names.
 //!
 //! [span kinds]: opentelemetry::trace::SpanKind
 //! [span status codes]: opentelemetry::trace::Status
@@ -86,6 +100,10 @@
 //!   exports OpenTelemetry metrics from specifically-named events. This enables
 //!   the `metrics` feature flag on the `opentelemetry` crate.  *Enabled by
 //!   default*.
+//! - `json`: Enables [`OpenTelemetryLayer::with_json_attribute_bundle`], which
+//!   records the attributes of spans as a single JSON attribute.
+//! - `sha2`: Enables [`OpenTelemetrySpanExt::set_enduser_id`], which records
+//!   user ids, optionally hashed with SHA-256.
 //!
 //! [layer]: tracing_subscriber::layer
 //!
@@ -125,19 +143,33 @@
 #[cfg(feature = "metrics")]
 mod metrics;
 
+/// Types of attribute values parsed from strings.
+mod attr_type;
+/// Helper types for recording values in an exporter-friendly format.
+mod duration;
+/// Formatting of trace and span ids recorded as attributes.
+mod id_format;
 /// Implementation of the trace::Layer as a source of OpenTelemetry data.
 mod layer;
+/// Attribute keys defined by the OpenTelemetry semantic conventions.
+mod semconv;
 /// Span extension which enables OpenTelemetry context management.
 mod span_ext;
 /// Protocols for OpenTelemetry Tracers that are compatible with Tracing
 mod tracer;
 
-pub use layer::{layer, OpenTelemetryLayer};
+pub use attr_type::AttrType;
+pub use duration::DurationNanos;
+pub use id_format::IdFormat;
+pub use layer::{
+    layer, CodeAttributeSchema, DropReason, FilteredParentMode, InactivityUnit, LateReparentPolicy,
+    NonFinite, OpenTelemetryLayer,
+};
 
 #[cfg(feature = "metrics")]
-pub use metrics::MetricsLayer;
-pub use span_ext::OpenTelemetrySpanExt;
-pub use tracer::PreSampledTracer;
+pub use metrics::{CounterOverflowPolicy, MetricsLayer};
+pub use span_ext::{AttributeTarget, OpenTelemetrySpanExt, Outcome};
+pub use tracer::{GlobalTracer, PreSampledTracer, ScopedTracer};
 
 /// Per-span OpenTelemetry data tracked by this crate.
 ///
@@ -151,6 +183,54 @@ pub struct OtelData {
     pub builder: opentelemetry::trace::SpanBuilder,
 }
 
+impl OtelData {
+    /// Creates the data of a span with the given parent context and builder.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use opentelemetry::trace::{SpanBuilder, SpanId, TraceId};
+    /// use opentelemetry::Context;
+    /// use tracing_opentelemetry::OtelData;
+    ///
+    /// let builder = SpanBuilder::from_name("request")
+    ///     .with_trace_id(TraceId::from(1))
+    ///     .with_span_id(SpanId::from(2));
+    /// let data = OtelData::new(Context::new(), builder);
+    ///
+    /// assert_eq!(data.trace_id(), Some(TraceId::from(1)));
+    /// assert_eq!(data.span_id(), Some(SpanId::from(2)));
+    /// ```
+    pub fn new(
+        parent_cx: opentelemetry::Context,
+        builder: opentelemetry::trace::SpanBuilder,
+    ) -> Self {
+        OtelData { parent_cx, builder }
+    }
+
+    /// Returns the trace id of the span, which is the one of its parent if it
+    /// has an active parent span, or the one of the builder otherwise.
+    ///
+    /// The full [`SpanContext`] of the span also depends on the sampling
+    /// decision, see [`PreSampledTracer::sampled_context`].
+    ///
+    /// [`SpanContext`]: opentelemetry::trace::SpanContext
+    pub fn trace_id(&self) -> Option<opentelemetry::trace::TraceId> {
+        use opentelemetry::trace::TraceContextExt;
+
+        if self.parent_cx.has_active_span() {
+            Some(self.parent_cx.span().span_context().trace_id())
+        } else {
+            self.builder.trace_id
+        }
+    }
+
+    /// Returns the span id assigned to the span, if any.
+    pub fn span_id(&self) -> Option<opentelemetry::trace::SpanId> {
+        self.builder.span_id
+    }
+}
+
 pub(crate) mod time {
     use std::time::SystemTime;
 
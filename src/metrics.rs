@@ -1,26 +1,54 @@
-use std::{collections::HashMap, fmt, sync::RwLock};
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    convert::TryFrom,
+    fmt,
+    sync::{Arc, Mutex, RwLock},
+};
 use tracing::{field::Visit, Subscriber};
-use tracing_core::{Field, Interest, Metadata};
+use tracing_core::{
+    callsite,
+    span::{Attributes, Id, Record},
+    Field, Interest, Metadata,
+};
 
+use crate::OtelData;
 use opentelemetry::{
-    metrics::{Counter, Histogram, Meter, MeterProvider, UpDownCounter},
+    global,
+    metrics::{
+        AsyncInstrument, Counter, Histogram, InstrumentBuilder, Meter, MeterProvider, MetricsError,
+        ObservableGauge, Unit, UpDownCounter,
+    },
+    trace::{SamplingDecision, SpanContext, TraceContextExt, TraceFlags},
     KeyValue, Value,
 };
 use tracing_subscriber::{
     filter::Filtered,
     layer::{Context, Filter},
-    registry::LookupSpan,
+    registry::{LookupSpan, SpanRef},
     Layer,
 };
 
 use smallvec::SmallVec;
 
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
+#[cfg(target_arch = "wasm32")]
+use web_time::Instant;
+
 const CARGO_PKG_VERSION: &str = env!("CARGO_PKG_VERSION");
 const INSTRUMENTATION_LIBRARY_NAME: &str = "tracing/tracing-opentelemetry";
 
 const METRIC_PREFIX_MONOTONIC_COUNTER: &str = "monotonic_counter.";
 const METRIC_PREFIX_COUNTER: &str = "counter.";
 const METRIC_PREFIX_HISTOGRAM: &str = "histogram.";
+const METRIC_PREFIXES: [&str; 3] = [
+    METRIC_PREFIX_MONOTONIC_COUNTER,
+    METRIC_PREFIX_COUNTER,
+    METRIC_PREFIX_HISTOGRAM,
+];
+const METRIC_SUFFIX_UNIT: &str = ".unit";
+const METRIC_SUFFIX_DESCRIPTION: &str = ".description";
 const I64_MAX: u64 = i64::MAX as u64;
 
 #[derive(Default)]
@@ -35,6 +63,81 @@ pub(crate) struct Instruments {
 
 type MetricsMap<T> = RwLock<HashMap<&'static str, T>>;
 
+/// A resolved instrument, cached per callsite.
+#[derive(Clone)]
+pub(crate) enum Instrument {
+    CounterU64(Counter<u64>),
+    CounterF64(Counter<f64>),
+    UpDownCounterI64(UpDownCounter<i64>),
+    UpDownCounterF64(UpDownCounter<f64>),
+    HistogramU64(Histogram<u64>),
+    HistogramF64(Histogram<f64>),
+}
+
+impl Instrument {
+    /// Records `instrument_type` on this instrument, returning `false` without
+    /// recording anything if it holds a value of a different type.
+    fn record(&self, instrument_type: InstrumentType, attributes: &[KeyValue]) -> bool {
+        match (self, instrument_type) {
+            (Instrument::CounterU64(ctr), InstrumentType::CounterU64(value)) => {
+                ctr.add(value, attributes)
+            }
+            (Instrument::CounterF64(ctr), InstrumentType::CounterF64(value)) => {
+                ctr.add(value, attributes)
+            }
+            (Instrument::UpDownCounterI64(ctr), InstrumentType::UpDownCounterI64(value)) => {
+                ctr.add(value, attributes)
+            }
+            (Instrument::UpDownCounterF64(ctr), InstrumentType::UpDownCounterF64(value)) => {
+                ctr.add(value, attributes)
+            }
+            (Instrument::HistogramU64(rec), InstrumentType::HistogramU64(value)) => {
+                rec.record(value, attributes)
+            }
+            (Instrument::HistogramF64(rec), InstrumentType::HistogramF64(value)) => {
+                rec.record(value, attributes)
+            }
+            _ => return false,
+        }
+        true
+    }
+}
+
+/// What a [`MetricsLayer`] does with `counter.` values which do not fit in an
+/// `i64`, see [`MetricsLayer::with_counter_overflow_policy`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum CounterOverflowPolicy {
+    /// Drop the value.
+    #[default]
+    Drop,
+    /// Record `i64::MAX` instead.
+    Saturate,
+}
+
+/// Unit and description of a metric, set by the `.unit` and `.description`
+/// fields of an event.
+#[derive(Default, Debug)]
+pub(crate) struct MetricDescriptor {
+    unit: Option<String>,
+    description: Option<String>,
+}
+
+impl MetricDescriptor {
+    fn describe<'a, T>(&self, mut builder: InstrumentBuilder<'a, T>) -> InstrumentBuilder<'a, T>
+    where
+        T: TryFrom<InstrumentBuilder<'a, T>, Error = MetricsError>,
+    {
+        if let Some(unit) = &self.unit {
+            builder = builder.with_unit(Unit::new(unit.clone()));
+        }
+        if let Some(description) = &self.description {
+            builder = builder.with_description(description.clone());
+        }
+        builder
+    }
+}
+
 #[derive(Copy, Clone, Debug)]
 pub(crate) enum InstrumentType {
     CounterU64(u64),
@@ -51,19 +154,20 @@ impl Instruments {
         meter: &Meter,
         instrument_type: InstrumentType,
         metric_name: &'static str,
+        descriptor: &MetricDescriptor,
         attributes: &[KeyValue],
-    ) {
-        fn update_or_insert<T>(
+    ) -> Instrument {
+        fn update_or_insert<T: Clone>(
             map: &MetricsMap<T>,
             name: &'static str,
             insert: impl FnOnce() -> T,
             update: impl FnOnce(&T),
-        ) {
+        ) -> T {
             {
                 let lock = map.read().unwrap();
                 if let Some(metric) = lock.get(name) {
                     update(metric);
-                    return;
+                    return metric.clone();
                 }
             }
 
@@ -73,71 +177,128 @@ impl Instruments {
             // handle the case where the entry was created while we were waiting to
             // acquire the write lock
             let metric = lock.entry(name).or_insert_with(insert);
-            update(metric)
+            update(metric);
+            metric.clone()
         }
 
         match instrument_type {
-            InstrumentType::CounterU64(value) => {
-                update_or_insert(
-                    &self.u64_counter,
-                    metric_name,
-                    || meter.u64_counter(metric_name).init(),
-                    |ctr| ctr.add(value, attributes),
-                );
-            }
-            InstrumentType::CounterF64(value) => {
-                update_or_insert(
-                    &self.f64_counter,
-                    metric_name,
-                    || meter.f64_counter(metric_name).init(),
-                    |ctr| ctr.add(value, attributes),
-                );
-            }
+            InstrumentType::CounterU64(value) => Instrument::CounterU64(update_or_insert(
+                &self.u64_counter,
+                metric_name,
+                || descriptor.describe(meter.u64_counter(metric_name)).init(),
+                |ctr| ctr.add(value, attributes),
+            )),
+            InstrumentType::CounterF64(value) => Instrument::CounterF64(update_or_insert(
+                &self.f64_counter,
+                metric_name,
+                || descriptor.describe(meter.f64_counter(metric_name)).init(),
+                |ctr| ctr.add(value, attributes),
+            )),
             InstrumentType::UpDownCounterI64(value) => {
-                update_or_insert(
+                Instrument::UpDownCounterI64(update_or_insert(
                     &self.i64_up_down_counter,
                     metric_name,
-                    || meter.i64_up_down_counter(metric_name).init(),
+                    || {
+                        descriptor
+                            .describe(meter.i64_up_down_counter(metric_name))
+                            .init()
+                    },
                     |ctr| ctr.add(value, attributes),
-                );
+                ))
             }
             InstrumentType::UpDownCounterF64(value) => {
-                update_or_insert(
+                Instrument::UpDownCounterF64(update_or_insert(
                     &self.f64_up_down_counter,
                     metric_name,
-                    || meter.f64_up_down_counter(metric_name).init(),
+                    || {
+                        descriptor
+                            .describe(meter.f64_up_down_counter(metric_name))
+                            .init()
+                    },
                     |ctr| ctr.add(value, attributes),
-                );
+                ))
             }
-            InstrumentType::HistogramU64(value) => {
-                update_or_insert(
-                    &self.u64_histogram,
-                    metric_name,
-                    || meter.u64_histogram(metric_name).init(),
-                    |rec| rec.record(value, attributes),
-                );
-            }
-            InstrumentType::HistogramF64(value) => {
-                update_or_insert(
-                    &self.f64_histogram,
-                    metric_name,
-                    || meter.f64_histogram(metric_name).init(),
-                    |rec| rec.record(value, attributes),
-                );
-            }
-        };
+            InstrumentType::HistogramU64(value) => Instrument::HistogramU64(update_or_insert(
+                &self.u64_histogram,
+                metric_name,
+                || descriptor.describe(meter.u64_histogram(metric_name)).init(),
+                |rec| rec.record(value, attributes),
+            )),
+            InstrumentType::HistogramF64(value) => Instrument::HistogramF64(update_or_insert(
+                &self.f64_histogram,
+                metric_name,
+                || descriptor.describe(meter.f64_histogram(metric_name)).init(),
+                |rec| rec.record(value, attributes),
+            )),
+        }
     }
 }
 
 pub(crate) struct MetricVisitor<'a> {
     attributes: &'a mut SmallVec<[KeyValue; 8]>,
     visited_metrics: &'a mut SmallVec<[(&'static str, InstrumentType); 2]>,
+    descriptors: &'a mut SmallVec<[(&'static str, MetricDescriptor); 1]>,
+    counter_overflow: CounterOverflowPolicy,
+    /// Names of the metrics with a value which could not be recorded.
+    invalid_values: &'a mut SmallVec<[(&'static str, InvalidValue); 1]>,
+}
+
+/// Why the value of a metric could not be recorded as is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum InvalidValue {
+    /// A `counter.` value greater than `i64::MAX`.
+    CounterOverflow,
+    /// A NaN or infinite value.
+    NonFinite,
+}
+
+impl<'a> MetricVisitor<'a> {
+    /// Records `value` as the unit or description of a metric if `field` is a
+    /// descriptor field, returning whether it was.
+    fn record_descriptor(&mut self, field: &Field, value: &str) -> bool {
+        let Some(name) = METRIC_PREFIXES
+            .iter()
+            .find_map(|prefix| field.name().strip_prefix(prefix))
+        else {
+            return false;
+        };
+        let (metric_name, is_unit) =
+            if let Some(metric_name) = name.strip_suffix(METRIC_SUFFIX_UNIT) {
+                (metric_name, true)
+            } else if let Some(metric_name) = name.strip_suffix(METRIC_SUFFIX_DESCRIPTION) {
+                (metric_name, false)
+            } else {
+                return false;
+            };
+
+        let index = match self
+            .descriptors
+            .iter()
+            .position(|(name, _)| *name == metric_name)
+        {
+            Some(index) => index,
+            None => {
+                self.descriptors
+                    .push((metric_name, MetricDescriptor::default()));
+                self.descriptors.len() - 1
+            }
+        };
+        let descriptor = &mut self.descriptors[index].1;
+        if is_unit {
+            descriptor.unit = Some(value.to_owned());
+        } else {
+            descriptor.description = Some(value.to_owned());
+        }
+        true
+    }
 }
 
 impl<'a> Visit for MetricVisitor<'a> {
     fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
-        self.attributes
-            .push(KeyValue::new(field.name(), format!("{value:?}")));
+        let value = format!("{value:?}");
+        if !self.record_descriptor(field, &value) {
+            self.attributes.push(KeyValue::new(field.name(), value));
+        }
     }
 
     fn record_u64(&mut self, field: &Field, value: u64) {
@@ -149,12 +310,12 @@ impl<'a> Visit for MetricVisitor<'a> {
                 self.visited_metrics
                     .push((metric_name, InstrumentType::UpDownCounterI64(value as i64)));
             } else {
-                eprintln!(
-                    "[tracing-opentelemetry]: Received Counter metric, but \
-                    provided u64: {} is greater than i64::MAX. Ignoring \
-                    this metric.",
-                    value
-                );
+                self.invalid_values
+                    .push((metric_name, InvalidValue::CounterOverflow));
+                if self.counter_overflow == CounterOverflowPolicy::Saturate {
+                    self.visited_metrics
+                        .push((metric_name, InstrumentType::UpDownCounterI64(i64::MAX)));
+                }
             }
         } else if let Some(metric_name) = field.name().strip_prefix(METRIC_PREFIX_HISTOGRAM) {
             self.visited_metrics
@@ -166,6 +327,17 @@ impl<'a> Visit for MetricVisitor<'a> {
     }
 
     fn record_f64(&mut self, field: &Field, value: f64) {
+        if !value.is_finite() {
+            if let Some(metric_name) = METRIC_PREFIXES
+                .iter()
+                .find_map(|prefix| field.name().strip_prefix(prefix))
+            {
+                self.invalid_values
+                    .push((metric_name, InvalidValue::NonFinite));
+                return;
+            }
+        }
+
         if let Some(metric_name) = field.name().strip_prefix(METRIC_PREFIX_MONOTONIC_COUNTER) {
             self.visited_metrics
                 .push((metric_name, InstrumentType::CounterF64(value)));
@@ -194,12 +366,22 @@ impl<'a> Visit for MetricVisitor<'a> {
     }
 
     fn record_str(&mut self, field: &Field, value: &str) {
-        self.attributes
-            .push(KeyValue::new(field.name(), value.to_owned()));
+        if !self.record_descriptor(field, value) {
+            self.attributes
+                .push(KeyValue::new(field.name(), value.to_owned()));
+        }
     }
 
     fn record_bool(&mut self, field: &Field, value: bool) {
-        self.attributes.push(KeyValue::new(field.name(), value));
+        if let Some(metric_name) = field.name().strip_prefix(METRIC_PREFIX_MONOTONIC_COUNTER) {
+            self.visited_metrics
+                .push((metric_name, InstrumentType::CounterU64(value as u64)));
+        } else if let Some(metric_name) = field.name().strip_prefix(METRIC_PREFIX_COUNTER) {
+            self.visited_metrics
+                .push((metric_name, InstrumentType::UpDownCounterI64(value as i64)));
+        } else {
+            self.attributes.push(KeyValue::new(field.name(), value));
+        }
     }
 }
 
@@ -252,6 +434,14 @@ impl<'a> Visit for MetricVisitor<'a> {
 /// info!(histogram.def = 1.1);
 /// ```
 ///
+/// Counters also accept boolean values, which increment the counter by `1`
+/// when `true` and by `0` when `false`:
+/// ```
+/// # use tracing::info;
+/// # let succeeded = true;
+/// info!(monotonic_counter.success = succeeded);
+/// ```
+///
 /// # Mixing data types
 ///
 /// ## Floating-point numbers
@@ -270,6 +460,11 @@ impl<'a> Visit for MetricVisitor<'a> {
 /// This is because all data published for a given metric name must be the same
 /// numeric type.
 ///
+/// NaN and infinite values are dropped, and reported to the OpenTelemetry
+/// [global error handler] the first time they are received for a metric.
+///
+/// [global error handler]: opentelemetry::global::set_error_handler
+///
 /// ## Integers
 ///
 /// Positive and negative integers can be mixed freely. The instrumentation
@@ -290,7 +485,8 @@ impl<'a> Visit for MetricVisitor<'a> {
 /// info!(counter.baz = 1_u64);
 ///
 /// // The subscriber receives a u64, but cannot cast it to i64 because of
-/// // overflow. An error is printed to stderr, and the metric is dropped.
+/// // overflow. An error is reported, and the metric is dropped unless
+/// // `MetricsLayer::with_counter_overflow_policy` says otherwise.
 /// info!(counter.baz = (i64::MAX as u64) + 1)
 /// ```
 ///
@@ -307,17 +503,68 @@ impl<'a> Visit for MetricVisitor<'a> {
 /// info!(monotonic_counter.foo = 1, bar = "baz", qux = 2);
 /// ```
 ///
+/// # Units and descriptions
+///
+/// The unit and description of a metric can be set with `.unit` and
+/// `.description` fields following the metric's field name. These fields are
+/// not added as attributes, and are only taken into account when the
+/// instrument is created, i.e. by the first event recording the metric.
+///
+/// For example:
+/// ```
+/// # use tracing::info;
+/// info!(
+///     monotonic_counter.bytes_sent = 512,
+///     monotonic_counter.bytes_sent.unit = "By",
+///     monotonic_counter.bytes_sent.description = "total bytes sent",
+/// );
+/// ```
+///
+/// # Histogram buckets
+///
+/// Histograms use the default bucket boundaries of the OpenTelemetry SDK. The
+/// `opentelemetry` metrics API does not allow instruments to choose their own
+/// boundaries, so they cannot be set from `tracing` events. Instead, register a
+/// [view] on the meter provider matching the histogram's name:
+///
+/// ```
+/// use opentelemetry_sdk::metrics::{new_view, Aggregation, Instrument, SdkMeterProvider, Stream};
+/// use tracing_opentelemetry::MetricsLayer;
+/// use tracing_subscriber::layer::SubscriberExt;
+/// use tracing_subscriber::Registry;
+///
+/// // Buckets for `histogram.request_ms` events
+/// let view = new_view(
+///     Instrument::new().name("request_ms"),
+///     Stream::new().aggregation(Aggregation::ExplicitBucketHistogram {
+///         boundaries: vec![5.0, 10.0, 25.0, 50.0, 100.0],
+///         record_min_max: true,
+///     }),
+/// )
+/// .unwrap();
+/// let meter_provider = SdkMeterProvider::builder().with_view(view).build();
+///
+/// let subscriber = Registry::default().with(MetricsLayer::new(meter_provider));
+/// # drop(subscriber);
+/// ```
+///
+/// [view]: https://opentelemetry.io/docs/specs/otel/metrics/sdk/#view
+///
 /// # Implementation Details
 ///
 /// `MetricsLayer` holds a set of maps, with each map corresponding to a
 /// type of metric supported by OpenTelemetry. These maps are populated lazily.
 /// The first time that a metric is emitted by the instrumentation, a `Metric`
-/// instance will be created and added to the corresponding map. This means that
-/// any time a metric is emitted by the instrumentation, one map lookup has to
-/// be performed.
+/// instance will be created and added to the corresponding map.
+///
+/// Once resolved, the instrument of each metric is also cached per callsite.
+/// Subsequent emissions from the same callsite only perform a lookup in this
+/// cache, under a read lock which is only contended while new callsites are
+/// resolved, skipping the maps of metrics and the meter of their target.
 ///
-/// In the future, this can be improved by associating each `Metric` instance to
-/// its callsite, eliminating the need for any maps.
+/// When [`MetricsLayer::with_meter_per_target`] is enabled, resolving an
+/// instrument performs an additional map lookup to find the [`Meter`] of the
+/// event's target.
 ///
 #[cfg_attr(docsrs, doc(cfg(feature = "metrics")))]
 pub struct MetricsLayer<S> {
@@ -343,15 +590,200 @@ where
         let layer = InstrumentLayer {
             meter,
             instruments: Default::default(),
+            callsite_instruments: Default::default(),
+            target_meter: None,
+            target_meters: Default::default(),
+            observable_gauges: Default::default(),
+            span_metrics: None,
+            counter_overflow: CounterOverflowPolicy::default(),
+            exemplars: false,
+            inherited_span_attributes: false,
+            reported_invalid_values: Default::default(),
         };
 
         MetricsLayer {
-            inner: layer.with_filter(MetricsFilter),
+            inner: layer.with_filter(MetricsFilter { spans: false }),
         }
     }
+
+    /// Gives each event target its own [`Meter`], named after the target and
+    /// created by `meter_provider`, which is usually the provider this layer
+    /// was created with.
+    ///
+    /// By default, all metrics are recorded by a single meter, so events from
+    /// different targets recording a metric with the same name update the same
+    /// instrument. With a meter per target, metrics are instead grouped in one
+    /// instrumentation scope per target.
+    ///
+    /// Note that every target then holds its own set of instruments, and
+    /// recording a metric requires an additional map lookup to find the meter
+    /// of the event's target.
+    ///
+    /// [`Meter`]: opentelemetry::metrics::Meter
+    pub fn with_meter_per_target<M>(mut self, meter_provider: M) -> Self
+    where
+        M: MeterProvider + Send + Sync + 'static,
+    {
+        self.inner.inner_mut().target_meter =
+            Some(Arc::new(move |target| meter_provider.meter(target)));
+        self
+    }
+
+    /// Sets what to do with `counter.` values greater than `i64::MAX`, which
+    /// cannot be recorded by the `i64` up-down counter of the metric.
+    ///
+    /// The first overflow of every metric is reported to the OpenTelemetry
+    /// [global error handler], whatever the policy.
+    ///
+    /// By default, such values are [dropped].
+    ///
+    /// [global error handler]: opentelemetry::global::set_error_handler
+    /// [dropped]: CounterOverflowPolicy::Drop
+    pub fn with_counter_overflow_policy(mut self, policy: CounterOverflowPolicy) -> Self {
+        self.inner.inner_mut().counter_overflow = policy;
+        self
+    }
+
+    /// Records the duration of every span, in seconds, to a histogram named
+    /// `name`.
+    ///
+    /// The fields of the span whose names are in `attribute_keys` are
+    /// recorded as attributes of the histogram, including fields recorded
+    /// after the span was created. Other fields are ignored to keep the number
+    /// of attribute combinations bounded. The duration of a span is the time
+    /// between its creation and when it is closed.
+    ///
+    /// This does not require an [`OpenTelemetryLayer`], and only spans enabled
+    /// by the filters of the subscriber are recorded.
+    ///
+    /// By default, span durations are not recorded.
+    ///
+    /// [`OpenTelemetryLayer`]: crate::OpenTelemetryLayer
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use opentelemetry_sdk::metrics::SdkMeterProvider;
+    /// use tracing_opentelemetry::MetricsLayer;
+    /// use tracing_subscriber::layer::SubscriberExt;
+    /// use tracing_subscriber::Registry;
+    ///
+    /// let metrics = MetricsLayer::new(SdkMeterProvider::default())
+    ///     .with_span_metrics("span.duration", vec!["http.route"]);
+    /// let subscriber = Registry::default().with(metrics);
+    /// # drop(subscriber);
+    /// ```
+    pub fn with_span_metrics(
+        mut self,
+        name: impl Into<Cow<'static, str>>,
+        attribute_keys: Vec<&'static str>,
+    ) -> Self {
+        let layer = self.inner.inner_mut();
+        layer.span_metrics = Some(SpanMetrics {
+            histogram: layer
+                .meter
+                .f64_histogram(name)
+                .with_unit(Unit::new("s"))
+                .init(),
+            attribute_keys,
+        });
+        self.inner.filter_mut().spans = true;
+        self
+    }
+
+    /// Sets whether or not metrics recorded by events within a span are
+    /// recorded with the fields of the span and its ancestors as attributes.
+    ///
+    /// This allows span-scoped dimensions, e.g. a `tenant` field of a request
+    /// span, to be attached to every metric recorded within the span without
+    /// repeating them on each event. Fields of a span override fields with the
+    /// same name of its ancestors, and fields of the event override fields of
+    /// its spans.
+    ///
+    /// Every field of every span then becomes a metric attribute, and each
+    /// combination of attribute values is a distinct time series. Spans with
+    /// unbounded fields such as ids or timestamps will make the number of
+    /// time series grow without bound, so this should only be enabled when
+    /// span fields have a small number of distinct values.
+    ///
+    /// By default, this is disabled.
+    pub fn with_inherited_span_attributes(mut self, inherited_span_attributes: bool) -> Self {
+        let layer = self.inner.inner_mut();
+        layer.inherited_span_attributes = inherited_span_attributes;
+        let spans = layer.needs_spans();
+        self.inner.filter_mut().spans = spans;
+        self
+    }
+
+    /// Sets whether or not metrics recorded by events within a span are
+    /// recorded with the OpenTelemetry context of the span as the current
+    /// context.
+    ///
+    /// This is a hook for meter providers which attach exemplars to metrics
+    /// from [`Context::current`], referring to the trace and span in which
+    /// they were recorded. The span is sampled if its sampling decision was
+    /// already taken, e.g. by [`OpenTelemetrySpanExt::context`], or otherwise
+    /// if its parent is.
+    ///
+    /// This requires the spans to be recorded by an [`OpenTelemetryLayer`].
+    /// Note that the meter provider of the OpenTelemetry SDK does not support
+    /// exemplars yet, so with it this only adds the cost of looking up the
+    /// span of every metric event.
+    ///
+    /// By default, this is disabled.
+    ///
+    /// [`Context::current`]: opentelemetry::Context::current
+    /// [`OpenTelemetrySpanExt::context`]: crate::OpenTelemetrySpanExt::context
+    /// [`OpenTelemetryLayer`]: crate::OpenTelemetryLayer
+    pub fn with_exemplars(mut self, exemplars: bool) -> Self {
+        let layer = self.inner.inner_mut();
+        layer.exemplars = exemplars;
+        let spans = layer.needs_spans();
+        self.inner.filter_mut().spans = spans;
+        self
+    }
+
+    /// Registers an observable gauge named `name`, whose value is reported by
+    /// `callback` every time metrics are collected.
+    ///
+    /// This is useful for values that are sampled rather than emitted by
+    /// events, such as queue depths or memory usage. Observable gauges are
+    /// independent of `tracing` events, and live as long as this layer.
+    ///
+    /// ```
+    /// # use opentelemetry_sdk::metrics::SdkMeterProvider;
+    /// use std::sync::atomic::{AtomicU64, Ordering};
+    /// use std::sync::Arc;
+    /// use tracing_opentelemetry::MetricsLayer;
+    /// use tracing_subscriber::Registry;
+    ///
+    /// let queue_depth = Arc::new(AtomicU64::new(0));
+    ///
+    /// let layer = MetricsLayer::<Registry>::new(SdkMeterProvider::default());
+    /// let depth = queue_depth.clone();
+    /// layer.register_observable_gauge("queue_depth", move |observer| {
+    ///     observer.observe(depth.load(Ordering::Relaxed), &[]);
+    /// });
+    /// ```
+    pub fn register_observable_gauge<F>(&self, name: impl Into<Cow<'static, str>>, callback: F)
+    where
+        F: Fn(&dyn AsyncInstrument<u64>) + Send + Sync + 'static,
+    {
+        let layer = self.inner.inner();
+        let gauge = layer
+            .meter
+            .u64_observable_gauge(name)
+            .with_callback(callback)
+            .init();
+        layer.observable_gauges.lock().unwrap().push(gauge);
+    }
 }
 
-struct MetricsFilter;
+struct MetricsFilter {
+    /// Whether spans are enabled, to record span metrics, exemplars or
+    /// inherited span attributes.
+    spans: bool,
+}
 
 impl MetricsFilter {
     fn is_metrics_event(&self, meta: &Metadata<'_>) -> bool {
@@ -363,15 +795,19 @@ impl MetricsFilter {
                     || name.starts_with(METRIC_PREFIX_HISTOGRAM)
             })
     }
+
+    fn is_enabled(&self, meta: &Metadata<'_>) -> bool {
+        (self.spans && meta.is_span()) || self.is_metrics_event(meta)
+    }
 }
 
 impl<S> Filter<S> for MetricsFilter {
     fn enabled(&self, meta: &Metadata<'_>, _cx: &Context<'_, S>) -> bool {
-        self.is_metrics_event(meta)
+        self.is_enabled(meta)
     }
 
     fn callsite_enabled(&self, meta: &'static Metadata<'static>) -> Interest {
-        if self.is_metrics_event(meta) {
+        if self.is_enabled(meta) {
             Interest::always()
         } else {
             Interest::never()
@@ -379,32 +815,380 @@ impl<S> Filter<S> for MetricsFilter {
     }
 }
 
+type TargetMeter = Arc<dyn Fn(&'static str) -> Meter + Send + Sync>;
+type TargetMeters = RwLock<HashMap<&'static str, (Meter, Instruments)>>;
+
+/// Instruments resolved by a layer for a metric of a callsite.
+type CallsiteInstruments = RwLock<HashMap<(callsite::Identifier, &'static str), Instrument>>;
+
 struct InstrumentLayer {
     meter: Meter,
     instruments: Instruments,
+    callsite_instruments: CallsiteInstruments,
+    target_meter: Option<TargetMeter>,
+    target_meters: TargetMeters,
+    /// Keeps registered observable gauges alive as long as the layer.
+    observable_gauges: Mutex<Vec<ObservableGauge<u64>>>,
+    span_metrics: Option<SpanMetrics>,
+    counter_overflow: CounterOverflowPolicy,
+    exemplars: bool,
+    inherited_span_attributes: bool,
+    /// Metrics for which an invalid value was reported.
+    reported_invalid_values: Mutex<HashSet<(&'static str, InvalidValue)>>,
+}
+
+/// Returns the OpenTelemetry context of a span, for exemplars to refer to it.
+fn exemplar_context(data: &OtelData) -> Option<opentelemetry::Context> {
+    let parent = data.parent_cx.span();
+    let parent_span_context = parent.span_context();
+    let trace_id = data.trace_id()?;
+    let span_id = data.span_id()?;
+    let sampled = match &data.builder.sampling_result {
+        Some(result) => result.decision == SamplingDecision::RecordAndSample,
+        None => parent_span_context.is_sampled(),
+    };
+    let trace_flags = if sampled {
+        TraceFlags::SAMPLED
+    } else {
+        TraceFlags::default()
+    };
+    let span_context = SpanContext::new(
+        trace_id,
+        span_id,
+        trace_flags,
+        false,
+        parent_span_context.trace_state().clone(),
+    );
+
+    Some(data.parent_cx.with_remote_span_context(span_context))
+}
+
+/// Histogram of span durations, see [`MetricsLayer::with_span_metrics`].
+struct SpanMetrics {
+    histogram: Histogram<f64>,
+    attribute_keys: Vec<&'static str>,
+}
+
+/// Start time and selected fields of a span, tracked for span metrics.
+struct SpanMetricsData {
+    start: Instant,
+    attributes: Vec<KeyValue>,
+}
+
+/// Fields of a span, tracked for inherited span attributes.
+struct SpanAttributes(Vec<KeyValue>);
+
+/// Records the fields of a span selected as metric attributes, or all of its
+/// fields if no keys are selected.
+struct SpanMetricsVisitor<'a> {
+    attribute_keys: Option<&'a [&'static str]>,
+    attributes: &'a mut Vec<KeyValue>,
+}
+
+impl<'a> SpanMetricsVisitor<'a> {
+    fn is_selected(&self, field: &Field) -> bool {
+        self.attribute_keys
+            .map_or(true, |keys| keys.contains(&field.name()))
+    }
+
+    fn record(&mut self, field: &Field, value: Value) {
+        match self
+            .attributes
+            .iter_mut()
+            .find(|kv| kv.key.as_str() == field.name())
+        {
+            Some(kv) => kv.value = value,
+            None => self.attributes.push(KeyValue::new(field.name(), value)),
+        }
+    }
+}
+
+impl<'a> Visit for SpanMetricsVisitor<'a> {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if self.is_selected(field) {
+            self.record(field, format!("{value:?}").into());
+        }
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        if self.is_selected(field) && value <= I64_MAX {
+            self.record(field, Value::I64(value as i64));
+        }
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        if self.is_selected(field) {
+            self.record(field, Value::F64(value));
+        }
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        if self.is_selected(field) {
+            self.record(field, Value::I64(value));
+        }
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if self.is_selected(field) {
+            self.record(field, value.to_owned().into());
+        }
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        if self.is_selected(field) {
+            self.record(field, Value::Bool(value));
+        }
+    }
+}
+
+impl InstrumentLayer {
+    /// Returns whether spans must be enabled for this layer.
+    fn needs_spans(&self) -> bool {
+        self.span_metrics.is_some() || self.exemplars || self.inherited_span_attributes
+    }
+
+    /// Appends the fields of the spans in the scope of `span` to `attributes`,
+    /// skipping keys which are already present.
+    fn inherit_span_attributes<S>(span: SpanRef<'_, S>, attributes: &mut SmallVec<[KeyValue; 8]>)
+    where
+        S: Subscriber + for<'span> LookupSpan<'span>,
+    {
+        for span in span.scope() {
+            let extensions = span.extensions();
+            let Some(SpanAttributes(span_attributes)) = extensions.get::<SpanAttributes>() else {
+                continue;
+            };
+            for attribute in span_attributes {
+                if !attributes.iter().any(|kv| kv.key == attribute.key) {
+                    attributes.push(attribute.clone());
+                }
+            }
+        }
+    }
+
+    /// Reports a value of a metric which could not be recorded as is, once
+    /// per metric and kind of invalid value.
+    fn report_invalid_value(&self, metric_name: &'static str, invalid_value: InvalidValue) {
+        let mut reported = self.reported_invalid_values.lock().unwrap();
+        if !reported.insert((metric_name, invalid_value)) {
+            return;
+        }
+        drop(reported);
+        let message = match invalid_value {
+            InvalidValue::CounterOverflow => {
+                let action = match self.counter_overflow {
+                    CounterOverflowPolicy::Drop => "dropped",
+                    CounterOverflowPolicy::Saturate => "saturated to i64::MAX",
+                };
+                format!(
+                    "received a value greater than i64::MAX for counter metric `{}`, \
+                    values which overflow are {}",
+                    metric_name, action
+                )
+            }
+            InvalidValue::NonFinite => format!(
+                "received a non-finite value for metric `{}`, non-finite values \
+                are dropped",
+                metric_name
+            ),
+        };
+        global::handle_error(MetricsError::Other(format!(
+            "[tracing-opentelemetry]: {}",
+            message
+        )));
+    }
+
+    fn update_metric(
+        &self,
+        target: &'static str,
+        instrument_type: InstrumentType,
+        metric_name: &'static str,
+        descriptor: &MetricDescriptor,
+        attributes: &[KeyValue],
+    ) -> Instrument {
+        let Some(target_meter) = &self.target_meter else {
+            return self.instruments.update_metric(
+                &self.meter,
+                instrument_type,
+                metric_name,
+                descriptor,
+                attributes,
+            );
+        };
+
+        {
+            let lock = self.target_meters.read().unwrap();
+            if let Some((meter, instruments)) = lock.get(target) {
+                return instruments.update_metric(
+                    meter,
+                    instrument_type,
+                    metric_name,
+                    descriptor,
+                    attributes,
+                );
+            }
+        }
+
+        let mut lock = self.target_meters.write().unwrap();
+        let (meter, instruments) = lock
+            .entry(target)
+            .or_insert_with(|| (target_meter(target), Instruments::default()));
+        instruments.update_metric(meter, instrument_type, metric_name, descriptor, attributes)
+    }
+
+    /// Records a metric of an event emitted by `callsite`, using the cached
+    /// instrument of the callsite when available.
+    fn record_metric(
+        &self,
+        callsite: &callsite::Identifier,
+        target: &'static str,
+        instrument_type: InstrumentType,
+        metric_name: &'static str,
+        descriptor: &MetricDescriptor,
+        attributes: &[KeyValue],
+    ) {
+        let key = (callsite.clone(), metric_name);
+        if let Some(instrument) = self.callsite_instruments.read().unwrap().get(&key) {
+            if instrument.record(instrument_type, attributes) {
+                return;
+            }
+        }
+
+        let instrument =
+            self.update_metric(target, instrument_type, metric_name, descriptor, attributes);
+        self.callsite_instruments
+            .write()
+            .unwrap()
+            .insert(key, instrument);
+    }
 }
 
 impl<S> Layer<S> for InstrumentLayer
 where
     S: Subscriber + for<'span> LookupSpan<'span>,
 {
-    fn on_event(&self, event: &tracing::Event<'_>, _ctx: Context<'_, S>) {
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        if self.span_metrics.is_none() && !self.inherited_span_attributes {
+            return;
+        }
+        let span = ctx.span(id).expect("Span not found, this is a bug");
+        let mut extensions = span.extensions_mut();
+
+        if let Some(span_metrics) = &self.span_metrics {
+            let mut attributes = Vec::new();
+            attrs.record(&mut SpanMetricsVisitor {
+                attribute_keys: Some(&span_metrics.attribute_keys),
+                attributes: &mut attributes,
+            });
+            extensions.insert(SpanMetricsData {
+                start: Instant::now(),
+                attributes,
+            });
+        }
+
+        if self.inherited_span_attributes {
+            let mut attributes = Vec::new();
+            attrs.record(&mut SpanMetricsVisitor {
+                attribute_keys: None,
+                attributes: &mut attributes,
+            });
+            extensions.insert(SpanAttributes(attributes));
+        }
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        if self.span_metrics.is_none() && !self.inherited_span_attributes {
+            return;
+        }
+        let span = ctx.span(id).expect("Span not found, this is a bug");
+        let mut extensions = span.extensions_mut();
+
+        if let Some(span_metrics) = &self.span_metrics {
+            if let Some(data) = extensions.get_mut::<SpanMetricsData>() {
+                values.record(&mut SpanMetricsVisitor {
+                    attribute_keys: Some(&span_metrics.attribute_keys),
+                    attributes: &mut data.attributes,
+                });
+            }
+        }
+
+        if let Some(SpanAttributes(attributes)) = extensions.get_mut::<SpanAttributes>() {
+            values.record(&mut SpanMetricsVisitor {
+                attribute_keys: None,
+                attributes,
+            });
+        }
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        let Some(span_metrics) = &self.span_metrics else {
+            return;
+        };
+        let span = ctx.span(&id).expect("Span not found, this is a bug");
+
+        let data = span.extensions_mut().remove::<SpanMetricsData>();
+        if let Some(data) = data {
+            span_metrics
+                .histogram
+                .record(data.start.elapsed().as_secs_f64(), &data.attributes);
+        }
+    }
+
+    fn on_event(&self, event: &tracing::Event<'_>, ctx: Context<'_, S>) {
+        let callsite = event.metadata().callsite();
+        let target = event.metadata().target();
         let mut attributes = SmallVec::new();
         let mut visited_metrics = SmallVec::new();
+        let mut descriptors = SmallVec::new();
+        let mut invalid_values = SmallVec::new();
         let mut metric_visitor = MetricVisitor {
             attributes: &mut attributes,
             visited_metrics: &mut visited_metrics,
+            descriptors: &mut descriptors,
+            counter_overflow: self.counter_overflow,
+            invalid_values: &mut invalid_values,
         };
         event.record(&mut metric_visitor);
 
+        for (metric_name, invalid_value) in invalid_values {
+            self.report_invalid_value(metric_name, invalid_value);
+        }
+
+        if self.inherited_span_attributes {
+            if let Some(span) = ctx.event_span(event) {
+                Self::inherit_span_attributes(span, &mut attributes);
+            }
+        }
+
+        // Meter providers supporting exemplars take them from the current
+        // context.
+        let _exemplar_context = if self.exemplars {
+            ctx.event_span(event)
+                .and_then(|span| {
+                    span.extensions()
+                        .get::<OtelData>()
+                        .and_then(exemplar_context)
+                })
+                .map(|cx| cx.attach())
+        } else {
+            None
+        };
+
         // associate attrivutes with visited metrics
+        let no_descriptor = MetricDescriptor::default();
         visited_metrics
             .into_iter()
             .for_each(|(metric_name, value)| {
-                self.instruments.update_metric(
-                    &self.meter,
+                let descriptor = descriptors
+                    .iter()
+                    .find(|(name, _)| *name == metric_name)
+                    .map_or(&no_descriptor, |(_, descriptor)| descriptor);
+                self.record_metric(
+                    &callsite,
+                    target,
                     value,
                     metric_name,
+                    descriptor,
                     attributes.as_slice(),
                 );
             })
@@ -501,7 +1285,7 @@ mod tests {
 
     #[test]
     fn filter_layer_should_filter_non_metrics_event() {
-        let layer = PanicLayer.with_filter(MetricsFilter);
+        let layer = PanicLayer.with_filter(MetricsFilter { spans: false });
         let subscriber = tracing_subscriber::registry().with(layer);
 
         tracing::subscriber::with_default(subscriber, || {
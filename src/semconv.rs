@@ -0,0 +1,349 @@
+//! Attribute keys defined by the OpenTelemetry semantic conventions.
+//!
+//! Generated from the `trace` and `resource` modules of
+//! `opentelemetry-semantic-conventions` 0.14.0. Keep sorted, lookups use a
+//! binary search.
+
+/// Known semantic convention attribute keys.
+const KNOWN_KEYS: &[&str] = &[
+    "aws.dynamodb.attribute_definitions",
+    "aws.dynamodb.attributes_to_get",
+    "aws.dynamodb.consistent_read",
+    "aws.dynamodb.consumed_capacity",
+    "aws.dynamodb.count",
+    "aws.dynamodb.exclusive_start_table",
+    "aws.dynamodb.global_secondary_indexes",
+    "aws.dynamodb.index_name",
+    "aws.dynamodb.item_collection_metrics",
+    "aws.dynamodb.limit",
+    "aws.dynamodb.local_secondary_indexes",
+    "aws.dynamodb.projection",
+    "aws.dynamodb.provisioned_read_capacity",
+    "aws.dynamodb.provisioned_write_capacity",
+    "aws.dynamodb.scan_forward",
+    "aws.dynamodb.scanned_count",
+    "aws.dynamodb.segment",
+    "aws.dynamodb.select",
+    "aws.dynamodb.table_count",
+    "aws.dynamodb.table_names",
+    "aws.dynamodb.total_segments",
+    "aws.ecs.cluster.arn",
+    "aws.ecs.container.arn",
+    "aws.ecs.launchtype",
+    "aws.ecs.task.arn",
+    "aws.ecs.task.family",
+    "aws.ecs.task.revision",
+    "aws.eks.cluster.arn",
+    "aws.lambda.invoked_arn",
+    "aws.log.group.arns",
+    "aws.log.group.names",
+    "aws.log.stream.arns",
+    "aws.log.stream.names",
+    "aws.request_id",
+    "aws.s3.bucket",
+    "aws.s3.copy_source",
+    "aws.s3.delete",
+    "aws.s3.key",
+    "aws.s3.part_number",
+    "aws.s3.upload_id",
+    "browser.brands",
+    "browser.language",
+    "browser.mobile",
+    "browser.platform",
+    "client.address",
+    "client.port",
+    "client.socket.address",
+    "client.socket.port",
+    "cloud.account.id",
+    "cloud.availability_zone",
+    "cloud.platform",
+    "cloud.provider",
+    "cloud.region",
+    "cloud.resource_id",
+    "cloudevents.event_id",
+    "cloudevents.event_source",
+    "cloudevents.event_spec_version",
+    "cloudevents.event_subject",
+    "cloudevents.event_type",
+    "code.column",
+    "code.filepath",
+    "code.function",
+    "code.lineno",
+    "code.namespace",
+    "container.command",
+    "container.command_args",
+    "container.command_line",
+    "container.id",
+    "container.image.id",
+    "container.image.name",
+    "container.image.tag",
+    "container.name",
+    "container.runtime",
+    "db.cassandra.consistency_level",
+    "db.cassandra.coordinator.dc",
+    "db.cassandra.coordinator.id",
+    "db.cassandra.idempotence",
+    "db.cassandra.page_size",
+    "db.cassandra.table",
+    "db.connection_string",
+    "db.cosmosdb.client_id",
+    "db.cosmosdb.connection_mode",
+    "db.cosmosdb.container",
+    "db.cosmosdb.operation_type",
+    "db.cosmosdb.request_charge",
+    "db.cosmosdb.request_content_length",
+    "db.cosmosdb.status_code",
+    "db.cosmosdb.sub_status_code",
+    "db.jdbc.driver_classname",
+    "db.mongodb.collection",
+    "db.mssql.instance_name",
+    "db.name",
+    "db.operation",
+    "db.redis.database_index",
+    "db.sql.table",
+    "db.statement",
+    "db.system",
+    "db.user",
+    "deployment.environment",
+    "destination.address",
+    "destination.domain",
+    "destination.port",
+    "device.id",
+    "device.manufacturer",
+    "device.model.identifier",
+    "device.model.name",
+    "enduser.id",
+    "enduser.role",
+    "enduser.scope",
+    "event.domain",
+    "event.name",
+    "exception.escaped",
+    "exception.message",
+    "exception.stacktrace",
+    "exception.type",
+    "faas.coldstart",
+    "faas.cron",
+    "faas.document.collection",
+    "faas.document.name",
+    "faas.document.operation",
+    "faas.document.time",
+    "faas.instance",
+    "faas.invocation_id",
+    "faas.invoked_name",
+    "faas.invoked_provider",
+    "faas.invoked_region",
+    "faas.max_memory",
+    "faas.name",
+    "faas.time",
+    "faas.trigger",
+    "faas.version",
+    "feature_flag.key",
+    "feature_flag.provider_name",
+    "feature_flag.variant",
+    "gcp.cloud_run.job.execution",
+    "gcp.cloud_run.job.task_index",
+    "gcp.gce.instance.hostname",
+    "gcp.gce.instance.name",
+    "graphql.document",
+    "graphql.operation.name",
+    "graphql.operation.type",
+    "heroku.app.id",
+    "heroku.release.commit",
+    "heroku.release.creation_timestamp",
+    "host.arch",
+    "host.id",
+    "host.image.id",
+    "host.image.name",
+    "host.image.version",
+    "host.name",
+    "host.type",
+    "http.method",
+    "http.request.body.size",
+    "http.request.method",
+    "http.request.method_original",
+    "http.request_content_length",
+    "http.resend_count",
+    "http.response.body.size",
+    "http.response.status_code",
+    "http.response_content_length",
+    "http.route",
+    "http.scheme",
+    "http.status_code",
+    "http.target",
+    "http.url",
+    "k8s.cluster.name",
+    "k8s.cluster.uid",
+    "k8s.container.name",
+    "k8s.container.restart_count",
+    "k8s.cronjob.name",
+    "k8s.cronjob.uid",
+    "k8s.daemonset.name",
+    "k8s.daemonset.uid",
+    "k8s.deployment.name",
+    "k8s.deployment.uid",
+    "k8s.job.name",
+    "k8s.job.uid",
+    "k8s.namespace.name",
+    "k8s.node.name",
+    "k8s.node.uid",
+    "k8s.pod.name",
+    "k8s.pod.uid",
+    "k8s.replicaset.name",
+    "k8s.replicaset.uid",
+    "k8s.statefulset.name",
+    "k8s.statefulset.uid",
+    "log.file.name",
+    "log.file.name_resolved",
+    "log.file.path",
+    "log.file.path_resolved",
+    "log.iostream",
+    "log.record.uid",
+    "message.compressed_size",
+    "message.id",
+    "message.type",
+    "message.uncompressed_size",
+    "messaging.batch.message_count",
+    "messaging.client_id",
+    "messaging.destination.anonymous",
+    "messaging.destination.name",
+    "messaging.destination.template",
+    "messaging.destination.temporary",
+    "messaging.kafka.consumer.group",
+    "messaging.kafka.destination.partition",
+    "messaging.kafka.message.key",
+    "messaging.kafka.message.offset",
+    "messaging.kafka.message.tombstone",
+    "messaging.message.conversation_id",
+    "messaging.message.id",
+    "messaging.message.payload_size_bytes",
+    "messaging.operation",
+    "messaging.rocketmq.client_group",
+    "messaging.rocketmq.consumption_model",
+    "messaging.rocketmq.message.group",
+    "messaging.rocketmq.message.keys",
+    "messaging.rocketmq.message.tag",
+    "messaging.rocketmq.message.type",
+    "messaging.rocketmq.namespace",
+    "messaging.system",
+    "net.host.name",
+    "net.host.port",
+    "net.peer.name",
+    "net.peer.port",
+    "net.protocol.name",
+    "net.protocol.version",
+    "net.sock.family",
+    "net.sock.host.addr",
+    "net.sock.host.port",
+    "net.sock.peer.addr",
+    "net.sock.peer.name",
+    "net.sock.peer.port",
+    "net.transport",
+    "network.carrier.icc",
+    "network.carrier.mcc",
+    "network.carrier.mnc",
+    "network.carrier.name",
+    "network.connection.subtype",
+    "network.connection.type",
+    "network.protocol.name",
+    "network.protocol.version",
+    "network.transport",
+    "network.type",
+    "opentracing.ref_type",
+    "os.description",
+    "os.name",
+    "os.type",
+    "os.version",
+    "otel.library.name",
+    "otel.library.version",
+    "otel.scope.name",
+    "otel.scope.version",
+    "otel.status_code",
+    "otel.status_description",
+    "peer.service",
+    "pool",
+    "process.command",
+    "process.command_args",
+    "process.command_line",
+    "process.executable.name",
+    "process.executable.path",
+    "process.owner",
+    "process.parent_pid",
+    "process.pid",
+    "process.runtime.description",
+    "process.runtime.name",
+    "process.runtime.version",
+    "rpc.connect_rpc.error_code",
+    "rpc.grpc.status_code",
+    "rpc.jsonrpc.error_code",
+    "rpc.jsonrpc.error_message",
+    "rpc.jsonrpc.request_id",
+    "rpc.jsonrpc.version",
+    "rpc.method",
+    "rpc.service",
+    "rpc.system",
+    "server.address",
+    "server.port",
+    "server.socket.address",
+    "server.socket.domain",
+    "server.socket.port",
+    "service.instance.id",
+    "service.name",
+    "service.namespace",
+    "service.version",
+    "source.address",
+    "source.domain",
+    "source.port",
+    "telemetry.auto.version",
+    "telemetry.sdk.language",
+    "telemetry.sdk.name",
+    "telemetry.sdk.version",
+    "thread.id",
+    "thread.name",
+    "type",
+    "url.fragment",
+    "url.full",
+    "url.path",
+    "url.query",
+    "url.scheme",
+    "user_agent.original",
+    "webengine.description",
+    "webengine.name",
+    "webengine.version",
+];
+
+/// Prefixes of templated semantic convention keys, such as
+/// `http.request.header.<key>`.
+const TEMPLATE_PREFIXES: &[&str] = &[
+    "http.request.header.",
+    "http.response.header.",
+    "rpc.connect_rpc.request.metadata.",
+    "rpc.connect_rpc.response.metadata.",
+    "rpc.grpc.request.metadata.",
+    "rpc.grpc.response.metadata.",
+];
+
+/// Returns whether `key` is an attribute key defined by the semantic
+/// conventions.
+pub(crate) fn is_known_key(key: &str) -> bool {
+    KNOWN_KEYS.binary_search(&key).is_ok()
+        || TEMPLATE_PREFIXES
+            .iter()
+            .any(|prefix| key.starts_with(prefix))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_are_sorted() {
+        assert!(KNOWN_KEYS.windows(2).all(|keys| keys[0] < keys[1]));
+    }
+
+    #[test]
+    fn recognizes_keys() {
+        assert!(is_known_key("http.request.method"));
+        assert!(is_known_key("http.request.header.x_forwarded_for"));
+        assert!(!is_known_key("htttp.method"));
+    }
+}
@@ -1,5 +1,54 @@
-use crate::layer::WithContext;
-use opentelemetry::{trace::SpanContext, Context, Key, KeyValue, Value};
+use crate::{layer::WithContext, semconv, AttrType, LateReparentPolicy, OtelData};
+use opentelemetry::{
+    global::{self, Error},
+    propagation::{Injector, TextMapPropagator},
+    trace::{
+        Link, SamplingDecision, SpanBuilder, SpanContext, SpanId, SpanKind, Status,
+        TraceContextExt, TraceId,
+    },
+    Context, Key, KeyValue, Value,
+};
+use std::borrow::Cow;
+use std::convert::TryFrom;
+use std::time::SystemTime;
+
+/// Where an attribute set with
+/// [`OpenTelemetrySpanExt::set_attribute_detailed`] was recorded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum AttributeTarget {
+    /// The attribute was added to the OpenTelemetry span builder of the span,
+    /// and will be exported when the span closes.
+    Builder,
+    /// The span is not tracked by an [`OpenTelemetryLayer`], e.g. because it
+    /// is disabled or already closed, and the attribute was discarded.
+    ///
+    /// [`OpenTelemetryLayer`]: crate::OpenTelemetryLayer
+    Dropped,
+}
+
+/// The outcome of the logical operation of a span, recorded with
+/// [`OpenTelemetrySpanExt::set_outcome`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    /// The operation completed successfully.
+    Success,
+    /// The operation failed.
+    Failure,
+    /// The operation completed, but only part of its work succeeded.
+    Partial,
+}
+
+impl Outcome {
+    /// The value recorded in the `operation.outcome` attribute.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Outcome::Success => "success",
+            Outcome::Failure => "failure",
+            Outcome::Partial => "partial",
+        }
+    }
+}
 
 /// Utility functions to allow tracing [`Span`]s to accept and return
 /// [OpenTelemetry] [`Context`]s.
@@ -11,7 +60,12 @@ pub trait OpenTelemetrySpanExt {
     /// Associates `self` with a given OpenTelemetry trace, using the provided
     /// parent [`Context`].
     ///
+    /// If `self` was already sampled, e.g. because its context was requested,
+    /// it is sampled again with its new parent unless configured otherwise
+    /// with [`OpenTelemetryLayer::with_late_reparent_policy`].
+    ///
     /// [`Context`]: opentelemetry::Context
+    /// [`OpenTelemetryLayer::with_late_reparent_policy`]: crate::OpenTelemetryLayer::with_late_reparent_policy
     ///
     /// # Examples
     ///
@@ -42,9 +96,35 @@ pub trait OpenTelemetrySpanExt {
     /// ```
     fn set_parent(&self, cx: Context);
 
+    /// Associates `self` with the OpenTelemetry trace of another `tracing`
+    /// span, making `parent` the parent of `self`.
+    ///
+    /// This is equivalent to `self.set_parent(parent.context())`. Nothing
+    /// happens if `parent` is not tracked by the OpenTelemetry layer.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use tracing_opentelemetry::OpenTelemetrySpanExt;
+    ///
+    /// let request = tracing::info_span!("request");
+    ///
+    /// // Spawned elsewhere, outside of the scope of `request`
+    /// let job = tracing::info_span!(parent: None, "job");
+    /// job.set_parent_span(&request);
+    /// ```
+    fn set_parent_span(&self, parent: &tracing::Span);
+
     /// Associates `self` with a given OpenTelemetry trace, using the provided
     /// followed span [`SpanContext`].
     ///
+    /// Spans are tracked by the OpenTelemetry layer as soon as they are
+    /// created, so links can be added at any time until the span closes,
+    /// including before it is entered. Links added to spans which are not
+    /// tracked, because they are disabled, filtered out of the layer by a
+    /// per-layer filter or already closed, are discarded. Invalid span
+    /// contexts are ignored.
+    ///
     /// [`SpanContext`]: opentelemetry::trace::SpanContext
     ///
     /// # Examples
@@ -83,9 +163,37 @@ pub trait OpenTelemetrySpanExt {
     /// Associates `self` with a given OpenTelemetry trace, using the provided
     /// followed span [`SpanContext`] and attributes.
     ///
+    /// Links are discarded in the same cases as with [`add_link`].
+    ///
     /// [`SpanContext`]: opentelemetry::trace::SpanContext
+    /// [`add_link`]: OpenTelemetrySpanExt::add_link
     fn add_link_with_attributes(&self, cx: SpanContext, attributes: Vec<KeyValue>);
 
+    /// Adds a link from `self` to each of the given `tracing` spans.
+    ///
+    /// This is useful for spans consolidating several in-flight operations,
+    /// such as a batch. IDs of spans that are closed or not tracked by the
+    /// OpenTelemetry layer are skipped. Note that the IDs of closed spans may
+    /// be reused by new spans, so callers should only pass IDs of spans that
+    /// they know to be open.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use tracing_opentelemetry::OpenTelemetrySpanExt;
+    ///
+    /// let first = tracing::info_span!("first");
+    /// let second = tracing::info_span!("second");
+    ///
+    /// let batch = tracing::info_span!("batch");
+    /// let ids = vec![first.id(), second.id()]
+    ///     .into_iter()
+    ///     .flatten()
+    ///     .collect::<Vec<_>>();
+    /// batch.link_to_all_active(&ids);
+    /// ```
+    fn link_to_all_active(&self, ids: &[tracing::span::Id]);
+
     /// Extracts an OpenTelemetry [`Context`] from `self`.
     ///
     /// [`Context`]: opentelemetry::Context
@@ -115,10 +223,106 @@ pub trait OpenTelemetrySpanExt {
     /// ```
     fn context(&self) -> Context;
 
+    /// Extracts an OpenTelemetry [`Context`] from the root of the tree of
+    /// `self`, i.e. its furthest ancestor tracked by the OpenTelemetry layer.
+    ///
+    /// This is useful for propagation schemes which refer to the root of the
+    /// trace rather than to the immediate parent. Only spans of this process
+    /// are considered: if the root has a remote parent, e.g. set with
+    /// [`set_parent`], the context of the local root is returned. To bound the
+    /// cost on deep trees, at most 128 spans are walked, and the context of the
+    /// furthest ancestor reached is returned.
+    ///
+    /// If `self` is not tracked by the OpenTelemetry layer, an empty context
+    /// is returned.
+    ///
+    /// [`Context`]: opentelemetry::Context
+    /// [`set_parent`]: OpenTelemetrySpanExt::set_parent
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use tracing_opentelemetry::OpenTelemetrySpanExt;
+    ///
+    /// let request = tracing::info_span!("request");
+    /// let query = tracing::info_span!(parent: &request, "query");
+    ///
+    /// // The context of `request`
+    /// let root_cx = query.root_context();
+    /// ```
+    fn root_context(&self) -> Context;
+
+    /// Returns the OpenTelemetry [`TraceId`] of `self`, or `None` if `self` is
+    /// not tracked by the OpenTelemetry layer.
+    ///
+    /// Unlike extracting it from [`OpenTelemetrySpanExt::context`], this does
+    /// not build a [`Context`].
+    ///
+    /// [`TraceId`]: opentelemetry::trace::TraceId
+    /// [`Context`]: opentelemetry::Context
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use tracing_opentelemetry::OpenTelemetrySpanExt;
+    ///
+    /// let request = tracing::info_span!("request");
+    ///
+    /// // Correlate non-tracing logs with the trace
+    /// if let Some(trace_id) = request.trace_id() {
+    ///     println!("trace_id={}", trace_id);
+    /// }
+    /// ```
+    fn trace_id(&self) -> Option<TraceId>;
+
+    /// Returns the OpenTelemetry [`SpanId`] of `self`, or `None` if `self` is
+    /// not tracked by the OpenTelemetry layer.
+    ///
+    /// Unlike extracting it from [`OpenTelemetrySpanExt::context`], this does
+    /// not build a [`Context`].
+    ///
+    /// [`SpanId`]: opentelemetry::trace::SpanId
+    /// [`Context`]: opentelemetry::Context
+    fn span_id(&self) -> Option<SpanId>;
+
+    /// Injects the OpenTelemetry [`Context`] of `self` into the given carrier,
+    /// using the provided propagator.
+    ///
+    /// This is a shorthand for extracting the context with
+    /// [`OpenTelemetrySpanExt::context`] and passing it to
+    /// [`TextMapPropagator::inject_context`].
+    ///
+    /// [`Context`]: opentelemetry::Context
+    /// [`TextMapPropagator::inject_context`]: opentelemetry::propagation::TextMapPropagator::inject_context
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use opentelemetry_sdk::propagation::TraceContextPropagator;
+    /// use tracing_opentelemetry::OpenTelemetrySpanExt;
+    /// use std::collections::HashMap;
+    ///
+    /// // Example carrier, could be a framework header map that impls otel's `Injector`.
+    /// let mut carrier = HashMap::new();
+    ///
+    /// // Generate a tracing span as usual
+    /// let app_root = tracing::span!(tracing::Level::INFO, "app_start");
+    ///
+    /// // Inject the span context into the outgoing request headers
+    /// app_root.inject_context(&TraceContextPropagator::new(), &mut carrier);
+    /// ```
+    fn inject_context(&self, propagator: &dyn TextMapPropagator, carrier: &mut dyn Injector);
+
     /// Sets an OpenTelemetry attribute directly for this span, bypassing `tracing`.
     /// If fields set here conflict with `tracing` fields, the `tracing` fields will supersede fields set with `set_attribute`.
     /// This allows for more than 32 fields.
     ///
+    /// If an attribute with the same key was already recorded, its value is
+    /// replaced, so that the last value set wins.
+    ///
+    /// Static keys and values, e.g. `&'static str` or `Cow::Borrowed`, are
+    /// recorded without being copied.
+    ///
     /// # Examples
     ///
     /// ```rust
@@ -133,18 +337,415 @@ pub trait OpenTelemetrySpanExt {
     /// app_root.set_attribute("http.request.header.x_forwarded_for", "example");
     /// ```
     fn set_attribute(&self, key: impl Into<Key>, value: impl Into<Value>);
+
+    /// Removes the OpenTelemetry attributes with the given key recorded so far
+    /// on this span, including attributes recorded from `tracing` fields.
+    ///
+    /// The OpenTelemetry span is only built when the tracing span closes, so
+    /// this can be called at any point of the span's lifetime. Fields recorded
+    /// after the removal are recorded again. Once the span has closed and been
+    /// exported, its attributes cannot be removed, as the OpenTelemetry
+    /// `Span` API has no way to do so.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use tracing_opentelemetry::OpenTelemetrySpanExt;
+    ///
+    /// let span = tracing::info_span!("request", user.email = "alice@example.com");
+    ///
+    /// // Do not export the email of the user
+    /// span.remove_attribute("user.email");
+    /// ```
+    fn remove_attribute(&self, key: impl Into<Key>);
+
+    /// Sets an OpenTelemetry attribute directly for this span, like
+    /// [`set_attribute`], and returns where the attribute was recorded.
+    ///
+    /// This helps diagnosing attributes missing from exported spans.
+    ///
+    /// [`set_attribute`]: OpenTelemetrySpanExt::set_attribute
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use tracing_opentelemetry::{AttributeTarget, OpenTelemetrySpanExt};
+    ///
+    /// // Without an `OpenTelemetryLayer`, attributes are dropped
+    /// let app_root = tracing::span!(tracing::Level::INFO, "app_start");
+    /// let target = app_root.set_attribute_detailed("retries", 3);
+    /// assert_eq!(target, AttributeTarget::Dropped);
+    /// ```
+    fn set_attribute_detailed(
+        &self,
+        key: impl Into<Key>,
+        value: impl Into<Value>,
+    ) -> AttributeTarget;
+
+    /// Sets several OpenTelemetry attributes directly for this span, bypassing
+    /// `tracing`.
+    ///
+    /// This is equivalent to calling [`set_attribute`] for each attribute, but
+    /// only looks up the span once.
+    ///
+    /// [`set_attribute`]: OpenTelemetrySpanExt::set_attribute
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use opentelemetry::KeyValue;
+    /// use tracing_opentelemetry::OpenTelemetrySpanExt;
+    /// use std::collections::HashMap;
+    ///
+    /// let mut headers = HashMap::new();
+    /// headers.insert("x_forwarded_for", "example");
+    ///
+    /// // Generate a tracing span as usual
+    /// let app_root = tracing::span!(tracing::Level::INFO, "app_start");
+    ///
+    /// // Record all headers as attributes
+    /// app_root.set_attributes(headers.iter().map(|(name, value)| {
+    ///     KeyValue::new(format!("http.request.header.{}", name), value.to_string())
+    /// }));
+    /// ```
+    fn set_attributes(&self, attributes: impl IntoIterator<Item = KeyValue>);
+
+    /// Sets an OpenTelemetry attribute of the given type, parsed from a
+    /// string.
+    ///
+    /// This is useful when attribute values come from configuration, which
+    /// only holds strings. If `value` cannot be parsed as `ty`, it is recorded
+    /// as a string instead, along with a `{key}.parse_error = true` attribute.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use tracing_opentelemetry::{AttrType, OpenTelemetrySpanExt};
+    ///
+    /// let app_root = tracing::span!(tracing::Level::INFO, "app_start");
+    ///
+    /// // Records `retries = 3` as an integer
+    /// app_root.set_typed_attribute("retries", "3", AttrType::I64);
+    /// ```
+    fn set_typed_attribute(&self, key: impl Into<Key>, value: &str, ty: AttrType);
+
+    /// Adds an OpenTelemetry event with the current time to this span, only if
+    /// the span is sampled.
+    ///
+    /// The sampling decision of the span is reused if it has already been
+    /// taken. Otherwise, spans with a valid parent follow the sampled flag of
+    /// their parent, and the sampling decision of root spans is taken by the
+    /// first call, so the attributes of the span recorded afterwards are not
+    /// available to the sampler.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use opentelemetry::KeyValue;
+    /// use tracing_opentelemetry::OpenTelemetrySpanExt;
+    ///
+    /// let request = tracing::info_span!("request");
+    ///
+    /// for chunk in 0..3 {
+    ///     request.add_event_if_sampled("chunk_received", vec![KeyValue::new("chunk", chunk)]);
+    /// }
+    /// ```
+    fn add_event_if_sampled(&self, name: impl Into<Cow<'static, str>>, attributes: Vec<KeyValue>);
+
+    /// Calls `f` with the [`OtelData`] of this span, allowing to read and
+    /// modify the span before it is exported.
+    ///
+    /// Nothing happens if `self` is not tracked by the OpenTelemetry layer.
+    /// The span's extensions are locked while `f` runs, so `f` must not call
+    /// methods of this trait on the same span.
+    ///
+    /// [`OtelData`]: crate::OtelData
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use opentelemetry::trace::{Link, SpanContext};
+    /// use tracing_opentelemetry::OpenTelemetrySpanExt;
+    ///
+    /// # let related: Vec<SpanContext> = Vec::new();
+    /// let app_root = tracing::span!(tracing::Level::INFO, "app_start");
+    ///
+    /// app_root.with_otel_data(|data| {
+    ///     data.builder
+    ///         .links
+    ///         .get_or_insert_with(Vec::new)
+    ///         .extend(related.into_iter().map(|cx| Link::new(cx, Vec::new())));
+    /// });
+    /// ```
+    fn with_otel_data(&self, f: impl FnOnce(&mut OtelData));
+
+    /// Sets an OpenTelemetry attribute whose key is expected to follow the
+    /// [OpenTelemetry semantic conventions][conv].
+    ///
+    /// In debug builds, an error is reported to the OpenTelemetry
+    /// [global error handler] if `key` is not a known semantic convention
+    /// attribute, which helps catching typos such as `htttp.method`. The
+    /// attribute is recorded either way. In release builds, this is equivalent
+    /// to [`set_attribute`].
+    ///
+    /// [conv]: https://github.com/open-telemetry/semantic-conventions
+    /// [global error handler]: opentelemetry::global::set_error_handler
+    /// [`set_attribute`]: OpenTelemetrySpanExt::set_attribute
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use opentelemetry_semantic_conventions::trace::HTTP_REQUEST_METHOD;
+    /// use tracing_opentelemetry::OpenTelemetrySpanExt;
+    ///
+    /// let span = tracing::info_span!("request");
+    /// span.set_semconv_attribute(HTTP_REQUEST_METHOD, "GET");
+    /// ```
+    fn set_semconv_attribute(&self, key: &'static str, value: impl Into<Value>);
+
+    /// Returns the value of an OpenTelemetry attribute previously recorded on
+    /// this span, either as a `tracing` field or with [`set_attribute`].
+    ///
+    /// If the attribute was recorded several times, the most recent value is
+    /// returned. Only attributes recorded on the span so far are visible,
+    /// attributes added when the span closes (such as timings) are not.
+    ///
+    /// [`set_attribute`]: OpenTelemetrySpanExt::set_attribute
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use tracing_opentelemetry::OpenTelemetrySpanExt;
+    ///
+    /// let span = tracing::info_span!("request");
+    ///
+    /// // Only record the route if no other middleware did so
+    /// if span.get_attribute("http.route").is_none() {
+    ///     span.set_attribute("http.route", "/users/{id}");
+    /// }
+    /// ```
+    fn get_attribute(&self, key: &str) -> Option<Value>;
+
+    /// Sets the `enduser.id` attribute of this span, optionally replacing the
+    /// id with its SHA-256 digest (hex encoded).
+    ///
+    /// Hashing keeps spans of the same user correlated without exporting the
+    /// user id itself. However, the digest is not salted, so low-entropy ids
+    /// such as email addresses or sequential numbers can be recovered by
+    /// hashing candidate ids. To prevent this, pass an id already hashed with
+    /// a secret key, e.g. with HMAC, and `hash` set to `false`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use tracing_opentelemetry::OpenTelemetrySpanExt;
+    ///
+    /// let span = tracing::info_span!("request");
+    /// span.set_enduser_id("alice@example.com", true);
+    /// ```
+    #[cfg(feature = "sha2")]
+    fn set_enduser_id(&self, id: &str, hash: bool);
+
+    /// Updates the description of this span's error status, without changing
+    /// its status code.
+    ///
+    /// Unlike the `otel.status_message` field, which always sets the status to
+    /// [`Status::Error`], this only refines the description of a span that
+    /// already has an error status. If the span status is [`Status::Unset`] or
+    /// [`Status::Ok`], this does nothing.
+    ///
+    /// [`Status::Error`]: opentelemetry::trace::Status::Error
+    /// [`Status::Unset`]: opentelemetry::trace::Status::Unset
+    /// [`Status::Ok`]: opentelemetry::trace::Status::Ok
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use tracing_opentelemetry::OpenTelemetrySpanExt;
+    ///
+    /// // Generate a tracing span as usual
+    /// let app_root = tracing::span!(tracing::Level::INFO, "app_start");
+    /// let _enter = app_root.enter();
+    ///
+    /// // An error event marks the span status as an error
+    /// tracing::error!("request failed");
+    ///
+    /// // Refine the description of the error status
+    /// app_root.set_status_description("upstream timed out");
+    /// ```
+    fn set_status_description(&self, description: impl Into<Cow<'static, str>>);
+
+    /// Merges the attributes, links and events of an externally built
+    /// OpenTelemetry [`SpanBuilder`] into this span.
+    ///
+    /// Attributes already set on the span take precedence: attributes of the
+    /// provided builder whose key is already present are ignored. Links and
+    /// events are appended after the ones already recorded. All other
+    /// properties of the provided builder are ignored.
+    ///
+    /// [`SpanBuilder`]: opentelemetry::trace::SpanBuilder
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use opentelemetry::{trace::SpanBuilder, KeyValue};
+    /// use tracing_opentelemetry::OpenTelemetrySpanExt;
+    ///
+    /// // A builder prepared by some framework
+    /// let builder = SpanBuilder::from_name("request")
+    ///     .with_attributes(vec![KeyValue::new("http.request.method", "GET")]);
+    ///
+    /// // Adopt its data in a tracing span
+    /// let span = tracing::info_span!("request");
+    /// span.merge_span_builder(builder);
+    /// ```
+    fn merge_span_builder(&self, builder: SpanBuilder);
+
+    /// Sets the `http.route` attribute of this span, and optionally updates the
+    /// span name to `{method} {route}`.
+    ///
+    /// The method is taken from the `http.request.method` attribute (or the
+    /// older `http.method` attribute) already recorded on the span. If no
+    /// method has been recorded, the span is named after the route alone.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use tracing_opentelemetry::OpenTelemetrySpanExt;
+    ///
+    /// let span = tracing::info_span!("request", http.request.method = "GET");
+    ///
+    /// // Once the router matched the request, the span is renamed to `GET /users/{id}`
+    /// span.set_http_route("/users/{id}", true);
+    /// ```
+    fn set_http_route(&self, route: &str, update_name: bool);
+
+    /// Marks this span as an outgoing RPC call, setting its kind to
+    /// [`SpanKind::Client`] and its `rpc.system`, `rpc.service` and
+    /// `rpc.method` attributes, following the [OpenTelemetry semantic
+    /// conventions for RPC][conv].
+    ///
+    /// [`SpanKind::Client`]: opentelemetry::trace::SpanKind::Client
+    /// [conv]: https://github.com/open-telemetry/semantic-conventions/blob/main/docs/rpc/rpc-spans.md
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use tracing_opentelemetry::OpenTelemetrySpanExt;
+    ///
+    /// let span = tracing::info_span!("grpc.client");
+    /// span.set_rpc("grpc", "myservice.EchoService", "Echo");
+    /// ```
+    fn set_rpc(&self, system: &str, service: &str, method: &str);
+
+    /// Records a generative AI operation on this span, setting its
+    /// `gen_ai.system`, `gen_ai.request.model`, `gen_ai.usage.input_tokens`
+    /// and `gen_ai.usage.output_tokens` attributes, following the
+    /// [OpenTelemetry semantic conventions for generative AI][conv].
+    ///
+    /// Token counts greater than `i64::MAX` are recorded as `i64::MAX`.
+    ///
+    /// [conv]: https://github.com/open-telemetry/semantic-conventions/blob/main/docs/gen-ai/gen-ai-spans.md
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use tracing_opentelemetry::OpenTelemetrySpanExt;
+    ///
+    /// let span = tracing::info_span!("chat");
+    /// span.set_gen_ai("openai", "gpt-4", 120, 48);
+    /// ```
+    fn set_gen_ai(&self, system: &str, model: &str, input_tokens: u64, output_tokens: u64);
+
+    /// Records the outcome of the logical operation of this span in the
+    /// `operation.outcome` attribute.
+    ///
+    /// The outcome is distinct from the span [`Status`]: only
+    /// [`Outcome::Failure`] is mapped to it, setting the status to
+    /// [`Status::Error`] if the span does not already have an error status.
+    /// [`Outcome::Success`] and [`Outcome::Partial`] leave the status
+    /// unchanged.
+    ///
+    /// [`Status`]: opentelemetry::trace::Status
+    /// [`Status::Error`]: opentelemetry::trace::Status::Error
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use tracing_opentelemetry::{OpenTelemetrySpanExt, Outcome};
+    ///
+    /// let span = tracing::info_span!("batch");
+    ///
+    /// // Some items of the batch could not be processed
+    /// span.set_outcome(Outcome::Partial);
+    /// ```
+    fn set_outcome(&self, outcome: Outcome);
+
+    /// Updates the name of the OpenTelemetry span.
+    ///
+    /// This can be called at any point of the span's lifetime, e.g. to refine
+    /// a generic name once more details about the work are known. It is
+    /// equivalent to recording the `otel.name` field of the span.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use tracing_opentelemetry::OpenTelemetrySpanExt;
+    ///
+    /// let span = tracing::info_span!("request");
+    /// let _enter = span.enter();
+    ///
+    /// // Once the matched route is known
+    /// span.update_name("GET /users/{id}");
+    /// ```
+    fn update_name(&self, name: impl Into<Cow<'static, str>>);
+
+    /// Overrides the start time of the OpenTelemetry span.
+    ///
+    /// By default, spans start when the tracing span is created. This is
+    /// useful for spans representing work which began earlier, e.g. a job
+    /// which waited in a queue before being picked up. The OpenTelemetry span
+    /// is only started when the tracing span closes, so this can be called at
+    /// any point of the span's lifetime.
+    ///
+    /// # Examples
+    ///
+    /// ```rust
+    /// use std::time::{Duration, SystemTime};
+    /// use tracing_opentelemetry::OpenTelemetrySpanExt;
+    ///
+    /// // The job was enqueued 50ms ago
+    /// let enqueued_at = SystemTime::now() - Duration::from_millis(50);
+    ///
+    /// let span = tracing::info_span!("job");
+    /// span.set_start_time(enqueued_at);
+    /// ```
+    fn set_start_time(&self, timestamp: SystemTime);
 }
 
 impl OpenTelemetrySpanExt for tracing::Span {
     fn set_parent(&self, cx: Context) {
-        let mut cx = Some(cx);
         self.with_subscriber(move |(id, subscriber)| {
             if let Some(get_context) = subscriber.downcast_ref::<WithContext>() {
-                get_context.with_context(subscriber, id, move |data, _tracer| {
-                    if let Some(cx) = cx.take() {
-                        data.parent_cx = cx;
-                    }
+                set_parent_context(get_context, subscriber, id, cx);
+            }
+        });
+    }
+
+    fn set_parent_span(&self, parent: &tracing::Span) {
+        let Some(parent_id) = parent.id() else {
+            return;
+        };
+        self.with_subscriber(|(id, subscriber)| {
+            if let Some(get_context) = subscriber.downcast_ref::<WithContext>() {
+                let mut cx = None;
+                get_context.with_context(subscriber, &parent_id, |data, tracer| {
+                    cx = Some(tracer.sampled_context(data));
                 });
+
+                if let Some(cx) = cx {
+                    set_parent_context(get_context, subscriber, id, cx);
+                }
             }
         });
     }
@@ -174,6 +775,29 @@ impl OpenTelemetrySpanExt for tracing::Span {
         }
     }
 
+    fn link_to_all_active(&self, ids: &[tracing::span::Id]) {
+        self.with_subscriber(|(id, subscriber)| {
+            if let Some(get_context) = subscriber.downcast_ref::<WithContext>() {
+                let mut links = Vec::with_capacity(ids.len());
+                for linked_id in ids {
+                    get_context.with_context(subscriber, linked_id, |data, tracer| {
+                        let cx = tracer.sampled_context(data).span().span_context().clone();
+                        if cx.is_valid() {
+                            links.push(Link::new(cx, Vec::new()));
+                        }
+                    });
+                }
+
+                get_context.with_context(subscriber, id, |data, _tracer| {
+                    data.builder
+                        .links
+                        .get_or_insert_with(|| Vec::with_capacity(links.len()))
+                        .append(&mut links);
+                });
+            }
+        });
+    }
+
     fn context(&self) -> Context {
         let mut cx = None;
         self.with_subscriber(|(id, subscriber)| {
@@ -187,23 +811,386 @@ impl OpenTelemetrySpanExt for tracing::Span {
         cx.unwrap_or_default()
     }
 
+    fn root_context(&self) -> Context {
+        let mut cx = None;
+        self.with_subscriber(|(id, subscriber)| {
+            if let Some(get_context) = subscriber.downcast_ref::<WithContext>() {
+                get_context.with_root_context(subscriber, id, |builder, tracer| {
+                    cx = Some(tracer.sampled_context(builder));
+                })
+            }
+        });
+
+        cx.unwrap_or_default()
+    }
+
+    fn trace_id(&self) -> Option<TraceId> {
+        let mut trace_id = None;
+        self.with_subscriber(|(id, subscriber)| {
+            if let Some(get_context) = subscriber.downcast_ref::<WithContext>() {
+                get_context.with_context(subscriber, id, |data, _tracer| {
+                    trace_id = if data.parent_cx.has_active_span() {
+                        Some(data.parent_cx.span().span_context().trace_id())
+                    } else {
+                        data.builder.trace_id
+                    };
+                })
+            }
+        });
+
+        trace_id
+    }
+
+    fn span_id(&self) -> Option<SpanId> {
+        let mut span_id = None;
+        self.with_subscriber(|(id, subscriber)| {
+            if let Some(get_context) = subscriber.downcast_ref::<WithContext>() {
+                get_context.with_context(subscriber, id, |data, _tracer| {
+                    span_id = data.builder.span_id;
+                })
+            }
+        });
+
+        span_id
+    }
+
+    fn inject_context(&self, propagator: &dyn TextMapPropagator, carrier: &mut dyn Injector) {
+        propagator.inject_context(&self.context(), carrier);
+    }
+
     fn set_attribute(&self, key: impl Into<Key>, value: impl Into<Value>) {
-        self.with_subscriber(move |(id, subscriber)| {
+        self.set_attribute_detailed(key, value);
+    }
+
+    fn set_attribute_detailed(
+        &self,
+        key: impl Into<Key>,
+        value: impl Into<Value>,
+    ) -> AttributeTarget {
+        let mut target = AttributeTarget::Dropped;
+        self.with_subscriber(|(id, subscriber)| {
             if let Some(get_context) = subscriber.downcast_ref::<WithContext>() {
                 let mut key = Some(key.into());
                 let mut value = Some(value.into());
-                get_context.with_context(subscriber, id, move |builder, _| {
-                    if builder.builder.attributes.is_none() {
-                        builder.builder.attributes = Some(Default::default());
+                get_context.with_context(subscriber, id, |builder, _| {
+                    replace_attribute(
+                        builder.builder.attributes.get_or_insert_with(Vec::new),
+                        KeyValue::new(key.take().unwrap(), value.take().unwrap()),
+                    );
+                    target = AttributeTarget::Builder;
+                })
+            }
+        });
+
+        target
+    }
+
+    fn set_attributes(&self, attributes: impl IntoIterator<Item = KeyValue>) {
+        let mut attributes = attributes.into_iter().peekable();
+        if attributes.peek().is_none() {
+            return;
+        }
+
+        self.with_subscriber(move |(id, subscriber)| {
+            if let Some(get_context) = subscriber.downcast_ref::<WithContext>() {
+                get_context.with_context(subscriber, id, |data, _tracer| {
+                    let builder_attributes = data.builder.attributes.get_or_insert_with(Vec::new);
+                    for attribute in &mut attributes {
+                        replace_attribute(builder_attributes, attribute);
                     }
-                    builder
+                });
+            }
+        });
+    }
+
+    fn remove_attribute(&self, key: impl Into<Key>) {
+        let key = key.into();
+        self.with_subscriber(|(id, subscriber)| {
+            if let Some(get_context) = subscriber.downcast_ref::<WithContext>() {
+                get_context.with_context(subscriber, id, |data, _tracer| {
+                    if let Some(attributes) = &mut data.builder.attributes {
+                        attributes.retain(|kv| kv.key != key);
+                    }
+                });
+            }
+        });
+    }
+
+    fn set_typed_attribute(&self, key: impl Into<Key>, value: &str, ty: AttrType) {
+        let key = key.into();
+        match ty.parse(value) {
+            Some(value) => self.set_attribute(key, value),
+            None => self.set_attributes([
+                KeyValue::new(format!("{}.parse_error", key), true),
+                KeyValue::new(key, value.to_owned()),
+            ]),
+        }
+    }
+
+    fn add_event_if_sampled(&self, name: impl Into<Cow<'static, str>>, attributes: Vec<KeyValue>) {
+        let mut event = Some((name, attributes));
+        self.with_subscriber(move |(id, subscriber)| {
+            if let Some(get_context) = subscriber.downcast_ref::<WithContext>() {
+                get_context.with_context(subscriber, id, |data, tracer| {
+                    let sampled = match &data.builder.sampling_result {
+                        Some(result) => result.decision == SamplingDecision::RecordAndSample,
+                        None => {
+                            let parent = data.parent_cx.span();
+                            let parent_span_context = parent.span_context();
+                            if parent_span_context.is_valid() {
+                                parent_span_context.is_sampled()
+                            } else {
+                                tracer
+                                    .sampled_context(data)
+                                    .span()
+                                    .span_context()
+                                    .is_sampled()
+                            }
+                        }
+                    };
+                    if !sampled {
+                        return;
+                    }
+                    if let Some((name, attributes)) = event.take() {
+                        data.builder.events.get_or_insert_with(Vec::new).push(
+                            opentelemetry::trace::Event::new(
+                                name,
+                                crate::time::now(),
+                                attributes,
+                                0,
+                            ),
+                        );
+                    }
+                });
+            }
+        });
+    }
+
+    fn with_otel_data(&self, f: impl FnOnce(&mut OtelData)) {
+        let mut f = Some(f);
+        self.with_subscriber(move |(id, subscriber)| {
+            if let Some(get_context) = subscriber.downcast_ref::<WithContext>() {
+                get_context.with_context(subscriber, id, |data, _tracer| {
+                    if let Some(f) = f.take() {
+                        f(data);
+                    }
+                });
+            }
+        });
+    }
+
+    fn set_semconv_attribute(&self, key: &'static str, value: impl Into<Value>) {
+        if cfg!(debug_assertions) && !semconv::is_known_key(key) {
+            global::handle_error(Error::Other(format!(
+                "[tracing-opentelemetry]: `{}` is not a known semantic convention attribute",
+                key
+            )));
+        }
+        self.set_attribute(key, value);
+    }
+
+    fn get_attribute(&self, key: &str) -> Option<Value> {
+        let mut value = None;
+        self.with_subscriber(|(id, subscriber)| {
+            if let Some(get_context) = subscriber.downcast_ref::<WithContext>() {
+                get_context.with_context(subscriber, id, |data, _tracer| {
+                    value = data
                         .builder
                         .attributes
-                        .as_mut()
-                        .unwrap()
-                        .push(KeyValue::new(key.take().unwrap(), value.take().unwrap()));
-                })
+                        .iter()
+                        .flatten()
+                        .rev()
+                        .find(|kv| kv.key.as_str() == key)
+                        .map(|kv| kv.value.clone());
+                });
+            }
+        });
+
+        value
+    }
+
+    #[cfg(feature = "sha2")]
+    fn set_enduser_id(&self, id: &str, hash: bool) {
+        use sha2::{Digest, Sha256};
+
+        let id = if hash {
+            format!("{:x}", Sha256::digest(id.as_bytes()))
+        } else {
+            id.to_owned()
+        };
+        self.set_attribute("enduser.id", id);
+    }
+
+    fn set_status_description(&self, description: impl Into<Cow<'static, str>>) {
+        let mut description = Some(description.into());
+        self.with_subscriber(move |(id, subscriber)| {
+            if let Some(get_context) = subscriber.downcast_ref::<WithContext>() {
+                get_context.with_context(subscriber, id, |data, _tracer| {
+                    if let Status::Error {
+                        description: current,
+                    } = &mut data.builder.status
+                    {
+                        if let Some(description) = description.take() {
+                            *current = description;
+                        }
+                    }
+                });
+            }
+        });
+    }
+
+    fn set_http_route(&self, route: &str, update_name: bool) {
+        self.with_subscriber(|(id, subscriber)| {
+            if let Some(get_context) = subscriber.downcast_ref::<WithContext>() {
+                get_context.with_context(subscriber, id, |data, _tracer| {
+                    let attributes = data.builder.attributes.get_or_insert_with(Vec::new);
+                    if update_name {
+                        let method = attributes.iter().rev().find(|kv| {
+                            matches!(kv.key.as_str(), "http.request.method" | "http.method")
+                        });
+                        data.builder.name = match method {
+                            Some(method) => format!("{} {}", method.value.as_str(), route).into(),
+                            None => route.to_owned().into(),
+                        };
+                    }
+                    replace_attribute(attributes, KeyValue::new("http.route", route.to_owned()));
+                });
+            }
+        });
+    }
+
+    fn set_rpc(&self, system: &str, service: &str, method: &str) {
+        self.with_subscriber(|(id, subscriber)| {
+            if let Some(get_context) = subscriber.downcast_ref::<WithContext>() {
+                get_context.with_context(subscriber, id, |data, _tracer| {
+                    data.builder.span_kind = Some(SpanKind::Client);
+                    let attributes = data.builder.attributes.get_or_insert_with(Vec::new);
+                    for attribute in [
+                        KeyValue::new("rpc.system", system.to_owned()),
+                        KeyValue::new("rpc.service", service.to_owned()),
+                        KeyValue::new("rpc.method", method.to_owned()),
+                    ] {
+                        replace_attribute(attributes, attribute);
+                    }
+                });
+            }
+        });
+    }
+
+    fn set_gen_ai(&self, system: &str, model: &str, input_tokens: u64, output_tokens: u64) {
+        let tokens = |count: u64| i64::try_from(count).unwrap_or(i64::MAX);
+        self.set_attributes([
+            KeyValue::new("gen_ai.system", system.to_owned()),
+            KeyValue::new("gen_ai.request.model", model.to_owned()),
+            KeyValue::new("gen_ai.usage.input_tokens", tokens(input_tokens)),
+            KeyValue::new("gen_ai.usage.output_tokens", tokens(output_tokens)),
+        ]);
+    }
+
+    fn set_outcome(&self, outcome: Outcome) {
+        self.with_subscriber(|(id, subscriber)| {
+            if let Some(get_context) = subscriber.downcast_ref::<WithContext>() {
+                get_context.with_context(subscriber, id, |data, _tracer| {
+                    if outcome == Outcome::Failure
+                        && !matches!(data.builder.status, Status::Error { .. })
+                    {
+                        data.builder.status = Status::error("");
+                    }
+                    replace_attribute(
+                        data.builder.attributes.get_or_insert_with(Vec::new),
+                        KeyValue::new("operation.outcome", outcome.as_str()),
+                    );
+                });
             }
         });
     }
+
+    fn update_name(&self, name: impl Into<Cow<'static, str>>) {
+        let mut name = Some(name.into());
+        self.with_subscriber(move |(id, subscriber)| {
+            if let Some(get_context) = subscriber.downcast_ref::<WithContext>() {
+                get_context.with_context(subscriber, id, |data, _tracer| {
+                    if let Some(name) = name.take() {
+                        data.builder.name = name;
+                    }
+                });
+            }
+        });
+    }
+
+    fn set_start_time(&self, timestamp: SystemTime) {
+        self.with_subscriber(|(id, subscriber)| {
+            if let Some(get_context) = subscriber.downcast_ref::<WithContext>() {
+                get_context.with_context(subscriber, id, |data, _tracer| {
+                    data.builder.start_time = Some(timestamp);
+                });
+            }
+        });
+    }
+
+    fn merge_span_builder(&self, builder: SpanBuilder) {
+        let mut builder = Some(builder);
+        self.with_subscriber(move |(id, subscriber)| {
+            if let Some(get_context) = subscriber.downcast_ref::<WithContext>() {
+                get_context.with_context(subscriber, id, |data, _tracer| {
+                    let other = match builder.take() {
+                        Some(other) => other,
+                        None => return,
+                    };
+
+                    if let Some(attributes) = other.attributes {
+                        let existing = data.builder.attributes.get_or_insert_with(Vec::new);
+                        let len = existing.len();
+                        for attribute in attributes {
+                            if !existing[..len].iter().any(|kv| kv.key == attribute.key) {
+                                existing.push(attribute);
+                            }
+                        }
+                    }
+                    if let Some(mut links) = other.links {
+                        data.builder
+                            .links
+                            .get_or_insert_with(Vec::new)
+                            .append(&mut links);
+                    }
+                    if let Some(mut events) = other.events {
+                        data.builder
+                            .events
+                            .get_or_insert_with(Vec::new)
+                            .append(&mut events);
+                    }
+                });
+            }
+        });
+    }
+}
+
+/// Assigns `cx` as the parent context of the span with the given `id`,
+/// following the late re-parenting policy of the layer.
+fn set_parent_context(
+    get_context: &WithContext,
+    dispatch: &tracing::Dispatch,
+    id: &tracing::span::Id,
+    cx: Context,
+) {
+    get_context.validate_parent_context(dispatch, &cx);
+    let policy = get_context.late_reparent_policy(dispatch);
+    let mut cx = Some(cx);
+    get_context.with_context(dispatch, id, |data, _tracer| {
+        if let Some(cx) = cx.take() {
+            data.parent_cx = cx;
+            if policy == LateReparentPolicy::Resample {
+                data.builder.sampling_result = None;
+            }
+        }
+    });
+}
+
+/// Replaces the value of the attribute with the same key as `attribute`, or
+/// appends `attribute` if there is none.
+fn replace_attribute(attributes: &mut Vec<KeyValue>, attribute: KeyValue) {
+    match attributes.iter_mut().find(|kv| kv.key == attribute.key) {
+        Some(kv) => kv.value = attribute.value,
+        None => attributes.push(attribute),
+    }
 }
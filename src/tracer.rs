@@ -1,4 +1,5 @@
 use opentelemetry::{
+    global::{self, BoxedSpan, BoxedTracer},
     trace as otel,
     trace::{
         noop, SamplingDecision, SamplingResult, SpanBuilder, SpanContext, SpanId, SpanKind,
@@ -6,7 +7,11 @@ use opentelemetry::{
     },
     Context as OtelContext,
 };
-use opentelemetry_sdk::trace::{Tracer as SdkTracer, TracerProvider as SdkTracerProvider};
+use opentelemetry_sdk::trace::{
+    IdGenerator, RandomIdGenerator, Span as SdkSpan, Tracer as SdkTracer,
+    TracerProvider as SdkTracerProvider,
+};
+use std::{borrow::Cow, sync::Arc};
 
 /// An interface for authors of OpenTelemetry SDKs to build pre-sampled tracers.
 ///
@@ -92,8 +97,7 @@ impl PreSampledTracer for SdkTracer {
                 builder.sampling_result.as_ref().unwrap(),
                 parent_trace_flags,
             )
-        }
-        .unwrap_or_default();
+        };
 
         let span_id = builder.span_id.unwrap_or(SpanId::INVALID);
         let span_context = SpanContext::new(trace_id, span_id, flags, false, trace_state);
@@ -113,6 +117,184 @@ impl PreSampledTracer for SdkTracer {
     }
 }
 
+/// An OpenTelemetry SDK [`Tracer`] whose instrumentation scope version is
+/// overridden.
+///
+/// The instrumentation scope of exported spans is the one of the tracer
+/// building them. This is a shim creating a tracer with the same scope name,
+/// schema URL and attributes as an existing SDK tracer, from the same tracer
+/// provider, but with a different version. It is useful when the tracer is
+/// built elsewhere, e.g. by a shared telemetry setup crate, and passed to
+/// [`OpenTelemetryLayer::with_tracer`].
+///
+/// If the tracer provider of the original tracer has been dropped, spans are
+/// built by the original tracer, which does not export them anyway.
+///
+/// [`Tracer`]: opentelemetry_sdk::trace::Tracer
+/// [`OpenTelemetryLayer::with_tracer`]: crate::OpenTelemetryLayer::with_tracer
+///
+/// # Examples
+///
+/// ```
+/// use opentelemetry::trace::TracerProvider as _;
+/// use opentelemetry_sdk::trace::TracerProvider;
+/// use tracing_opentelemetry::ScopedTracer;
+/// use tracing_subscriber::layer::SubscriberExt;
+/// use tracing_subscriber::Registry;
+///
+/// let provider = TracerProvider::builder().build();
+/// let tracer = provider.tracer("my-service");
+///
+/// let tracer = ScopedTracer::new(&tracer, env!("CARGO_PKG_VERSION"));
+/// let subscriber = Registry::default().with(tracing_opentelemetry::layer().with_tracer(tracer));
+/// # drop(subscriber);
+/// ```
+#[derive(Clone, Debug)]
+pub struct ScopedTracer(SdkTracer);
+
+impl ScopedTracer {
+    /// Creates a tracer using the instrumentation scope of `tracer`, with its
+    /// version replaced by `version`.
+    pub fn new(tracer: &SdkTracer, version: impl Into<Cow<'static, str>>) -> Self {
+        let Some(provider) = tracer.provider() else {
+            return ScopedTracer(tracer.clone());
+        };
+
+        let mut library = tracer.instrumentation_library().clone();
+        library.version = Some(version.into());
+        ScopedTracer(otel::TracerProvider::library_tracer(
+            &provider,
+            Arc::new(library),
+        ))
+    }
+}
+
+impl otel::Tracer for ScopedTracer {
+    type Span = SdkSpan;
+
+    fn build_with_context(&self, builder: SpanBuilder, parent_cx: &OtelContext) -> Self::Span {
+        self.0.build_with_context(builder, parent_cx)
+    }
+}
+
+impl PreSampledTracer for ScopedTracer {
+    fn sampled_context(&self, data: &mut crate::OtelData) -> OtelContext {
+        self.0.sampled_context(data)
+    }
+
+    fn new_trace_id(&self) -> otel::TraceId {
+        self.0.new_trace_id()
+    }
+
+    fn new_span_id(&self) -> otel::SpanId {
+        self.0.new_span_id()
+    }
+}
+
+/// A [`Tracer`] of the global tracer provider, usable with an
+/// [`OpenTelemetryLayer`].
+///
+/// Tracers returned by [`global::tracer`] hide the type of the tracer
+/// provider, so they cannot implement [`PreSampledTracer`]. This wrapper
+/// implements it without access to the provider, which lets libraries create
+/// a layer exporting to whichever provider the application installs.
+///
+/// Trace and span ids are generated by a [`RandomIdGenerator`] by default,
+/// see [`GlobalTracer::with_id_generator`].
+///
+/// # Sampling
+///
+/// The sampler of the global provider is not available before spans are
+/// exported, so the contexts returned by
+/// [`OpenTelemetrySpanExt::context`] assume that root spans are sampled, and
+/// that other spans follow the sampling decision of their parent. This
+/// matches the default `ParentBased(AlwaysOn)` sampler of the SDK. With other
+/// samplers, the sampling flag of propagated contexts may differ from the
+/// decision taken when spans are exported, and downstream services may record
+/// spans whose parents are dropped.
+///
+/// [`Tracer`]: opentelemetry::trace::Tracer
+/// [`OpenTelemetryLayer`]: crate::OpenTelemetryLayer
+/// [`global::tracer`]: opentelemetry::global::tracer
+/// [`RandomIdGenerator`]: opentelemetry_sdk::trace::RandomIdGenerator
+/// [`OpenTelemetrySpanExt::context`]: crate::OpenTelemetrySpanExt::context
+///
+/// # Examples
+///
+/// ```
+/// use tracing_opentelemetry::GlobalTracer;
+/// use tracing_subscriber::layer::SubscriberExt;
+/// use tracing_subscriber::Registry;
+///
+/// let tracer = GlobalTracer::new("my-library");
+/// let subscriber = Registry::default().with(tracing_opentelemetry::layer().with_tracer(tracer));
+/// # drop(subscriber);
+/// ```
+#[derive(Clone, Debug)]
+pub struct GlobalTracer {
+    tracer: Arc<BoxedTracer>,
+    id_generator: Arc<dyn IdGenerator>,
+}
+
+impl GlobalTracer {
+    /// Creates a tracer named `name` from the global tracer provider.
+    pub fn new(name: impl Into<Cow<'static, str>>) -> Self {
+        GlobalTracer {
+            tracer: Arc::new(global::tracer(name)),
+            id_generator: Arc::new(RandomIdGenerator::default()),
+        }
+    }
+
+    /// Sets the generator of trace and span ids.
+    ///
+    /// This should match the id generator of the global tracer provider, e.g.
+    /// when exporting to a backend expecting ids in a specific format.
+    pub fn with_id_generator<T: IdGenerator + 'static>(self, id_generator: T) -> Self {
+        GlobalTracer {
+            id_generator: Arc::new(id_generator),
+            ..self
+        }
+    }
+}
+
+impl otel::Tracer for GlobalTracer {
+    type Span = BoxedSpan;
+
+    fn build_with_context(&self, builder: SpanBuilder, parent_cx: &OtelContext) -> Self::Span {
+        self.tracer.build_with_context(builder, parent_cx)
+    }
+}
+
+impl PreSampledTracer for GlobalTracer {
+    fn sampled_context(&self, data: &mut crate::OtelData) -> OtelContext {
+        let parent_cx = &data.parent_cx;
+        let builder = &mut data.builder;
+
+        let (trace_id, flags, trace_state) = if parent_cx.has_active_span() {
+            let span = parent_cx.span();
+            let sc = span.span_context();
+            (sc.trace_id(), sc.trace_flags(), sc.trace_state().clone())
+        } else {
+            let trace_id = *builder
+                .trace_id
+                .get_or_insert_with(|| self.id_generator.new_trace_id());
+            (trace_id, TraceFlags::SAMPLED, TraceState::default())
+        };
+
+        let span_id = builder.span_id.unwrap_or(SpanId::INVALID);
+        let span_context = SpanContext::new(trace_id, span_id, flags, false, trace_state);
+        parent_cx.with_remote_span_context(span_context)
+    }
+
+    fn new_trace_id(&self) -> otel::TraceId {
+        self.id_generator.new_trace_id()
+    }
+
+    fn new_span_id(&self) -> otel::SpanId {
+        self.id_generator.new_span_id()
+    }
+}
+
 fn current_trace_state(
     builder: &SpanBuilder,
     parent_cx: &OtelContext,
@@ -135,22 +317,25 @@ fn current_trace_state(
 fn process_sampling_result(
     sampling_result: &SamplingResult,
     trace_flags: TraceFlags,
-) -> Option<(TraceFlags, TraceState)> {
+) -> (TraceFlags, TraceState) {
     match sampling_result {
+        // Like the SDK, keep the trace state of dropped spans so that it is
+        // still propagated to downstream services.
         SamplingResult {
             decision: SamplingDecision::Drop,
+            trace_state,
             ..
-        } => None,
+        } => (TraceFlags::default(), trace_state.clone()),
         SamplingResult {
             decision: SamplingDecision::RecordOnly,
             trace_state,
             ..
-        } => Some((trace_flags & !TraceFlags::SAMPLED, trace_state.clone())),
+        } => (trace_flags & !TraceFlags::SAMPLED, trace_state.clone()),
         SamplingResult {
             decision: SamplingDecision::RecordAndSample,
             trace_state,
             ..
-        } => Some((trace_flags | TraceFlags::SAMPLED, trace_state.clone())),
+        } => (trace_flags | TraceFlags::SAMPLED, trace_state.clone()),
     }
 }
 
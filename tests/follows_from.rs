@@ -1,5 +1,5 @@
 use futures_util::future::BoxFuture;
-use opentelemetry::trace::TracerProvider as _;
+use opentelemetry::{trace::TracerProvider as _, KeyValue};
 use opentelemetry_sdk::{
     export::trace::{ExportResult, SpanData, SpanExporter},
     trace::{Tracer, TracerProvider},
@@ -60,3 +60,31 @@ fn trace_follows_from_closed() {
     // Only the child spans are reported.
     assert_eq!(spans.len(), 2);
 }
+
+#[test]
+fn follows_from_link_type_attribute() {
+    let exporter = TestExporter::default();
+    let provider = TracerProvider::builder()
+        .with_simple_exporter(exporter.clone())
+        .build();
+    let subscriber = tracing_subscriber::registry().with(
+        layer()
+            .with_tracer(provider.tracer("test"))
+            .with_follows_from_link_type_attribute(true),
+    );
+
+    tracing::subscriber::with_default(subscriber, || {
+        let cause = tracing::debug_span!("cause");
+        let effect = tracing::debug_span!("effect");
+        effect.follows_from(&cause);
+    });
+
+    drop(provider); // flush all spans
+    let spans = exporter.0.lock().unwrap();
+    let effect = spans.iter().find(|s| s.name == "effect").unwrap();
+    assert_eq!(effect.links.len(), 1);
+    assert_eq!(
+        effect.links[0].attributes,
+        vec![KeyValue::new("link.type", "follows_from")]
+    );
+}
@@ -0,0 +1,50 @@
+// A separate test binary, since the test sets the global tracer provider.
+use futures_util::future::BoxFuture;
+use opentelemetry::{global, trace::TraceContextExt};
+use opentelemetry_sdk::{
+    export::trace::{ExportResult, SpanData, SpanExporter},
+    trace::TracerProvider,
+};
+use std::sync::{Arc, Mutex};
+use tracing_opentelemetry::{layer, GlobalTracer, OpenTelemetrySpanExt};
+use tracing_subscriber::prelude::*;
+
+#[derive(Clone, Default, Debug)]
+struct TestExporter(Arc<Mutex<Vec<SpanData>>>);
+
+impl SpanExporter for TestExporter {
+    fn export(&mut self, mut batch: Vec<SpanData>) -> BoxFuture<'static, ExportResult> {
+        self.0.lock().unwrap().append(&mut batch);
+        Box::pin(std::future::ready(Ok(())))
+    }
+}
+
+#[test]
+fn global_tracer_exports_to_global_provider() {
+    let exporter = TestExporter::default();
+    let provider = TracerProvider::builder()
+        .with_simple_exporter(exporter.clone())
+        .build();
+    global::set_tracer_provider(provider);
+
+    let subscriber =
+        tracing_subscriber::registry().with(layer().with_tracer(GlobalTracer::new("test")));
+
+    let mut contexts = Vec::new();
+    tracing::subscriber::with_default(subscriber, || {
+        let root = tracing::debug_span!("root");
+        let child = root.in_scope(|| tracing::debug_span!("child"));
+        contexts.push(root.context().span().span_context().clone());
+        contexts.push(child.context().span().span_context().clone());
+    });
+
+    global::shutdown_tracer_provider(); // flush all spans
+    let spans = exporter.0.lock().unwrap();
+    let root = spans.iter().find(|s| s.name == "root").unwrap();
+    let child = spans.iter().find(|s| s.name == "child").unwrap();
+    assert!(contexts.iter().all(|cx| cx.is_sampled()));
+    assert_eq!(contexts[0], root.span_context);
+    assert_eq!(contexts[1].span_id(), child.span_context.span_id());
+    assert_eq!(child.parent_span_id, root.span_context.span_id());
+    assert_eq!(child.span_context.trace_id(), root.span_context.trace_id());
+}
@@ -1,7 +1,14 @@
-use opentelemetry::{metrics::MetricsError, KeyValue};
+use opentelemetry::{
+    metrics::{
+        Histogram as HistogramInstrument, InstrumentProvider, Meter, MeterProvider, MetricsError,
+        SyncHistogram, Unit,
+    },
+    trace::{SpanContext, TraceContextExt, TracerProvider as _},
+    Context, KeyValue,
+};
 use opentelemetry_sdk::{
     metrics::{
-        data::{self, Histogram, Sum},
+        data::{self, Gauge, Histogram, Sum},
         reader::{
             AggregationSelector, DefaultAggregationSelector, DefaultTemporalitySelector,
             MetricReader, TemporalitySelector,
@@ -11,9 +18,13 @@ use opentelemetry_sdk::{
     AttributeSet, Resource,
 };
 
-use std::{fmt::Debug, sync::Arc};
+use std::{
+    borrow::Cow,
+    fmt::Debug,
+    sync::{Arc, Mutex},
+};
 use tracing::Subscriber;
-use tracing_opentelemetry::MetricsLayer;
+use tracing_opentelemetry::{CounterOverflowPolicy, MetricsLayer, OpenTelemetrySpanExt};
 use tracing_subscriber::prelude::*;
 
 const CARGO_PKG_VERSION: &str = env!("CARGO_PKG_VERSION");
@@ -51,6 +62,37 @@ async fn u64_counter_is_exported_i64_at_instrumentation_point() {
     exporter.export().unwrap();
 }
 
+#[tokio::test]
+async fn u64_counter_is_accumulated_across_emissions_from_same_callsite() {
+    let (subscriber, exporter) = init_subscriber(
+        "hello_world_repeated".to_string(),
+        InstrumentKind::Counter,
+        3_u64,
+        None,
+    );
+
+    tracing::subscriber::with_default(subscriber, || {
+        for _ in 0..3 {
+            tracing::info!(monotonic_counter.hello_world_repeated = 1_u64);
+        }
+    });
+
+    exporter.export().unwrap();
+}
+
+#[tokio::test]
+async fn bool_counter_is_exported() {
+    let (subscriber, exporter) =
+        init_subscriber("success".to_string(), InstrumentKind::Counter, 1_u64, None);
+
+    tracing::subscriber::with_default(subscriber, || {
+        tracing::info!(monotonic_counter.success = true);
+        tracing::info!(monotonic_counter.success = false);
+    });
+
+    exporter.export().unwrap();
+}
+
 #[tokio::test]
 async fn f64_counter_is_exported() {
     let (subscriber, exporter) = init_subscriber(
@@ -147,6 +189,70 @@ async fn f64_histogram_is_exported() {
     exporter.export().unwrap();
 }
 
+#[tokio::test]
+async fn nonfinite_histogram_value_is_dropped() {
+    let (subscriber, exporter) = init_subscriber(
+        "abcdefg_racecar".to_string(),
+        InstrumentKind::Histogram,
+        777.0012_f64,
+        None,
+    );
+
+    tracing::subscriber::with_default(subscriber, || {
+        tracing::info!(histogram.abcdefg_racecar = f64::NAN);
+        tracing::info!(histogram.abcdefg_racecar = 777.0012_f64);
+    });
+
+    exporter.export().unwrap();
+}
+
+#[tokio::test]
+async fn overflowing_up_down_counter_is_dropped() {
+    let (subscriber, exporter) = init_subscriber(
+        "pebcak_overflow".to_string(),
+        InstrumentKind::UpDownCounter,
+        5_i64,
+        None,
+    );
+
+    tracing::subscriber::with_default(subscriber, || {
+        tracing::info!(counter.pebcak_overflow = u64::MAX);
+        tracing::info!(counter.pebcak_overflow = 5_u64);
+    });
+
+    exporter.export().unwrap();
+}
+
+#[tokio::test]
+async fn overflowing_up_down_counter_is_saturated() {
+    let reader = TestReader {
+        inner: Arc::new(ManualReader::builder().build()),
+    };
+    let provider = MeterProviderBuilder::default()
+        .with_reader(reader.clone())
+        .build();
+    let subscriber = tracing_subscriber::registry().with(
+        MetricsLayer::new(provider.clone())
+            .with_counter_overflow_policy(CounterOverflowPolicy::Saturate),
+    );
+
+    tracing::subscriber::with_default(subscriber, || {
+        tracing::info!(counter.queue_bytes = u64::MAX);
+    });
+
+    let mut rm = data::ResourceMetrics {
+        resource: Resource::default(),
+        scope_metrics: Vec::new(),
+    };
+    reader.collect(&mut rm).unwrap();
+
+    let metric = &rm.scope_metrics[0].metrics[0];
+    assert_eq!(metric.name, "queue_bytes");
+    let sum = metric.data.as_any().downcast_ref::<Sum<i64>>().unwrap();
+    assert_eq!(sum.data_points.len(), 1);
+    assert_eq!(sum.data_points[0].value, i64::MAX);
+}
+
 #[tokio::test]
 async fn u64_counter_with_attributes_is_exported() {
     let (subscriber, exporter) = init_subscriber(
@@ -401,6 +507,276 @@ async fn debug_attribute_is_exported() {
     exporter.export().unwrap();
 }
 
+#[tokio::test]
+async fn meter_per_target_exports_one_scope_per_target() {
+    let reader = TestReader {
+        inner: Arc::new(ManualReader::builder().build()),
+    };
+    let provider = MeterProviderBuilder::default()
+        .with_reader(reader.clone())
+        .build();
+    let subscriber = tracing_subscriber::registry()
+        .with(MetricsLayer::new(provider.clone()).with_meter_per_target(provider.clone()));
+
+    tracing::subscriber::with_default(subscriber, || {
+        tracing::event!(
+            target: "first_target",
+            tracing::Level::INFO,
+            monotonic_counter.hello_world = 1_u64
+        );
+        tracing::event!(
+            target: "second_target",
+            tracing::Level::INFO,
+            monotonic_counter.hello_world = 2_u64
+        );
+    });
+
+    let mut rm = data::ResourceMetrics {
+        resource: Resource::default(),
+        scope_metrics: Vec::new(),
+    };
+    reader.collect(&mut rm).unwrap();
+
+    let mut scopes = rm
+        .scope_metrics
+        .iter()
+        .map(|scope_metrics| {
+            assert_eq!(scope_metrics.metrics.len(), 1);
+            assert_eq!(scope_metrics.metrics[0].name, "hello_world");
+            scope_metrics.scope.name.as_ref()
+        })
+        .collect::<Vec<_>>();
+    scopes.sort_unstable();
+    assert_eq!(scopes, vec!["first_target", "second_target"]);
+}
+
+#[tokio::test]
+async fn span_duration_is_exported_with_selected_attributes() {
+    let reader = TestReader {
+        inner: Arc::new(ManualReader::builder().build()),
+    };
+    let provider = MeterProviderBuilder::default()
+        .with_reader(reader.clone())
+        .build();
+    let subscriber = tracing_subscriber::registry().with(
+        MetricsLayer::new(provider.clone())
+            .with_span_metrics("span.duration", vec!["http.route", "http.status"]),
+    );
+
+    tracing::subscriber::with_default(subscriber, || {
+        let span = tracing::info_span!(
+            "request",
+            http.route = "/users/:id",
+            user.id = 42,
+            http.status = tracing::field::Empty
+        );
+        span.record("http.status", 200);
+    });
+
+    let mut rm = data::ResourceMetrics {
+        resource: Resource::default(),
+        scope_metrics: Vec::new(),
+    };
+    reader.collect(&mut rm).unwrap();
+
+    let metric = &rm.scope_metrics[0].metrics[0];
+    assert_eq!(metric.name, "span.duration");
+    assert_eq!(metric.unit.as_str(), "s");
+    let histogram = metric
+        .data
+        .as_any()
+        .downcast_ref::<Histogram<f64>>()
+        .unwrap();
+    assert_eq!(histogram.data_points.len(), 1);
+    assert_eq!(histogram.data_points[0].count, 1);
+    assert_eq!(
+        histogram.data_points[0].attributes,
+        AttributeSet::from(
+            &[
+                KeyValue::new("http.route", "/users/:id"),
+                KeyValue::new("http.status", 200),
+            ][..]
+        )
+    );
+}
+
+#[tokio::test]
+async fn span_fields_are_inherited_as_attributes() {
+    let reader = TestReader {
+        inner: Arc::new(ManualReader::builder().build()),
+    };
+    let provider = MeterProviderBuilder::default()
+        .with_reader(reader.clone())
+        .build();
+    let subscriber = tracing_subscriber::registry()
+        .with(MetricsLayer::new(provider.clone()).with_inherited_span_attributes(true));
+
+    tracing::subscriber::with_default(subscriber, || {
+        let _request = tracing::info_span!("request", tenant = "acme", region = "eu").entered();
+        let _job = tracing::info_span!("job", region = "us").entered();
+        tracing::info!(monotonic_counter.jobs = 1_u64, queue = "default");
+    });
+
+    let mut rm = data::ResourceMetrics {
+        resource: Resource::default(),
+        scope_metrics: Vec::new(),
+    };
+    reader.collect(&mut rm).unwrap();
+
+    let metric = &rm.scope_metrics[0].metrics[0];
+    assert_eq!(metric.name, "jobs");
+    let sum = metric.data.as_any().downcast_ref::<Sum<u64>>().unwrap();
+    assert_eq!(sum.data_points.len(), 1);
+    assert_eq!(
+        sum.data_points[0].attributes,
+        AttributeSet::from(
+            &[
+                KeyValue::new("queue", "default"),
+                KeyValue::new("region", "us"),
+                KeyValue::new("tenant", "acme"),
+            ][..]
+        )
+    );
+}
+
+#[tokio::test]
+async fn observable_gauge_is_exported() {
+    let reader = TestReader {
+        inner: Arc::new(ManualReader::builder().build()),
+    };
+    let provider = MeterProviderBuilder::default()
+        .with_reader(reader.clone())
+        .build();
+    let layer = MetricsLayer::new(provider.clone());
+    layer.register_observable_gauge("queue_depth", |observer| {
+        observer.observe(42, &[KeyValue::new("queue", "jobs")]);
+    });
+    let _subscriber = tracing_subscriber::registry().with(layer);
+
+    let mut rm = data::ResourceMetrics {
+        resource: Resource::default(),
+        scope_metrics: Vec::new(),
+    };
+    reader.collect(&mut rm).unwrap();
+
+    let metric = &rm.scope_metrics[0].metrics[0];
+    assert_eq!(metric.name, "queue_depth");
+    let gauge = metric.data.as_any().downcast_ref::<Gauge<u64>>().unwrap();
+    assert_eq!(gauge.data_points.len(), 1);
+    assert_eq!(gauge.data_points[0].value, 42);
+    assert_eq!(
+        gauge.data_points[0].attributes,
+        AttributeSet::from(&[KeyValue::new("queue", "jobs")][..])
+    );
+}
+
+#[tokio::test]
+async fn unit_and_description_are_set_on_creation() {
+    let reader = TestReader {
+        inner: Arc::new(ManualReader::builder().build()),
+    };
+    let provider = MeterProviderBuilder::default()
+        .with_reader(reader.clone())
+        .build();
+    let subscriber = tracing_subscriber::registry().with(MetricsLayer::new(provider.clone()));
+
+    tracing::subscriber::with_default(subscriber, || {
+        tracing::info!(
+            monotonic_counter.bytes_sent = 1_u64,
+            monotonic_counter.bytes_sent.unit = "By",
+            monotonic_counter.bytes_sent.description = "total bytes"
+        );
+        tracing::info!(
+            monotonic_counter.bytes_sent = 1_u64,
+            monotonic_counter.bytes_sent.unit = "kB",
+            monotonic_counter.bytes_sent.description = "ignored"
+        );
+    });
+
+    let mut rm = data::ResourceMetrics {
+        resource: Resource::default(),
+        scope_metrics: Vec::new(),
+    };
+    reader.collect(&mut rm).unwrap();
+
+    let metric = &rm.scope_metrics[0].metrics[0];
+    assert_eq!(metric.name, "bytes_sent");
+    assert_eq!(metric.unit.as_str(), "By");
+    assert_eq!(metric.description, "total bytes");
+    let sum = metric.data.as_any().downcast_ref::<Sum<u64>>().unwrap();
+    assert_eq!(sum.data_points.len(), 1);
+    assert_eq!(sum.data_points[0].value, 2);
+    assert_eq!(sum.data_points[0].attributes, AttributeSet::default());
+}
+
+#[test]
+fn exemplar_context_is_current_when_recording() {
+    let provider = ContextRecordingProvider::default();
+    let tracer_provider = opentelemetry_sdk::trace::TracerProvider::default();
+    let subscriber = tracing_subscriber::registry()
+        .with(tracing_opentelemetry::layer().with_tracer(tracer_provider.tracer("test")))
+        .with(MetricsLayer::new(provider.clone()).with_exemplars(true));
+
+    tracing::subscriber::with_default(subscriber, || {
+        tracing::info!(histogram.latency = 1.0);
+
+        let span = tracing::info_span!("request");
+        // Takes the sampling decision of the span
+        span.context();
+        span.in_scope(|| tracing::info!(histogram.latency = 2.0));
+
+        let contexts = provider.0.lock().unwrap();
+        assert_eq!(contexts.len(), 2);
+        assert!(!contexts[0].is_valid());
+        assert_eq!(Some(contexts[1].trace_id()), span.trace_id());
+        assert_eq!(Some(contexts[1].span_id()), span.span_id());
+        assert!(contexts[1].is_sampled());
+    });
+}
+
+/// Meter provider recording the current span context of every histogram
+/// measurement.
+#[derive(Clone, Default)]
+struct ContextRecordingProvider(Arc<Mutex<Vec<SpanContext>>>);
+
+impl MeterProvider for ContextRecordingProvider {
+    fn versioned_meter(
+        &self,
+        _name: impl Into<Cow<'static, str>>,
+        _version: Option<impl Into<Cow<'static, str>>>,
+        _schema_url: Option<impl Into<Cow<'static, str>>>,
+        _attributes: Option<Vec<KeyValue>>,
+    ) -> Meter {
+        Meter::new(Arc::new(self.clone()))
+    }
+}
+
+impl InstrumentProvider for ContextRecordingProvider {
+    fn f64_histogram(
+        &self,
+        _name: Cow<'static, str>,
+        _description: Option<Cow<'static, str>>,
+        _unit: Option<Unit>,
+    ) -> opentelemetry::metrics::Result<HistogramInstrument<f64>> {
+        Ok(HistogramInstrument::new(Arc::new(self.clone())))
+    }
+
+    fn register_callback(
+        &self,
+        _instruments: &[Arc<dyn std::any::Any>],
+        _callbacks: Box<dyn Fn(&dyn opentelemetry::metrics::Observer) + Send + Sync>,
+    ) -> opentelemetry::metrics::Result<Box<dyn opentelemetry::metrics::CallbackRegistration>> {
+        Err(MetricsError::Other("callbacks are not supported".into()))
+    }
+}
+
+impl SyncHistogram<f64> for ContextRecordingProvider {
+    fn record(&self, _value: f64, _attributes: &[KeyValue]) {
+        let span_context = Context::current().span().span_context().clone();
+        self.0.lock().unwrap().push(span_context);
+    }
+}
+
 fn init_subscriber<T>(
     expected_metric_name: String,
     expected_instrument_kind: InstrumentKind,
@@ -1,5 +1,5 @@
 use futures_util::future::BoxFuture;
-use opentelemetry::trace::TracerProvider as _;
+use opentelemetry::trace::{SpanId, TracerProvider as _};
 use opentelemetry_sdk::{
     export::trace::{ExportResult, SpanData, SpanExporter},
     trace::{Tracer, TracerProvider},
@@ -7,7 +7,7 @@ use opentelemetry_sdk::{
 use std::sync::{Arc, Mutex};
 use tracing::level_filters::LevelFilter;
 use tracing::Subscriber;
-use tracing_opentelemetry::layer;
+use tracing_opentelemetry::{layer, FilteredParentMode};
 use tracing_subscriber::prelude::*;
 
 #[derive(Clone, Default, Debug)]
@@ -108,3 +108,94 @@ fn explicit_parents_of_events() {
         assert_eq!(&expected_child_events, &actual_events[..]);
     }
 }
+
+#[test]
+fn filtered_parents() {
+    let parent_of_grandchild = |mode: FilteredParentMode| {
+        let exporter = TestExporter::default();
+        let provider = TracerProvider::builder()
+            .with_simple_exporter(exporter.clone())
+            .build();
+        let subscriber = tracing_subscriber::registry()
+            .with(
+                layer()
+                    .with_tracer(provider.tracer("test"))
+                    .with_filtered_parent_mode(mode)
+                    .with_filter(LevelFilter::DEBUG),
+            )
+            .with(tracing_subscriber::fmt::layer().with_filter(LevelFilter::TRACE));
+
+        tracing::subscriber::with_default(subscriber, || {
+            let root = tracing::debug_span!("root");
+            let middle = tracing::trace_span!(parent: &root, "middle");
+            tracing::debug_span!(parent: &middle, "grandchild");
+        });
+
+        drop(provider); // flush all spans
+        let spans = exporter.0.lock().unwrap();
+        let span = |name: &str| spans.iter().find(|s| s.name == name).unwrap().clone();
+        let (root, grandchild) = (span("root"), span("grandchild"));
+        (root.span_context, grandchild)
+    };
+
+    let (root, grandchild) = parent_of_grandchild(FilteredParentMode::AsRoot);
+    assert_ne!(grandchild.span_context.trace_id(), root.trace_id());
+    assert_eq!(grandchild.parent_span_id, SpanId::INVALID);
+
+    let (root, grandchild) = parent_of_grandchild(FilteredParentMode::NearestVisibleAncestor);
+    assert_eq!(grandchild.span_context.trace_id(), root.trace_id());
+    assert_eq!(grandchild.parent_span_id, root.span_id());
+}
+
+#[test]
+fn runtime_attributes_on_root_spans() {
+    let exporter = TestExporter::default();
+    let provider = TracerProvider::builder()
+        .with_simple_exporter(exporter.clone())
+        .build();
+    let subscriber = tracing_subscriber::registry().with(
+        layer()
+            .with_tracer(provider.tracer("test"))
+            .with_runtime_attributes(true)
+            .with_runtime_version("1.75.0"),
+    );
+    let described = tracing_subscriber::registry().with(
+        layer()
+            .with_tracer(provider.tracer("test"))
+            .with_runtime_attributes(true)
+            .with_runtime_description("x86_64-unknown-linux-gnu"),
+    );
+
+    tracing::subscriber::with_default(subscriber, || {
+        tracing::debug_span!("root").in_scope(|| {
+            tracing::debug_span!("child");
+        });
+    });
+    tracing::subscriber::with_default(described, || {
+        tracing::debug_span!("described");
+    });
+
+    drop(provider); // flush all spans
+    let spans = exporter.0.lock().unwrap();
+    let runtime_attributes = |name: &str| {
+        let span = spans.iter().find(|s| s.name == name).unwrap();
+        span.attributes
+            .iter()
+            .filter(|kv| kv.key.as_str().starts_with("process.runtime."))
+            .map(|kv| (kv.key.as_str().to_owned(), kv.value.as_str().into_owned()))
+            .collect::<Vec<_>>()
+    };
+
+    let root = runtime_attributes("root");
+    assert_eq!(root.len(), 3);
+    assert!(root.contains(&("process.runtime.name".to_owned(), "rust".to_owned())));
+    assert!(root.contains(&("process.runtime.version".to_owned(), "1.75.0".to_owned())));
+    assert!(root.iter().all(|(_, value)| !value.is_empty()));
+    assert!(runtime_attributes("child").is_empty());
+
+    let described = runtime_attributes("described");
+    assert!(described.contains(&(
+        "process.runtime.description".to_owned(),
+        "x86_64-unknown-linux-gnu".to_owned()
+    )));
+}
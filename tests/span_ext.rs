@@ -0,0 +1,976 @@
+use futures_util::future::BoxFuture;
+use opentelemetry::{
+    global,
+    propagation::TextMapPropagator,
+    trace::{
+        SpanBuilder, SpanContext, SpanId, SpanKind, Status, TraceContextExt, TraceFlags, TraceId,
+        TraceState, TracerProvider as _,
+    },
+    Array, Context, KeyValue, Value,
+};
+use opentelemetry_sdk::{
+    export::trace::{ExportResult, SpanData, SpanExporter},
+    propagation::TraceContextPropagator,
+    trace::{Tracer, TracerProvider},
+};
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
+use tracing::Subscriber;
+use tracing_opentelemetry::{
+    layer, AttrType, AttributeTarget, IdFormat, LateReparentPolicy, OpenTelemetrySpanExt, Outcome,
+    ScopedTracer,
+};
+use tracing_subscriber::prelude::*;
+
+#[derive(Clone, Default, Debug)]
+struct TestExporter(Arc<Mutex<Vec<SpanData>>>);
+
+impl SpanExporter for TestExporter {
+    fn export(&mut self, mut batch: Vec<SpanData>) -> BoxFuture<'static, ExportResult> {
+        let spans = self.0.clone();
+        Box::pin(async move {
+            if let Ok(mut inner) = spans.lock() {
+                inner.append(&mut batch);
+            }
+            Ok(())
+        })
+    }
+}
+
+fn test_tracer() -> (Tracer, TracerProvider, TestExporter, impl Subscriber) {
+    let exporter = TestExporter::default();
+    let provider = TracerProvider::builder()
+        .with_simple_exporter(exporter.clone())
+        .build();
+    let tracer = provider.tracer("test");
+    let subscriber = tracing_subscriber::registry().with(layer().with_tracer(tracer.clone()));
+
+    (tracer, provider, exporter, subscriber)
+}
+
+fn export_on_error_only_tracer() -> (TracerProvider, TestExporter, impl Subscriber) {
+    let exporter = TestExporter::default();
+    let provider = TracerProvider::builder()
+        .with_simple_exporter(exporter.clone())
+        .build();
+    let subscriber = tracing_subscriber::registry().with(
+        layer()
+            .with_tracer(provider.tracer("test"))
+            .with_export_on_error_only(true),
+    );
+
+    (provider, exporter, subscriber)
+}
+
+#[test]
+fn successful_traces_are_dropped() {
+    let (provider, exporter, subscriber) = export_on_error_only_tracer();
+
+    tracing::subscriber::with_default(subscriber, || {
+        let _root = tracing::debug_span!("root").entered();
+        let _child = tracing::debug_span!("child").entered();
+        tracing::info!("all good");
+    });
+
+    drop(provider); // flush all spans
+    let spans = exporter.0.lock().unwrap();
+    assert!(spans.is_empty());
+}
+
+#[test]
+fn error_traces_are_exported() {
+    let (provider, exporter, subscriber) = export_on_error_only_tracer();
+
+    tracing::subscriber::with_default(subscriber, || {
+        {
+            let _root = tracing::debug_span!("ok_root").entered();
+            let _child = tracing::debug_span!("ok_child").entered();
+        }
+
+        let _root = tracing::debug_span!("root").entered();
+        {
+            let _child = tracing::debug_span!("child").entered();
+            tracing::error!("something failed");
+        }
+        let _sibling = tracing::debug_span!("sibling").entered();
+    });
+
+    drop(provider); // flush all spans
+    let spans = exporter.0.lock().unwrap();
+    let mut names = spans.iter().map(|s| s.name.as_ref()).collect::<Vec<_>>();
+    names.sort_unstable();
+    assert_eq!(names, vec!["child", "root", "sibling"]);
+}
+
+#[test]
+fn scope_version_is_overridden() {
+    let exporter = TestExporter::default();
+    let provider = TracerProvider::builder()
+        .with_simple_exporter(exporter.clone())
+        .build();
+    let tracer = provider.versioned_tracer("test", Some("1.0.0"), None::<&str>, None);
+    let tracer = ScopedTracer::new(&tracer, "2.0.0");
+    let subscriber = tracing_subscriber::registry().with(layer().with_tracer(tracer));
+
+    tracing::subscriber::with_default(subscriber, || {
+        tracing::debug_span!("request");
+    });
+
+    drop(provider); // flush all spans
+    let spans = exporter.0.lock().unwrap();
+    assert_eq!(spans.len(), 1);
+    assert_eq!(spans[0].instrumentation_lib.name, "test");
+    assert_eq!(
+        spans[0].instrumentation_lib.version.as_deref(),
+        Some("2.0.0")
+    );
+}
+
+#[test]
+fn set_parent_span_shares_trace_id() {
+    let (_tracer, provider, exporter, subscriber) = test_tracer();
+
+    tracing::subscriber::with_default(subscriber, || {
+        let request = tracing::debug_span!("request");
+        let job = tracing::debug_span!(parent: None, "job");
+        job.set_parent_span(&request);
+
+        let orphan = tracing::debug_span!(parent: None, "orphan");
+        orphan.set_parent_span(&tracing::Span::none());
+    });
+
+    drop(provider); // flush all spans
+    let spans = exporter.0.lock().unwrap();
+    let request = spans.iter().find(|s| s.name == "request").unwrap();
+    let job = spans.iter().find(|s| s.name == "job").unwrap();
+    let orphan = spans.iter().find(|s| s.name == "orphan").unwrap();
+    assert_eq!(job.span_context.trace_id(), request.span_context.trace_id());
+    assert_eq!(job.parent_span_id, request.span_context.span_id());
+    assert_ne!(
+        orphan.span_context.trace_id(),
+        request.span_context.trace_id()
+    );
+}
+
+#[test]
+fn set_status_description_refines_error() {
+    let (_tracer, provider, exporter, subscriber) = test_tracer();
+
+    tracing::subscriber::with_default(subscriber, || {
+        let span = tracing::debug_span!("failed", otel.status_message = "initial");
+        span.set_status_description("refined");
+
+        let untouched = tracing::debug_span!("untouched");
+        untouched.set_status_description("ignored");
+    });
+
+    drop(provider); // flush all spans
+    let spans = exporter.0.lock().unwrap();
+    let failed = spans.iter().find(|s| s.name == "failed").unwrap();
+    assert_eq!(failed.status, Status::error("refined"));
+    let untouched = spans.iter().find(|s| s.name == "untouched").unwrap();
+    assert_eq!(untouched.status, Status::Unset);
+}
+
+#[test]
+fn link_to_all_active_links_live_spans() {
+    let (_tracer, provider, exporter, subscriber) = test_tracer();
+
+    tracing::subscriber::with_default(subscriber, || {
+        let first = tracing::debug_span!("first");
+        let second = tracing::debug_span!("second");
+
+        let batch = tracing::debug_span!("batch");
+        batch.link_to_all_active(&[first.id().unwrap(), second.id().unwrap()]);
+    });
+
+    drop(provider); // flush all spans
+    let spans = exporter.0.lock().unwrap();
+    let span_id = |name: &str| {
+        spans
+            .iter()
+            .find(|s| s.name == name)
+            .unwrap()
+            .span_context
+            .span_id()
+    };
+    let batch = spans.iter().find(|s| s.name == "batch").unwrap();
+    let linked = batch
+        .links
+        .iter()
+        .map(|link| link.span_context.span_id())
+        .collect::<Vec<_>>();
+    assert_eq!(linked, vec![span_id("first"), span_id("second")]);
+}
+
+#[test]
+fn inject_context_round_trips_trace_id() {
+    let (_tracer, provider, exporter, subscriber) = test_tracer();
+    let propagator = TraceContextPropagator::new();
+    let mut carrier = HashMap::new();
+
+    tracing::subscriber::with_default(subscriber, || {
+        let span = tracing::debug_span!("request");
+        span.inject_context(&propagator, &mut carrier);
+    });
+
+    drop(provider); // flush all spans
+    let spans = exporter.0.lock().unwrap();
+    let span = spans.iter().find(|s| s.name == "request").unwrap();
+    let extracted = propagator.extract(&carrier);
+    assert_eq!(
+        extracted.span().span_context().trace_id(),
+        span.span_context.trace_id()
+    );
+    assert_eq!(
+        extracted.span().span_context().span_id(),
+        span.span_context.span_id()
+    );
+}
+
+#[test]
+fn merge_span_builder_keeps_existing_attributes() {
+    let (_tracer, provider, exporter, subscriber) = test_tracer();
+
+    tracing::subscriber::with_default(subscriber, || {
+        let span = tracing::debug_span!("request", route = "/users");
+        span.merge_span_builder(SpanBuilder::from_name("external").with_attributes(vec![
+            KeyValue::new("route", "/ignored"),
+            KeyValue::new("http.request.method", "GET"),
+        ]));
+    });
+
+    drop(provider); // flush all spans
+    let spans = exporter.0.lock().unwrap();
+    let span = spans.iter().find(|s| s.name == "request").unwrap();
+    let attribute = |key: &str| {
+        span.attributes
+            .iter()
+            .find(|kv| kv.key.as_str() == key)
+            .map(|kv| kv.value.clone())
+    };
+    assert_eq!(attribute("route"), Some(Value::from("/users")));
+    assert_eq!(attribute("http.request.method"), Some(Value::from("GET")));
+    assert_eq!(
+        span.attributes
+            .iter()
+            .filter(|kv| kv.key.as_str() == "route")
+            .count(),
+        1
+    );
+}
+
+#[test]
+fn set_http_route_updates_name() {
+    let (_tracer, provider, exporter, subscriber) = test_tracer();
+
+    tracing::subscriber::with_default(subscriber, || {
+        let renamed = tracing::debug_span!("request", http.request.method = "GET");
+        renamed.set_http_route("/users/{id}", true);
+
+        let kept = tracing::debug_span!("kept");
+        kept.set_http_route("/health", false);
+    });
+
+    drop(provider); // flush all spans
+    let spans = exporter.0.lock().unwrap();
+    let renamed = spans.iter().find(|s| s.name == "GET /users/{id}").unwrap();
+    assert!(renamed
+        .attributes
+        .iter()
+        .any(|kv| kv.key.as_str() == "http.route" && kv.value == Value::from("/users/{id}")));
+    let kept = spans.iter().find(|s| s.name == "kept").unwrap();
+    assert!(kept
+        .attributes
+        .iter()
+        .any(|kv| kv.key.as_str() == "http.route" && kv.value == Value::from("/health")));
+}
+
+#[test]
+fn set_http_route_replaces_route() {
+    let (_tracer, provider, exporter, subscriber) = test_tracer();
+
+    tracing::subscriber::with_default(subscriber, || {
+        let span = tracing::debug_span!("request");
+        span.set_http_route("/users", false);
+        span.set_http_route("/users/{id}", false);
+    });
+
+    drop(provider); // flush all spans
+    let spans = exporter.0.lock().unwrap();
+    let routes: Vec<_> = spans[0]
+        .attributes
+        .iter()
+        .filter(|kv| kv.key.as_str() == "http.route")
+        .collect();
+    assert_eq!(routes.len(), 1);
+    assert_eq!(routes[0].value, Value::from("/users/{id}"));
+}
+
+#[cfg(feature = "sha2")]
+#[test]
+fn set_enduser_id_hashes_id() {
+    let (_tracer, provider, exporter, subscriber) = test_tracer();
+
+    tracing::subscriber::with_default(subscriber, || {
+        tracing::debug_span!("plain").set_enduser_id("alice", false);
+        tracing::debug_span!("hashed").set_enduser_id("alice", true);
+        tracing::debug_span!("hashed_again").set_enduser_id("alice", true);
+    });
+
+    drop(provider); // flush all spans
+    let spans = exporter.0.lock().unwrap();
+    let enduser_id = |name: &str| {
+        spans
+            .iter()
+            .find(|s| s.name == name)
+            .unwrap()
+            .attributes
+            .iter()
+            .find(|kv| kv.key.as_str() == "enduser.id")
+            .map(|kv| kv.value.as_str().into_owned())
+            .unwrap()
+    };
+    assert_eq!(enduser_id("plain"), "alice");
+    assert_ne!(enduser_id("hashed"), "alice");
+    assert_eq!(
+        enduser_id("hashed"),
+        "2bd806c97f0e00af1a1fc3328fa763a9269723c8db8fac4f93af71db186d6e90"
+    );
+    assert_eq!(enduser_id("hashed"), enduser_id("hashed_again"));
+}
+
+#[test]
+fn update_name_after_enter() {
+    let (_tracer, provider, exporter, subscriber) = test_tracer();
+
+    tracing::subscriber::with_default(subscriber, || {
+        let updated = tracing::debug_span!("request");
+        let _enter = updated.enter();
+        tracing::debug!("started");
+        updated.update_name("GET /users/{id}");
+
+        let recorded = tracing::debug_span!("request", otel.name = tracing::field::Empty);
+        let _enter = recorded.enter();
+        recorded.record("otel.name", "GET /health");
+    });
+
+    drop(provider); // flush all spans
+    let spans = exporter.0.lock().unwrap();
+    let mut names = spans.iter().map(|s| s.name.as_ref()).collect::<Vec<_>>();
+    names.sort_unstable();
+    assert_eq!(names, vec!["GET /health", "GET /users/{id}"]);
+}
+
+#[test]
+fn get_attribute_reads_recorded_values() {
+    let (_tracer, _provider, _exporter, subscriber) = test_tracer();
+
+    tracing::subscriber::with_default(subscriber, || {
+        let span = tracing::debug_span!("request", http.request.method = "GET");
+        span.set_attribute("http.route", "/users");
+        span.set_attribute("http.route", "/users/{id}");
+
+        assert_eq!(
+            span.get_attribute("http.request.method"),
+            Some(Value::from("GET"))
+        );
+        assert_eq!(
+            span.get_attribute("http.route"),
+            Some(Value::from("/users/{id}"))
+        );
+        assert_eq!(span.get_attribute("missing"), None);
+    });
+}
+
+#[test]
+fn set_attributes_records_all_attributes() {
+    let (_tracer, provider, exporter, subscriber) = test_tracer();
+
+    tracing::subscriber::with_default(subscriber, || {
+        let span = tracing::debug_span!("request");
+        span.set_attributes(vec![KeyValue::new("a", 1), KeyValue::new("b", "two")]);
+        span.set_attributes(Vec::new());
+    });
+
+    drop(provider); // flush all spans
+    let spans = exporter.0.lock().unwrap();
+    let attributes = &spans[0].attributes;
+    assert!(attributes
+        .iter()
+        .any(|kv| kv.key.as_str() == "a" && kv.value == Value::I64(1)));
+    assert!(attributes
+        .iter()
+        .any(|kv| kv.key.as_str() == "b" && kv.value == Value::from("two")));
+}
+
+#[test]
+fn set_typed_attribute_parses_values() {
+    let (_tracer, provider, exporter, subscriber) = test_tracer();
+
+    tracing::subscriber::with_default(subscriber, || {
+        let span = tracing::debug_span!("typed");
+        span.set_typed_attribute("bool", "true", AttrType::Bool);
+        span.set_typed_attribute("i64", "-42", AttrType::I64);
+        span.set_typed_attribute("f64", "1.5", AttrType::F64);
+        span.set_typed_attribute("string", "text", AttrType::String);
+        span.set_typed_attribute("array", "1, 2, 3", AttrType::I64Array);
+        span.set_typed_attribute("invalid", "ten", AttrType::I64);
+    });
+
+    drop(provider); // flush all spans
+    let spans = exporter.0.lock().unwrap();
+    let attribute = |key: &str| {
+        spans[0]
+            .attributes
+            .iter()
+            .find(|kv| kv.key.as_str() == key)
+            .map(|kv| kv.value.clone())
+    };
+    assert_eq!(attribute("bool"), Some(Value::Bool(true)));
+    assert_eq!(attribute("i64"), Some(Value::I64(-42)));
+    assert_eq!(attribute("f64"), Some(Value::F64(1.5)));
+    assert_eq!(attribute("string"), Some(Value::from("text")));
+    assert_eq!(
+        attribute("array"),
+        Some(Value::Array(Array::I64(vec![1, 2, 3])))
+    );
+    assert_eq!(attribute("invalid"), Some(Value::from("ten")));
+    assert_eq!(attribute("invalid.parse_error"), Some(Value::Bool(true)));
+    assert_eq!(attribute("i64.parse_error"), None);
+}
+
+#[test]
+fn trace_id_and_span_id_match_exported_span() {
+    let (_tracer, provider, exporter, subscriber) = test_tracer();
+
+    let mut ids = None;
+    tracing::subscriber::with_default(subscriber, || {
+        let root = tracing::debug_span!("root");
+        let child = root.in_scope(|| tracing::debug_span!("child"));
+        ids = Some((child.trace_id(), child.span_id()));
+
+        assert_eq!(tracing::Span::none().trace_id(), None);
+        assert_eq!(tracing::Span::none().span_id(), None);
+    });
+
+    drop(provider); // flush all spans
+    let spans = exporter.0.lock().unwrap();
+    let child = spans.iter().find(|s| s.name == "child").unwrap();
+    assert_eq!(
+        ids,
+        Some((
+            Some(child.span_context.trace_id()),
+            Some(child.span_context.span_id())
+        ))
+    );
+}
+
+#[test]
+fn span_id_attribute_matches_exported_ids() {
+    let exporter = TestExporter::default();
+    let provider = TracerProvider::builder()
+        .with_simple_exporter(exporter.clone())
+        .build();
+    let subscriber = tracing_subscriber::registry().with(
+        layer()
+            .with_tracer(provider.tracer("test"))
+            .with_attribute_from_span_id(true)
+            .with_id_format(IdFormat::Decimal),
+    );
+
+    tracing::subscriber::with_default(subscriber, || {
+        let root = tracing::debug_span!("root");
+        root.in_scope(|| tracing::debug_span!("child"));
+    });
+
+    drop(provider); // flush all spans
+    let spans = exporter.0.lock().unwrap();
+    assert_eq!(spans.len(), 2);
+    for span in spans.iter() {
+        let attribute = |key: &str| {
+            span.attributes
+                .iter()
+                .find(|kv| kv.key.as_str() == key)
+                .map(|kv| kv.value.clone())
+        };
+        let context = &span.span_context;
+        assert_eq!(
+            attribute("otel.span_id"),
+            Some(IdFormat::Decimal.span_id(context.span_id()).into())
+        );
+        assert_eq!(
+            attribute("otel.trace_id"),
+            Some(IdFormat::Decimal.trace_id(context.trace_id()).into())
+        );
+        let parent_span_id =
+            (span.name == "child").then(|| IdFormat::Decimal.span_id(span.parent_span_id).into());
+        assert_eq!(attribute("otel.parent_span_id"), parent_span_id);
+    }
+}
+
+#[test]
+fn context_without_eager_span_id() {
+    let exporter = TestExporter::default();
+    let provider = TracerProvider::builder()
+        .with_simple_exporter(exporter.clone())
+        .build();
+    let tracer = provider.tracer("test");
+    let subscriber =
+        tracing_subscriber::registry().with(layer().with_tracer(tracer).with_eager_span_id(false));
+
+    let mut trace_id = None;
+    tracing::subscriber::with_default(subscriber, || {
+        let span = tracing::debug_span!("lazy");
+        let cx = span.context();
+        assert!(!cx.span().span_context().is_valid());
+        trace_id = Some(cx.span().span_context().trace_id());
+    });
+
+    drop(provider); // flush all spans
+    let spans = exporter.0.lock().unwrap();
+    let lazy = spans.iter().find(|s| s.name == "lazy").unwrap();
+    assert!(lazy.span_context.is_valid());
+    assert_eq!(trace_id, Some(lazy.span_context.trace_id()));
+}
+
+#[test]
+fn with_otel_data_modifies_builder() {
+    let (_tracer, provider, exporter, subscriber) = test_tracer();
+
+    tracing::subscriber::with_default(subscriber, || {
+        let span = tracing::debug_span!("enriched");
+        span.with_otel_data(|data| {
+            data.builder.name = "renamed".into();
+        });
+
+        let mut called = false;
+        tracing::Span::none().with_otel_data(|_| called = true);
+        assert!(!called);
+    });
+
+    drop(provider); // flush all spans
+    let spans = exporter.0.lock().unwrap();
+    assert_eq!(spans.len(), 1);
+    assert_eq!(spans[0].name, "renamed");
+}
+
+#[test]
+fn set_attribute_detailed_reports_target() {
+    let (_tracer, provider, exporter, subscriber) = test_tracer();
+
+    tracing::subscriber::with_default(subscriber, || {
+        let span = tracing::debug_span!("tracked");
+        assert_eq!(
+            span.set_attribute_detailed("retries", 3),
+            AttributeTarget::Builder
+        );
+        assert_eq!(
+            tracing::Span::none().set_attribute_detailed("retries", 3),
+            AttributeTarget::Dropped
+        );
+    });
+
+    tracing::subscriber::with_default(tracing_subscriber::registry(), || {
+        let span = tracing::debug_span!("untracked");
+        assert_eq!(
+            span.set_attribute_detailed("retries", 3),
+            AttributeTarget::Dropped
+        );
+    });
+
+    drop(provider); // flush all spans
+    let spans = exporter.0.lock().unwrap();
+    assert_eq!(spans.len(), 1);
+    assert!(spans[0]
+        .attributes
+        .iter()
+        .any(|kv| kv.key.as_str() == "retries" && kv.value == Value::I64(3)));
+}
+
+#[test]
+fn set_rpc_records_attributes_and_kind() {
+    let (_tracer, provider, exporter, subscriber) = test_tracer();
+
+    tracing::subscriber::with_default(subscriber, || {
+        let span = tracing::debug_span!("grpc.client");
+        span.set_rpc("grpc", "myservice.EchoService", "Echo");
+    });
+
+    drop(provider); // flush all spans
+    let spans = exporter.0.lock().unwrap();
+    let span = &spans[0];
+    assert_eq!(span.span_kind, SpanKind::Client);
+    let attribute = |key: &str| {
+        span.attributes
+            .iter()
+            .find(|kv| kv.key.as_str() == key)
+            .map(|kv| kv.value.as_str().into_owned())
+    };
+    assert_eq!(attribute("rpc.system").as_deref(), Some("grpc"));
+    assert_eq!(
+        attribute("rpc.service").as_deref(),
+        Some("myservice.EchoService")
+    );
+    assert_eq!(attribute("rpc.method").as_deref(), Some("Echo"));
+}
+
+#[test]
+fn add_link_before_enter_and_on_filtered_spans() {
+    let exporter = TestExporter::default();
+    let provider = TracerProvider::builder()
+        .with_simple_exporter(exporter.clone())
+        .build();
+    let subscriber = tracing_subscriber::registry().with(
+        layer().with_tracer(provider.tracer("test")).with_filter(
+            tracing_subscriber::filter::filter_fn(|metadata| metadata.name() != "filtered"),
+        ),
+    );
+
+    tracing::subscriber::with_default(subscriber, || {
+        let producer = tracing::debug_span!("producer");
+        let producer_cx = producer.context().span().span_context().clone();
+
+        let consumer = tracing::debug_span!("consumer");
+        consumer.add_link(producer_cx.clone());
+        consumer.in_scope(|| {});
+
+        let filtered = tracing::debug_span!("filtered");
+        filtered.add_link(producer_cx);
+        filtered.in_scope(|| {});
+    });
+
+    drop(provider); // flush all spans
+    let spans = exporter.0.lock().unwrap();
+    assert_eq!(spans.len(), 2);
+    let span = |name: &str| spans.iter().find(|s| s.name == name).unwrap();
+    let consumer = span("consumer");
+    assert_eq!(consumer.links.len(), 1);
+    assert_eq!(
+        consumer.links[0].span_context.span_id(),
+        span("producer").span_context.span_id()
+    );
+    assert!(span("producer").links.is_empty());
+}
+
+#[test]
+fn set_outcome_records_attribute_and_status() {
+    let (_tracer, provider, exporter, subscriber) = test_tracer();
+
+    tracing::subscriber::with_default(subscriber, || {
+        tracing::debug_span!("success").set_outcome(Outcome::Success);
+        tracing::debug_span!("failure").set_outcome(Outcome::Failure);
+        tracing::debug_span!("partial").set_outcome(Outcome::Partial);
+
+        let described = tracing::debug_span!("described", otel.status_message = "timed out");
+        described.set_outcome(Outcome::Failure);
+    });
+
+    drop(provider); // flush all spans
+    let spans = exporter.0.lock().unwrap();
+    let outcome = |name: &str| {
+        let span = spans.iter().find(|s| s.name == name).unwrap();
+        let outcome = span
+            .attributes
+            .iter()
+            .find(|kv| kv.key.as_str() == "operation.outcome")
+            .map(|kv| kv.value.as_str().into_owned());
+        (outcome, span.status.clone())
+    };
+
+    assert_eq!(
+        outcome("success"),
+        (Some("success".to_owned()), Status::Unset)
+    );
+    assert_eq!(
+        outcome("failure"),
+        (Some("failure".to_owned()), Status::error(""))
+    );
+    assert_eq!(
+        outcome("partial"),
+        (Some("partial".to_owned()), Status::Unset)
+    );
+    assert_eq!(
+        outcome("described"),
+        (Some("failure".to_owned()), Status::error("timed out"))
+    );
+}
+
+#[test]
+fn set_start_time_overrides_start() {
+    let (_tracer, provider, exporter, subscriber) = test_tracer();
+    let enqueued_at = SystemTime::now() - Duration::from_secs(60);
+
+    tracing::subscriber::with_default(subscriber, || {
+        let span = tracing::debug_span!("job");
+        span.in_scope(|| {});
+        span.set_start_time(enqueued_at);
+
+        tracing::debug_span!("untouched");
+    });
+
+    drop(provider); // flush all spans
+    let spans = exporter.0.lock().unwrap();
+    let span = |name: &str| spans.iter().find(|s| s.name == name).unwrap();
+    assert_eq!(span("job").start_time, enqueued_at);
+    assert!(span("untouched").start_time > enqueued_at);
+}
+
+#[test]
+fn root_context_returns_root_of_tree() {
+    let (_tracer, provider, _exporter, subscriber) = test_tracer();
+
+    tracing::subscriber::with_default(subscriber, || {
+        let root = tracing::debug_span!("root");
+        let middle = tracing::debug_span!(parent: &root, "middle");
+        let leaf = tracing::debug_span!(parent: &middle, "leaf");
+
+        let root_span_context = root.context().span().span_context().clone();
+        assert!(root_span_context.is_valid());
+        assert_eq!(
+            leaf.root_context().span().span_context(),
+            &root_span_context
+        );
+        assert_eq!(
+            root.root_context().span().span_context(),
+            &root_span_context
+        );
+        assert!(!tracing::Span::none()
+            .root_context()
+            .span()
+            .span_context()
+            .is_valid());
+    });
+
+    drop(provider);
+}
+
+#[test]
+fn set_gen_ai_records_attributes() {
+    let (_tracer, provider, exporter, subscriber) = test_tracer();
+
+    tracing::subscriber::with_default(subscriber, || {
+        let span = tracing::debug_span!("chat");
+        span.set_gen_ai("openai", "gpt-4", 120, u64::MAX);
+    });
+
+    drop(provider); // flush all spans
+    let spans = exporter.0.lock().unwrap();
+    let attribute = |key: &str| {
+        spans[0]
+            .attributes
+            .iter()
+            .find(|kv| kv.key.as_str() == key)
+            .map(|kv| kv.value.clone())
+    };
+    assert_eq!(attribute("gen_ai.system"), Some(Value::from("openai")));
+    assert_eq!(
+        attribute("gen_ai.request.model"),
+        Some(Value::from("gpt-4"))
+    );
+    assert_eq!(
+        attribute("gen_ai.usage.input_tokens"),
+        Some(Value::I64(120))
+    );
+    assert_eq!(
+        attribute("gen_ai.usage.output_tokens"),
+        Some(Value::I64(i64::MAX))
+    );
+}
+
+#[test]
+fn set_attribute_overrides_and_remove_attribute() {
+    let (_tracer, provider, exporter, subscriber) = test_tracer();
+
+    tracing::subscriber::with_default(subscriber, || {
+        let span = tracing::debug_span!("request", user.email = "alice@example.com");
+        span.set_attribute("http.route", "/users/:id");
+        span.set_attribute("http.route", "/users/{id}");
+        span.set_attributes([KeyValue::new("retries", 1), KeyValue::new("retries", 2)]);
+        span.remove_attribute("user.email");
+    });
+
+    drop(provider); // flush all spans
+    let spans = exporter.0.lock().unwrap();
+    let values = |key: &str| {
+        spans[0]
+            .attributes
+            .iter()
+            .filter(|kv| kv.key.as_str() == key)
+            .map(|kv| kv.value.clone())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(values("http.route"), vec![Value::from("/users/{id}")]);
+    assert_eq!(values("retries"), vec![Value::I64(2)]);
+    assert!(values("user.email").is_empty());
+}
+
+#[test]
+fn late_reparent_policy() {
+    let exported_after_unsampled_reparent = |policy: Option<LateReparentPolicy>| {
+        let exporter = TestExporter::default();
+        let provider = TracerProvider::builder()
+            .with_simple_exporter(exporter.clone())
+            .build();
+        let layer = layer().with_tracer(provider.tracer("test"));
+        let layer = match policy {
+            Some(policy) => layer.with_late_reparent_policy(policy),
+            None => layer,
+        };
+        let subscriber = tracing_subscriber::registry().with(layer);
+        let unsampled_parent = Context::new().with_remote_span_context(SpanContext::new(
+            TraceId::from(1),
+            SpanId::from(1),
+            TraceFlags::default(),
+            true,
+            TraceState::default(),
+        ));
+
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::debug_span!("job");
+            // Sampled as a root span
+            assert!(span.context().span().span_context().is_sampled());
+            span.set_parent(unsampled_parent);
+        });
+
+        drop(provider); // flush all spans
+        let spans = exporter.0.lock().unwrap();
+        spans.len()
+    };
+
+    assert_eq!(
+        exported_after_unsampled_reparent(Some(LateReparentPolicy::KeepDecision)),
+        1
+    );
+    assert_eq!(
+        exported_after_unsampled_reparent(Some(LateReparentPolicy::Resample)),
+        0
+    );
+    // Resampling is the default
+    assert_eq!(exported_after_unsampled_reparent(None), 0);
+}
+
+#[test]
+fn set_parent_span_resamples() {
+    let (_tracer, provider, exporter, subscriber) = test_tracer();
+    let unsampled_parent = Context::new().with_remote_span_context(SpanContext::new(
+        TraceId::from(1),
+        SpanId::from(1),
+        TraceFlags::default(),
+        true,
+        TraceState::default(),
+    ));
+
+    tracing::subscriber::with_default(subscriber, || {
+        let request = tracing::debug_span!("request");
+        request.set_parent(unsampled_parent);
+
+        let job = tracing::debug_span!(parent: None, "job");
+        // Sampled as a root span
+        assert!(job.context().span().span_context().is_sampled());
+        job.set_parent_span(&request);
+    });
+
+    drop(provider); // flush all spans
+    let spans = exporter.0.lock().unwrap();
+    assert!(spans.is_empty());
+}
+
+#[test]
+fn add_event_if_sampled() {
+    let (_tracer, provider, exporter, subscriber) = test_tracer();
+    let remote_parent = |trace_flags| {
+        Context::new().with_remote_span_context(SpanContext::new(
+            TraceId::from(1),
+            SpanId::from(1),
+            trace_flags,
+            true,
+            TraceState::default(),
+        ))
+    };
+
+    tracing::subscriber::with_default(subscriber, || {
+        let sampled = tracing::debug_span!("sampled");
+        sampled.add_event_if_sampled("event", vec![KeyValue::new("key", "value")]);
+
+        // Not sampled when the event is added, and sampled after resampling
+        let unsampled = tracing::debug_span!("unsampled");
+        unsampled.set_parent(remote_parent(TraceFlags::default()));
+        unsampled.add_event_if_sampled("event", vec![KeyValue::new("key", "value")]);
+        unsampled.set_parent(remote_parent(TraceFlags::SAMPLED));
+    });
+
+    drop(provider); // flush all spans
+    let spans = exporter.0.lock().unwrap();
+    let events = |name: &str| {
+        let span = spans.iter().find(|s| s.name == name).unwrap();
+        span.events
+            .iter()
+            .map(|event| (event.name.clone(), event.attributes.clone()))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        events("sampled"),
+        vec![("event".into(), vec![KeyValue::new("key", "value")])]
+    );
+    assert!(events("unsampled").is_empty());
+}
+
+#[cfg(debug_assertions)]
+#[test]
+fn unknown_semconv_key_is_reported() {
+    let errors = Arc::new(Mutex::new(Vec::new()));
+    let handler_errors = errors.clone();
+    global::set_error_handler(move |err| handler_errors.lock().unwrap().push(err.to_string()))
+        .unwrap();
+    let (_tracer, provider, exporter, subscriber) = test_tracer();
+
+    tracing::subscriber::with_default(subscriber, || {
+        let span = tracing::debug_span!("request");
+        span.set_semconv_attribute("http.request.method", "GET");
+        span.set_semconv_attribute("htttp.method", "GET");
+    });
+
+    drop(provider); // flush all spans
+                    // Other tests may report errors through the same global handler
+    let errors = errors.lock().unwrap();
+    let reported = |key: &str| errors.iter().filter(|err| err.contains(key)).count();
+    assert_eq!(reported("htttp.method"), 1);
+    assert_eq!(reported("http.request.method"), 0);
+
+    let spans = exporter.0.lock().unwrap();
+    assert!(spans[0]
+        .attributes
+        .iter()
+        .any(|kv| kv.key.as_str() == "htttp.method" && kv.value == Value::from("GET")));
+}
+
+#[test]
+fn set_attribute_static_and_owned_values_match() {
+    let (_tracer, provider, exporter, subscriber) = test_tracer();
+
+    tracing::subscriber::with_default(subscriber, || {
+        let span = tracing::debug_span!("request");
+        span.set_attribute("static", "/users/{id}");
+        span.set_attribute("borrowed", Cow::Borrowed("/users/{id}"));
+        span.set_attribute("owned", String::from("/users/{id}"));
+    });
+
+    drop(provider); // flush all spans
+    let spans = exporter.0.lock().unwrap();
+    let value = |key: &str| {
+        spans[0]
+            .attributes
+            .iter()
+            .find(|kv| kv.key.as_str() == key)
+            .map(|kv| kv.value.clone())
+            .unwrap()
+    };
+    assert_eq!(value("static"), Value::from("/users/{id}"));
+    assert_eq!(value("borrowed"), value("static"));
+    assert_eq!(value("owned"), value("static"));
+}
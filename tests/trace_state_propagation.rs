@@ -1,13 +1,16 @@
 use futures_util::future::BoxFuture;
 use opentelemetry::{
     propagation::{TextMapCompositePropagator, TextMapPropagator},
-    trace::{SpanContext, TraceContextExt, Tracer as _, TracerProvider as _},
+    trace::{
+        SpanContext, SpanId, TraceContextExt, TraceFlags, TraceId, TraceState, Tracer as _,
+        TracerProvider as _,
+    },
     Context,
 };
 use opentelemetry_sdk::{
     export::trace::{ExportResult, SpanData, SpanExporter},
     propagation::{BaggagePropagator, TraceContextPropagator},
-    trace::{Tracer, TracerProvider},
+    trace::{config, Sampler, Tracer, TracerProvider},
 };
 use std::collections::{HashMap, HashSet};
 use std::sync::{Arc, Mutex};
@@ -63,6 +66,29 @@ fn trace_root_with_children() {
     assert_shared_attrs_eq(&spans[0].span_context, &spans[1].span_context);
 }
 
+#[test]
+fn pinned_root_trace_id() {
+    let (_tracer, provider, exporter, subscriber) = test_tracer();
+    let pinned = "0af7651916cd43dd8448eb211c80319c";
+
+    tracing::subscriber::with_default(subscriber, || {
+        let root = tracing::debug_span!("root", otel.trace_id = pinned);
+        root.in_scope(|| {
+            tracing::debug_span!("child", otel.trace_id = "4bf92f3577b34da6a3ce929d0e0e4736")
+        });
+    });
+
+    drop(provider); // flush all spans
+    let spans = exporter.0.lock().unwrap();
+    assert_eq!(spans.len(), 2);
+    for span in spans.iter() {
+        assert_eq!(
+            span.span_context.trace_id(),
+            TraceId::from_hex(pinned).unwrap()
+        );
+    }
+}
+
 #[test]
 fn propagate_invalid_context() {
     let (_tracer, provider, exporter, subscriber) = test_tracer();
@@ -81,6 +107,45 @@ fn propagate_invalid_context() {
     assert_shared_attrs_eq(&spans[0].span_context, &spans[1].span_context);
 }
 
+#[test]
+fn validate_invalid_context() {
+    let provider = TracerProvider::builder().build();
+    let invalid = Arc::new(Mutex::new(Vec::new()));
+    let subscriber = tracing_subscriber::registry().with(
+        layer()
+            .with_tracer(provider.tracer("test"))
+            .with_trace_context_validation({
+                let invalid = invalid.clone();
+                move |cx| invalid.lock().unwrap().push(cx.clone())
+            }),
+    );
+    let remote_cx = |trace_id| {
+        Context::new().with_remote_span_context(SpanContext::new(
+            trace_id,
+            SpanId::from(1),
+            TraceFlags::SAMPLED,
+            true,
+            TraceState::default(),
+        ))
+    };
+
+    tracing::subscriber::with_default(subscriber, || {
+        {
+            let _attached = remote_cx(TraceId::INVALID).attach();
+            tracing::debug_span!("root").in_scope(|| tracing::debug_span!("child"));
+        }
+        tracing::debug_span!("reparented").set_parent(remote_cx(TraceId::INVALID));
+
+        let _attached = remote_cx(TraceId::from(1)).attach();
+        tracing::debug_span!("valid");
+        tracing::debug_span!("valid_reparented").set_parent(remote_cx(TraceId::from(1)));
+    });
+
+    let invalid = invalid.lock().unwrap();
+    assert_eq!(invalid.len(), 2);
+    assert!(invalid.iter().all(|cx| cx.span_id() == SpanId::from(1)));
+}
+
 #[test]
 fn inject_context_into_outgoing_requests() {
     let (_tracer, _provider, _exporter, subscriber) = test_tracer();
@@ -187,3 +252,54 @@ impl SpanExporter for TestExporter {
         })
     }
 }
+
+#[test]
+fn trace_state_survives_parent_child_hop() {
+    let (_tracer, provider, exporter, subscriber) = test_tracer();
+    let propagator = test_propagator();
+    let cx = propagator.extract(&test_carrier());
+    let mut outgoing_req_carrier = HashMap::new();
+
+    tracing::subscriber::with_default(subscriber, || {
+        let root = tracing::debug_span!("root");
+        root.set_parent(cx);
+        let child = tracing::debug_span!(parent: &root, "child");
+        let grandchild = tracing::debug_span!(parent: &child, "grandchild");
+        grandchild.inject_context(&propagator, &mut outgoing_req_carrier);
+    });
+
+    drop(provider); // flush all spans
+    let spans = exporter.0.lock().unwrap();
+    assert_eq!(spans.len(), 3);
+    for span in spans.iter() {
+        assert_eq!(span.span_context.trace_state().header(), "test1=test2");
+    }
+    assert_eq!(
+        outgoing_req_carrier.get("tracestate").map(String::as_str),
+        Some("test1=test2")
+    );
+}
+
+#[test]
+fn trace_state_survives_unsampled_hop() {
+    let provider = TracerProvider::builder()
+        .with_config(config().with_sampler(Sampler::AlwaysOff))
+        .build();
+    let subscriber =
+        tracing_subscriber::registry().with(layer().with_tracer(provider.tracer("test")));
+    let propagator = test_propagator();
+    let cx = propagator.extract(&test_carrier());
+    let mut outgoing_req_carrier = HashMap::new();
+
+    tracing::subscriber::with_default(subscriber, || {
+        let root = tracing::debug_span!("root");
+        root.set_parent(cx);
+        let child = tracing::debug_span!(parent: &root, "child");
+        child.inject_context(&propagator, &mut outgoing_req_carrier);
+    });
+
+    assert_eq!(
+        outgoing_req_carrier.get("tracestate").map(String::as_str),
+        Some("test1=test2")
+    );
+}